@@ -0,0 +1,66 @@
+// How compute_portfolio_performance scales with the number of assets (inner dot product)
+// and the number of periods (outer loop). Run with `cargo bench --bench asset_scaling`,
+// the HTML report under target/criterion plots time against each parameter.
+use athena::performance::compute_portfolio_performance;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rand::Rng;
+use std::hint::black_box;
+
+const ASSET_COUNTS: [usize; 5] = [10, 50, 100, 500, 1000];
+const PERIOD_COUNTS: [usize; 3] = [10, 100, 1000];
+
+fn random_scenario(periods: usize, assets: usize) -> Vec<Vec<f64>> {
+    let mut rng = rand::rng();
+    (0..periods)
+        .map(|_| (0..assets).map(|_| rng.random_range(-0.02..0.02)).collect())
+        .collect()
+}
+
+fn asset_scaling(c: &mut Criterion) {
+    for periods in PERIOD_COUNTS {
+        let mut group = c.benchmark_group(format!("compute_portfolio_performance/{}_periods", periods));
+        for assets in ASSET_COUNTS {
+            let returns = random_scenario(periods, assets);
+            let weights = vec![1.0 / assets as f64; assets];
+
+            group.throughput(Throughput::Elements((periods * assets) as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(assets), &assets, |b, _| {
+                b.iter(|| {
+                    compute_portfolio_performance(
+                        black_box(&returns),
+                        black_box(&weights),
+                        10_000.0,
+                        0.02,
+                        365.0,
+                    )
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+// w = [1.0] takes the single-asset fast path, w = [1.0, 0.0] computes the same returns the general way
+fn single_asset_fast_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compute_portfolio_performance/single_asset");
+    for periods in PERIOD_COUNTS {
+        let returns = random_scenario(periods, 2);
+        for (label, weights) in [("fast_path", vec![1.0]), ("general_path", vec![1.0, 0.0])] {
+            group.bench_with_input(BenchmarkId::new(label, periods), &periods, |b, _| {
+                b.iter(|| {
+                    compute_portfolio_performance(
+                        black_box(&returns),
+                        black_box(&weights),
+                        10_000.0,
+                        0.02,
+                        365.0,
+                    )
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, asset_scaling, single_asset_fast_path);
+criterion_main!(benches);
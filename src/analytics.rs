@@ -0,0 +1,255 @@
+// Statistical tests on simulation output and on realized data, e.g. VaR backtesting, and
+// sensitivities of the simulated metrics.
+use aegis_athena_contracts::simulation::{ChristoffersenTest, EvolutionConfig, KupiecTest, Portfolio};
+
+use crate::performance::{FLOAT_COMPARISON_EPSILON, compute_portfolio_performance};
+use crate::stats::normal_cdf;
+
+// Significance level at which the tests reject their null hypothesis
+const SIGNIFICANCE: f64 = 0.05;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KupiecTestResult {
+    pub lr_statistic: f64,
+    pub p_value: f64,
+    pub reject_h0: bool, // at the 5% level
+}
+
+impl From<KupiecTestResult> for KupiecTest {
+    fn from(result: KupiecTestResult) -> Self {
+        KupiecTest {
+            lr_statistic: result.lr_statistic,
+            p_value: result.p_value,
+            reject_h0: result.reject_h0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChristoffersenResult {
+    pub lr_statistic: f64,
+    pub p_value: f64,
+    pub reject_h0: bool, // at the 5% level, i.e. violations cluster
+    pub transition_counts: [[u32; 2]; 2], // [from][to], 1 = violation
+}
+
+impl From<ChristoffersenResult> for ChristoffersenTest {
+    fn from(result: ChristoffersenResult) -> Self {
+        let [[n00, n01], [n10, n11]] = result.transition_counts;
+        ChristoffersenTest {
+            lr_statistic: result.lr_statistic,
+            p_value: result.p_value,
+            reject_h0: result.reject_h0,
+            n00,
+            n01,
+            n10,
+            n11,
+        }
+    }
+}
+
+/// Upper tail of a chi-squared with one degree of freedom: P(X > x) = 2 (1 - Φ(√x)).
+pub fn chi_squared_1_survival(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    (2.0 * (1.0 - normal_cdf(x.sqrt()))).clamp(0.0, 1.0)
+}
+
+// k ln(p), with the 0 ln 0 = 0 convention the likelihoods need
+fn log_likelihood_term(count: f64, probability: f64) -> f64 {
+    if count == 0.0 { 0.0 } else { count * probability.ln() }
+}
+
+/// Kupiec's proportion-of-failures test. H0: violations happen with probability
+/// `1 - confidence_level`, i.e. the VaR model is calibrated.
+/// LR = -2 ln(L_null / L_alt) ~ chi-squared(1) under H0.
+pub fn kupiec_pof_test(violations: u32, n_observations: u32, confidence_level: f64) -> KupiecTestResult {
+    if n_observations == 0 || violations > n_observations {
+        panic!(
+            "Configuration Error: {} violations out of {} observations.",
+            violations, n_observations
+        );
+    }
+    if confidence_level.is_nan() || confidence_level <= 0.0 || confidence_level >= 1.0 {
+        panic!(
+            "Configuration Error: confidence_level must be in (0, 1) (found {}).",
+            confidence_level
+        );
+    }
+
+    let x = f64::from(violations);
+    let t = f64::from(n_observations);
+    let expected_rate = 1.0 - confidence_level;
+    let observed_rate = x / t;
+
+    let log_null = log_likelihood_term(t - x, 1.0 - expected_rate) + log_likelihood_term(x, expected_rate);
+    let log_alt = log_likelihood_term(t - x, 1.0 - observed_rate) + log_likelihood_term(x, observed_rate);
+    let lr_statistic = (-2.0 * (log_null - log_alt)).max(0.0);
+    let p_value = chi_squared_1_survival(lr_statistic);
+
+    KupiecTestResult {
+        lr_statistic,
+        p_value,
+        reject_h0: p_value < SIGNIFICANCE,
+    }
+}
+
+/// Christoffersen's independence test. H0: violations follow an iid Bernoulli process. The
+/// alternative is a first-order Markov chain where a violation today changes the odds of one
+/// tomorrow, which is what a model that misses volatility clustering produces.
+/// LR = -2 ln(L(π) / L(π01, π11)) ~ chi-squared(1) under H0.
+pub fn christoffersen_independence_test(violation_sequence: &[bool]) -> ChristoffersenResult {
+    if violation_sequence.len() < 2 {
+        panic!(
+            "Configuration Error: the independence test needs at least 2 observations (found {}).",
+            violation_sequence.len()
+        );
+    }
+
+    let mut transition_counts = [[0u32; 2]; 2];
+    for pair in violation_sequence.windows(2) {
+        transition_counts[pair[0] as usize][pair[1] as usize] += 1;
+    }
+    let [[n00, n01], [n10, n11]] = transition_counts.map(|row| row.map(f64::from));
+
+    let pi_01 = if n00 + n01 > 0.0 { n01 / (n00 + n01) } else { 0.0 };
+    let pi_11 = if n10 + n11 > 0.0 { n11 / (n10 + n11) } else { 0.0 };
+    let pi = (n01 + n11) / (n00 + n01 + n10 + n11);
+
+    let log_null = log_likelihood_term(n00 + n10, 1.0 - pi) + log_likelihood_term(n01 + n11, pi);
+    let log_alt = log_likelihood_term(n00, 1.0 - pi_01)
+        + log_likelihood_term(n01, pi_01)
+        + log_likelihood_term(n10, 1.0 - pi_11)
+        + log_likelihood_term(n11, pi_11);
+    let lr_statistic = (-2.0 * (log_null - log_alt)).max(0.0);
+    let p_value = chi_squared_1_survival(lr_statistic);
+
+    ChristoffersenResult {
+        lr_statistic,
+        p_value,
+        reject_h0: p_value < SIGNIFICANCE,
+        transition_counts,
+    }
+}
+
+/// Finite-difference sensitivity of the Sharpe ratio to asset `asset_idx`'s weight:
+/// `(Sharpe(w + epsilon e_i) - Sharpe(w)) / epsilon`, both evaluated on the same `returns`.
+/// The bumped weights are not renormalized, the extra `epsilon` is extra exposure.
+pub fn marginal_sharpe(
+    portfolio: &Portfolio,
+    asset_idx: usize,
+    epsilon: f64,
+    returns: &[Vec<f64>],
+    config: &EvolutionConfig,
+) -> f64 {
+    if asset_idx >= portfolio.weights.len() {
+        panic!(
+            "Configuration Error: asset {} is out of range for a portfolio of {} assets.",
+            asset_idx,
+            portfolio.weights.len()
+        );
+    }
+    if !epsilon.is_finite() || epsilon.abs() < FLOAT_COMPARISON_EPSILON {
+        panic!("Configuration Error: epsilon must be finite and non-zero (found {}).", epsilon);
+    }
+
+    let sharpe = |weights: &[f64]| {
+        compute_portfolio_performance(
+            returns,
+            weights,
+            config.money_to_invest,
+            config.risk_free_rate,
+            config.time_horizon_in_days,
+        )
+        .sharpe_ratio
+    };
+    let mut bumped = portfolio.weights.clone();
+    bumped[asset_idx] += epsilon;
+    (sharpe(&bumped) - sharpe(&portfolio.weights)) / epsilon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kupiec_matches_the_likelihood_ratio() {
+        // 250 days at 99%: 2.5 violations expected
+        let calibrated = kupiec_pof_test(2, 250, 0.99);
+        assert!(!calibrated.reject_h0);
+
+        let too_many = kupiec_pof_test(10, 250, 0.99);
+        let log_null = 240.0 * 0.99_f64.ln() + 10.0 * 0.01_f64.ln();
+        let log_alt = 240.0 * 0.96_f64.ln() + 10.0 * 0.04_f64.ln();
+        assert!((too_many.lr_statistic - (-2.0 * (log_null - log_alt))).abs() < 1e-9);
+        assert!(too_many.p_value < 0.001);
+        assert!(too_many.reject_h0);
+    }
+
+    #[test]
+    fn christoffersen_rejects_clustered_violations() {
+        // the same 10 violations out of 100, in two runs of 5 or spread out evenly
+        let clustered: Vec<bool> = (0..100).map(|i| (50..55).contains(&i) || (80..85).contains(&i)).collect();
+        let spread: Vec<bool> = (0..100).map(|i| i % 10 == 5).collect();
+
+        let result = christoffersen_independence_test(&clustered);
+        assert_eq!(result.transition_counts, [[87, 2], [2, 8]]);
+        assert!(result.reject_h0);
+        assert!(!christoffersen_independence_test(&spread).reject_h0);
+    }
+
+    #[test]
+    fn the_highest_marginal_sharpe_is_the_best_asset_to_add() {
+        let returns: Vec<Vec<f64>> = [
+            [0.02, -0.01, 0.005],
+            [-0.03, 0.02, 0.01],
+            [0.04, 0.00, -0.02],
+            [0.01, -0.02, 0.015],
+            [-0.01, 0.03, 0.0],
+            [0.025, 0.01, -0.005],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|simple: &f64| simple.ln_1p()).collect())
+        .collect();
+        let config = EvolutionConfig {
+            money_to_invest: 1_000.0,
+            risk_free_rate: 0.02,
+            time_horizon_in_days: 365.0,
+            ..Default::default()
+        };
+        let portfolio = Portfolio {
+            weights: vec![0.4, 0.3, 0.3],
+            ..Default::default()
+        };
+        let sharpe = |weights: &[f64]| {
+            compute_portfolio_performance(&returns, weights, 1_000.0, 0.02, 365.0).sharpe_ratio
+        };
+        let argmax = |values: &[f64]| {
+            (0..values.len())
+                .max_by(|&a, &b| values[a].total_cmp(&values[b]))
+                .unwrap()
+        };
+
+        let marginals: Vec<f64> = (0..3)
+            .map(|asset| marginal_sharpe(&portfolio, asset, 1e-6, &returns, &config))
+            .collect();
+        // the Sharpe actually reached by adding 1% of each asset
+        let bumped: Vec<f64> = (0..3)
+            .map(|asset| {
+                let mut weights = portfolio.weights.clone();
+                weights[asset] += 0.01;
+                sharpe(&weights)
+            })
+            .collect();
+        assert_eq!(argmax(&marginals), argmax(&bumped));
+        // and the marginals rank the others the same way too
+        let ranking = |values: &[f64]| {
+            let mut assets: Vec<usize> = (0..values.len()).collect();
+            assets.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+            assets
+        };
+        assert_eq!(ranking(&marginals), ranking(&bumped));
+        assert!(bumped.iter().all(|reached| *reached > sharpe(&portfolio.weights)));
+    }
+}
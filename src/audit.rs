@@ -0,0 +1,169 @@
+// Scenario audit log, so a risk number can be reproduced from the exact scenarios behind it.
+//
+// Every sampled scenario is sent over a channel to a background task that appends it, MessagePack
+// encoded, to one file per UTC hour (`scenarios-YYYYMMDDTHH.msgpack`). The simulation thread only
+// pays for a clone and a channel send, never for file I/O. The channel is bounded: when the disk
+// can't keep up, scenarios are dropped (and counted) rather than piling up in memory.
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aegis_athena_contracts::simulation::SimulationScenario;
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tracing::warn;
+
+const SECONDS_PER_HOUR: u64 = 3_600;
+// Scenarios waiting for the writer, a few seconds' worth for a busy server
+pub const DEFAULT_AUDIT_QUEUE_CAPACITY: usize = 10_000;
+// Drops are logged on the first one and then every this many, not one line each
+const DROP_WARNING_INTERVAL: u64 = 1_000;
+
+pub fn current_hour() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / SECONDS_PER_HOUR)
+}
+
+/// File that scenarios sampled during `hour` (hours since the Unix epoch) are written to.
+pub fn log_file_name(hour: u64) -> String {
+    let days = (hour / 24) as i64;
+    let (year, month, day) = civil_from_days(days);
+    format!("scenarios-{:04}{:02}{:02}T{:02}.msgpack", year, month, day, hour % 24)
+}
+
+// Days since 1970-01-01 -> (year, month, day), Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+pub struct AuditLog {
+    directory: PathBuf,
+    sender: Sender<(u64, SimulationScenario)>,
+    dropped: AtomicU64,
+}
+
+impl AuditLog {
+    /// Starts the writer task with room for `queue_capacity` pending scenarios, must be called
+    /// from within the tokio runtime.
+    pub fn spawn(directory: PathBuf, queue_capacity: usize) -> Self {
+        if queue_capacity == 0 {
+            panic!("Configuration Error: the audit log queue needs room for at least one scenario.");
+        }
+        let (sender, receiver) = channel(queue_capacity);
+        tokio::spawn(write_scenarios(directory.clone(), receiver));
+        AuditLog {
+            directory,
+            sender,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Queues `scenario` for writing, never blocks: with the queue full the scenario is dropped
+    /// and counted. Returns the hour it is (or would have been) filed under.
+    pub fn record(&self, scenario: SimulationScenario) -> u64 {
+        let hour = current_hour();
+        match self.sender.try_send((hour, scenario)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped == 1 || dropped % DROP_WARNING_INTERVAL == 0 {
+                    warn!(dropped, "audit log queue is full, scenarios are not being recorded");
+                }
+            }
+            Err(TrySendError::Closed(_)) => warn!("audit log writer has stopped, scenario not recorded"),
+        }
+        hour
+    }
+
+    /// Scenarios dropped because the writer couldn't keep up, since startup.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Log files covering `first_hour..=last_hour`, comma separated (for response metadata).
+    pub fn files_between(&self, first_hour: u64, last_hour: u64) -> String {
+        (first_hour..=last_hour.max(first_hour))
+            .map(|hour| self.directory.join(log_file_name(hour)).display().to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+async fn write_scenarios(directory: PathBuf, mut receiver: Receiver<(u64, SimulationScenario)>) {
+    let mut current: Option<(u64, BufWriter<tokio::fs::File>)> = None;
+
+    while let Some((hour, scenario)) = receiver.recv().await {
+        // Rotate on the first scenario of a new hour
+        if current.as_ref().map(|(open_hour, _)| *open_hour) != Some(hour) {
+            if let Some((_, mut writer)) = current.take() {
+                if let Err(e) = writer.flush().await {
+                    warn!("failed to flush audit log: {}", e);
+                }
+            }
+            let path = directory.join(log_file_name(hour));
+            match OpenOptions::new().create(true).append(true).open(&path).await {
+                Ok(file) => current = Some((hour, BufWriter::new(file))),
+                Err(e) => {
+                    warn!("failed to open audit log {}: {}", path.display(), e);
+                    continue;
+                }
+            }
+        }
+        let Some((_, writer)) = current.as_mut() else {
+            continue;
+        };
+
+        match rmp_serde::to_vec(&scenario) {
+            Ok(bytes) => {
+                if let Err(e) = writer.write_all(&bytes).await {
+                    warn!("failed to write audit log: {}", e);
+                }
+            }
+            Err(e) => warn!("failed to encode scenario for the audit log: {}", e),
+        }
+        // Flush whenever we catch up, so little is lost if the process dies
+        if receiver.is_empty() {
+            if let Err(e) = writer.flush().await {
+                warn!("failed to flush audit log: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // current-thread runtime: the writer task doesn't get to run before the test returns
+    #[tokio::test]
+    async fn full_queue_drops_and_counts() {
+        let audit_log = AuditLog::spawn(std::env::temp_dir().join("athena-audit-test"), 1);
+        for _ in 0..3 {
+            audit_log.record(SimulationScenario::default());
+        }
+        assert_eq!(audit_log.dropped(), 2);
+    }
+
+    #[test]
+    fn file_names_follow_the_utc_calendar() {
+        // 2024-02-29T13 is hour 474_781 since the epoch
+        assert_eq!(log_file_name(474_781), "scenarios-20240229T13.msgpack");
+        assert_eq!(log_file_name(0), "scenarios-19700101T00.msgpack");
+    }
+}
@@ -0,0 +1,157 @@
+// TTL cache for whole simulation results, so scheduled jobs re-sending the exact same
+// request don't pay for a fresh Monte Carlo run every time.
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use prost::Message;
+
+/// Hash of the protobuf encoding, i.e. two requests collide only if they are field-for-field equal.
+pub fn request_hash<M: Message>(request: &M) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    request.encode_to_vec().hash(&mut hasher);
+    hasher.finish()
+}
+
+// Used by ResultCache::default, a few thousand batch results at most
+pub const DEFAULT_RESULT_CACHE_ENTRIES: usize = 1024;
+
+pub struct ResultCache<V> {
+    entries: DashMap<u64, CacheEntry<V>>,
+    max_entries: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CacheEntry<V> {
+    inserted: Instant,
+    expires: Instant, // for purge_expired, lookups still apply their own ttl
+    value: V,
+}
+
+impl<V> Default for ResultCache<V> {
+    fn default() -> Self {
+        ResultCache::new(DEFAULT_RESULT_CACHE_ENTRIES)
+    }
+}
+
+impl<V> ResultCache<V> {
+    pub fn new(max_entries: usize) -> Self {
+        if max_entries == 0 {
+            panic!("Configuration Error: the result cache needs room for at least one entry.");
+        }
+        ResultCache {
+            entries: DashMap::new(),
+            max_entries,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Drops every entry past the ttl it was inserted with, returns how many went. Lookups only
+    /// evict the entry they hit, this is for the ones nobody asks for again.
+    pub fn purge_expired(&self) -> usize {
+        let before = self.entries.len();
+        let now = Instant::now();
+        self.entries.retain(|_, entry| entry.expires > now);
+        before.saturating_sub(self.entries.len())
+    }
+}
+
+impl<V: Clone> ResultCache<V> {
+    /// Cached value for `key` if it was stored less than `ttl` ago. Stale entries are evicted.
+    pub fn get_fresh(&self, key: u64, ttl: Duration) -> Option<V> {
+        let fresh = self
+            .entries
+            .get(&key)
+            .filter(|entry| entry.inserted.elapsed() < ttl)
+            .map(|entry| entry.value.clone());
+        if fresh.is_none() {
+            self.entries.remove(&key);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        fresh
+    }
+
+    /// Stores `value` until `ttl` from now. A full cache first drops its expired entries, then
+    /// the oldest one. Concurrent inserts can overshoot `max_entries` by the number of writers.
+    pub fn insert(&self, key: u64, value: V, ttl: Duration) {
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.max_entries && self.purge_expired() == 0 {
+            // linear scan, fine for the few thousand entries a cache holds
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|entry| entry.inserted)
+                .map(|entry| *entry.key());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        let inserted = Instant::now();
+        self.entries.insert(key, CacheEntry {
+            inserted,
+            expires: inserted + ttl,
+            value,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Fraction of lookups served from the cache since startup, 0 before the first lookup.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let lookups = hits + self.misses.load(Ordering::Relaxed);
+        if lookups == 0 { 0.0 } else { hits as f64 / lookups as f64 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn full_cache_evicts_the_oldest_entry() {
+        let cache = ResultCache::new(2);
+        cache.insert(1, "a", TTL);
+        cache.insert(2, "b", TTL);
+        cache.insert(3, "c", TTL);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get_fresh(1, TTL), None);
+        assert_eq!(cache.get_fresh(3, TTL), Some("c"));
+        // replacing a key that is already there doesn't evict anything
+        cache.insert(3, "d", TTL);
+        assert_eq!(cache.get_fresh(2, TTL), Some("b"));
+    }
+
+    #[test]
+    fn purge_drops_only_expired_entries() {
+        let cache = ResultCache::new(10);
+        cache.insert(1, "expired", Duration::ZERO);
+        cache.insert(2, "fresh", TTL);
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get_fresh(2, TTL), Some("fresh"));
+    }
+
+    #[test]
+    fn expired_entries_make_room_before_live_ones() {
+        let cache = ResultCache::new(2);
+        cache.insert(1, "fresh", TTL);
+        cache.insert(2, "expired", Duration::ZERO);
+        cache.insert(3, "new", TTL);
+        assert_eq!(cache.get_fresh(1, TTL), Some("fresh"));
+        assert_eq!(cache.get_fresh(3, TTL), Some("new"));
+    }
+}
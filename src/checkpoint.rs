@@ -0,0 +1,56 @@
+// Checkpoints of a long batch's running sums, so a crash only costs the iterations since the last
+// one. Only the core sums are kept: the optional ones (hedging, insurance, custom metrics, ...)
+// of a resumed batch cover the iterations run after the resume.
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchCheckpoint {
+    pub sum_returns: Vec<f64>,
+    pub sum_vols: Vec<f64>,
+    pub sum_sharpes: Vec<f64>,
+    pub completed_iterations: u32,
+}
+
+// Plain tuple on disk, bincode serializes it without any derive
+type Encoded = (Vec<f64>, Vec<f64>, Vec<f64>, u32);
+
+impl BatchCheckpoint {
+    pub fn encode(&self) -> Vec<u8> {
+        let encoded: (&[f64], &[f64], &[f64], u32) =
+            (&self.sum_returns, &self.sum_vols, &self.sum_sharpes, self.completed_iterations);
+        bincode::serialize(&encoded).expect("plain vectors always serialize")
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        let (sum_returns, sum_vols, sum_sharpes, completed_iterations): Encoded = bincode::deserialize(bytes)?;
+        Ok(BatchCheckpoint {
+            sum_returns,
+            sum_vols,
+            sum_sharpes,
+            completed_iterations,
+        })
+    }
+}
+
+/// Where the checkpoints of `job_id` go. Ids are client-supplied, anything that could escape
+/// `directory` is replaced.
+pub fn checkpoint_file(directory: &Path, job_id: &str) -> PathBuf {
+    let file_name: String = job_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    directory.join(format!("{}.checkpoint", file_name))
+}
+
+/// Called from the blocking batch loop, hence std::fs. Write then rename, so a crash mid-write
+/// leaves the previous checkpoint intact.
+pub fn save_checkpoint(checkpoint: &BatchCheckpoint, path: &Path) -> std::io::Result<()> {
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, checkpoint.encode())?;
+    std::fs::rename(&partial, path)
+}
+
+pub async fn load_checkpoint(path: &Path) -> Result<BatchCheckpoint, Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = tokio::fs::read(path).await?;
+    Ok(BatchCheckpoint::decode(&bytes)?)
+}
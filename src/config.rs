@@ -0,0 +1,456 @@
+// Up-front validation of `EvolutionConfig`, so a bad configuration is rejected before we
+// deserialize anything or start sampling (compute_portfolio_performance would panic much later).
+use std::fmt;
+
+use aegis_athena_contracts::simulation::{EvolutionConfig, Portfolio, SimulationMode};
+
+use crate::performance::FLOAT_COMPARISON_EPSILON;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    NonPositiveTimeHorizon(f64),
+    NonFiniteRiskFreeRate(f64),
+    MoneyToInvestTooSmall(f64),
+    InvalidAnnualizationBasis(f64),
+    TooFewPeriodsToSample(u32),
+    RollingWindowTooLarge { rolling_window: u32, periods_to_sample: u32 },
+    BlockSizeTooLarge { block_size: u32, periods_to_sample: u32 },
+    InvalidCrisisProbability(f64),
+    InvalidCrisisCorrelation(f64),
+    NonPositiveWinsorizeLimit(f64),
+    InvalidRebalancingBand(f64),
+    InvalidMinPositionSize(f64),
+    InvalidInterIterationCorrelation(f64),
+    InvalidProtectivePut { protection_level: f64, put_cost_annual: f64 },
+    InvalidDcaSchedule { n_installments: u32, installment_frequency_periods: u32 },
+    ZeroCheckpointInterval,
+    InvalidVarianceGamma { sigma: f64, nu: f64, dt: f64, n_assets: u32 },
+    InvalidStable { alpha: f64, beta: f64, scale: f64 },
+    NegativeKdeBandwidth(f64),
+    HedgeLengthMismatch { hedge_weights: usize, hedge_asset_indices: usize },
+    HedgeAssetOutOfRange { index: u32, dimension: usize },
+    NonFiniteHedgeRatio(f64),
+    NonFiniteBondSensitivity { index: u32, duration: f64, convexity: f64 },
+    FixedIncomeAssetOutOfRange { index: u32, dimension: usize },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::NonPositiveTimeHorizon(days) => {
+                write!(f, "time_horizon_in_days must be > 0 (found {})", days)
+            }
+            ConfigError::NonFiniteRiskFreeRate(rate) => {
+                write!(f, "risk_free_rate must be finite (found {})", rate)
+            }
+            ConfigError::MoneyToInvestTooSmall(money) => write!(
+                f,
+                "money_to_invest must be greater than {} (found {})",
+                FLOAT_COMPARISON_EPSILON, money
+            ),
+            ConfigError::InvalidAnnualizationBasis(basis) => write!(
+                f,
+                "annualization_basis must be a number of days in (0, 366] (found {})",
+                basis
+            ),
+            ConfigError::TooFewPeriodsToSample(periods) => write!(
+                f,
+                "periods_to_sample must be >= 2 for the selected sampler, a volatility needs two periods (found {})",
+                periods
+            ),
+            ConfigError::RollingWindowTooLarge {
+                rolling_window,
+                periods_to_sample,
+            } => write!(
+                f,
+                "rolling_window ({}) cannot exceed periods_to_sample ({})",
+                rolling_window, periods_to_sample
+            ),
+            ConfigError::BlockSizeTooLarge {
+                block_size,
+                periods_to_sample,
+            } => write!(
+                f,
+                "bootstrap_block_size ({}) must be smaller than periods_to_sample ({})",
+                block_size, periods_to_sample
+            ),
+            ConfigError::InvalidCrisisProbability(p) => {
+                write!(f, "correlation_stress.crisis_probability must be in [0, 1] (found {})", p)
+            }
+            ConfigError::InvalidCrisisCorrelation(rho) => {
+                write!(f, "correlation_stress.crisis_correlation must be in [-1, 1] (found {})", rho)
+            }
+            ConfigError::NonPositiveWinsorizeLimit(limit) => {
+                write!(f, "winsorize must be a finite log-return > 0 (found {})", limit)
+            }
+            ConfigError::InvalidRebalancingBand(threshold) => {
+                write!(f, "rebalancing_band must be a weight deviation in (0, 1] (found {})", threshold)
+            }
+            ConfigError::InvalidMinPositionSize(size) => {
+                write!(f, "min_position_size must be a weight in (0, 1) (found {})", size)
+            }
+            ConfigError::InvalidInterIterationCorrelation(rho) => {
+                write!(f, "inter_iteration_correlation must be in (-1, 1) (found {})", rho)
+            }
+            ConfigError::InvalidProtectivePut {
+                protection_level,
+                put_cost_annual,
+            } => write!(
+                f,
+                "protective_put needs a finite protection_level and a finite put_cost_annual >= 0 (found {} and {})",
+                protection_level, put_cost_annual
+            ),
+            ConfigError::InvalidDcaSchedule {
+                n_installments,
+                installment_frequency_periods,
+            } => write!(
+                f,
+                "dca needs n_installments and installment_frequency_periods >= 1 (found {} and {})",
+                n_installments, installment_frequency_periods
+            ),
+            ConfigError::ZeroCheckpointInterval => write!(f, "checkpoint_every_n must be >= 1 when set"),
+            ConfigError::InvalidVarianceGamma { sigma, nu, dt, n_assets } => write!(
+                f,
+                "variance_gamma needs sigma >= 0, nu > 0, dt > 0 and n_assets >= 1 (found {}, {}, {} and {})",
+                sigma, nu, dt, n_assets
+            ),
+            ConfigError::InvalidStable { alpha, beta, scale } => write!(
+                f,
+                "stable needs alpha in (0, 2], beta in [-1, 1], scale > 0 and a finite location (found {}, {} and {})",
+                alpha, beta, scale
+            ),
+            ConfigError::NegativeKdeBandwidth(bandwidth) => {
+                write!(f, "kde.bandwidth must be a finite log-return >= 0 (found {})", bandwidth)
+            }
+            ConfigError::HedgeLengthMismatch {
+                hedge_weights,
+                hedge_asset_indices,
+            } => write!(
+                f,
+                "hedge has {} hedge_weights for {} hedge_asset_indices",
+                hedge_weights, hedge_asset_indices
+            ),
+            ConfigError::HedgeAssetOutOfRange { index, dimension } => write!(
+                f,
+                "hedge_asset_indices contains {} but the sampler only produces {} assets",
+                index, dimension
+            ),
+            ConfigError::NonFiniteHedgeRatio(ratio) => {
+                write!(f, "hedge.hedge_ratio must be finite (found {})", ratio)
+            }
+            ConfigError::NonFiniteBondSensitivity {
+                index,
+                duration,
+                convexity,
+            } => write!(
+                f,
+                "fixed_income_assets entry for asset {} needs a finite duration and convexity (found {} and {})",
+                index, duration, convexity
+            ),
+            ConfigError::FixedIncomeAssetOutOfRange { index, dimension } => write!(
+                f,
+                "fixed_income_assets references asset {} but the sampler only produces {} assets",
+                index, dimension
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub trait ValidateConfig {
+    /// Collects every problem instead of stopping at the first one, so they can all be fixed at once.
+    fn validate(&self) -> Result<(), Vec<ConfigError>>;
+
+    /// The checks that need the number of assets per period, once the sampler is known.
+    fn validate_for_dimension(&self, dimension: usize) -> Result<(), Vec<ConfigError>>;
+}
+
+impl ValidateConfig for EvolutionConfig {
+    fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        // NaN fails every comparison, hence the explicit is_nan checks
+        if self.time_horizon_in_days.is_nan() || self.time_horizon_in_days <= 0.0 {
+            errors.push(ConfigError::NonPositiveTimeHorizon(self.time_horizon_in_days));
+        }
+        if !self.risk_free_rate.is_finite() {
+            errors.push(ConfigError::NonFiniteRiskFreeRate(self.risk_free_rate));
+        }
+        if !self.money_to_invest.is_finite() || self.money_to_invest <= FLOAT_COMPARISON_EPSILON {
+            errors.push(ConfigError::MoneyToInvestTooSmall(self.money_to_invest));
+        }
+        // 0 means unset (proto default), everything else has to look like a day count
+        if self.annualization_basis != 0.0 && !(0.0..=366.0).contains(&self.annualization_basis) {
+            errors.push(ConfigError::InvalidAnnualizationBasis(self.annualization_basis));
+        }
+        if samples_periods(self) && self.periods_to_sample < 2 {
+            errors.push(ConfigError::TooFewPeriodsToSample(self.periods_to_sample));
+        }
+        if self.rolling_window > self.periods_to_sample {
+            errors.push(ConfigError::RollingWindowTooLarge {
+                rolling_window: self.rolling_window,
+                periods_to_sample: self.periods_to_sample,
+            });
+        }
+        if self.bootstrap_block_size != 0 && self.bootstrap_block_size >= self.periods_to_sample {
+            errors.push(ConfigError::BlockSizeTooLarge {
+                block_size: self.bootstrap_block_size,
+                periods_to_sample: self.periods_to_sample,
+            });
+        }
+        if let Some(stress) = &self.correlation_stress {
+            if !(0.0..=1.0).contains(&stress.crisis_probability) {
+                errors.push(ConfigError::InvalidCrisisProbability(stress.crisis_probability));
+            }
+            if !(-1.0..=1.0).contains(&stress.crisis_correlation) {
+                errors.push(ConfigError::InvalidCrisisCorrelation(stress.crisis_correlation));
+            }
+        }
+        if let Some(limit) = self.winsorize {
+            if !limit.is_finite() || limit <= 0.0 {
+                errors.push(ConfigError::NonPositiveWinsorizeLimit(limit));
+            }
+        }
+        if let Some(threshold) = self.rebalancing_band {
+            if threshold.is_nan() || threshold <= 0.0 || threshold > 1.0 {
+                errors.push(ConfigError::InvalidRebalancingBand(threshold));
+            }
+        }
+        if let Some(size) = self.min_position_size {
+            if size.is_nan() || size <= 0.0 || size >= 1.0 {
+                errors.push(ConfigError::InvalidMinPositionSize(size));
+            }
+        }
+        if let Some(rho) = self.inter_iteration_correlation {
+            if rho.is_nan() || rho.abs() >= 1.0 {
+                errors.push(ConfigError::InvalidInterIterationCorrelation(rho));
+            }
+        }
+        if let Some(put) = &self.protective_put {
+            let valid_cost = put.put_cost_annual.is_finite() && put.put_cost_annual >= 0.0;
+            if !put.protection_level.is_finite() || !valid_cost {
+                errors.push(ConfigError::InvalidProtectivePut {
+                    protection_level: put.protection_level,
+                    put_cost_annual: put.put_cost_annual,
+                });
+            }
+        }
+        if let Some(dca) = &self.dca {
+            if dca.n_installments == 0 || dca.installment_frequency_periods == 0 {
+                errors.push(ConfigError::InvalidDcaSchedule {
+                    n_installments: dca.n_installments,
+                    installment_frequency_periods: dca.installment_frequency_periods,
+                });
+            }
+        }
+
+        if let Some(vg) = &self.variance_gamma {
+            let valid = vg.sigma >= 0.0 && vg.nu > 0.0 && vg.dt > 0.0 && vg.theta.is_finite() && vg.n_assets > 0;
+            if !valid {
+                errors.push(ConfigError::InvalidVarianceGamma {
+                    sigma: vg.sigma,
+                    nu: vg.nu,
+                    dt: vg.dt,
+                    n_assets: vg.n_assets,
+                });
+            }
+        }
+
+        if let Some(stable) = &self.stable {
+            let valid = stable.alpha > 0.0
+                && stable.alpha <= 2.0
+                && (-1.0..=1.0).contains(&stable.beta)
+                && stable.scale > 0.0
+                && stable.location.is_finite();
+            if !valid {
+                errors.push(ConfigError::InvalidStable {
+                    alpha: stable.alpha,
+                    beta: stable.beta,
+                    scale: stable.scale,
+                });
+            }
+        }
+
+        if let Some(bandwidth) = self.kde.as_ref().and_then(|kde| kde.bandwidth) {
+            if !bandwidth.is_finite() || bandwidth < 0.0 {
+                errors.push(ConfigError::NegativeKdeBandwidth(bandwidth));
+            }
+        }
+
+        if let Some(hedge) = &self.hedge {
+            if hedge.hedge_weights.len() != hedge.hedge_asset_indices.len() {
+                errors.push(ConfigError::HedgeLengthMismatch {
+                    hedge_weights: hedge.hedge_weights.len(),
+                    hedge_asset_indices: hedge.hedge_asset_indices.len(),
+                });
+            }
+            if !hedge.hedge_ratio.is_finite() {
+                errors.push(ConfigError::NonFiniteHedgeRatio(hedge.hedge_ratio));
+            }
+        }
+
+        if self.checkpoint_every_n == Some(0) {
+            errors.push(ConfigError::ZeroCheckpointInterval);
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    fn validate_for_dimension(&self, dimension: usize) -> Result<(), Vec<ConfigError>> {
+        let errors: Vec<ConfigError> = self
+            .hedge
+            .iter()
+            .flat_map(|hedge| &hedge.hedge_asset_indices)
+            .filter(|&&index| index as usize >= dimension)
+            .map(|&index| ConfigError::HedgeAssetOutOfRange { index, dimension })
+            .collect();
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+// Whether the selected sampler draws periods_to_sample periods itself. The server's default
+// sampler and scenario replays bring their own period counts.
+fn samples_periods(config: &EvolutionConfig) -> bool {
+    if config.variance_gamma.is_some() || config.stable.is_some() || config.kde.is_some() {
+        return true;
+    }
+    match SimulationMode::try_from(config.simulation_mode) {
+        Ok(SimulationMode::MonteCarlo) => config.distribution_params.is_some(),
+        Ok(SimulationMode::QuasiMonteCarlo | SimulationMode::Bootstrap | SimulationMode::Hybrid) => true,
+        Ok(SimulationMode::HistoricalReplay) | Err(_) => false,
+    }
+}
+
+// Only the bond sleeves need checking, the weights are matched to the sampler by the service
+impl ValidateConfig for Portfolio {
+    fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let errors: Vec<ConfigError> = self
+            .fixed_income_assets
+            .iter()
+            .filter(|bond| !bond.duration.is_finite() || !bond.convexity.is_finite())
+            .map(|bond| ConfigError::NonFiniteBondSensitivity {
+                index: bond.asset_index,
+                duration: bond.duration,
+                convexity: bond.convexity,
+            })
+            .collect();
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    fn validate_for_dimension(&self, dimension: usize) -> Result<(), Vec<ConfigError>> {
+        let errors: Vec<ConfigError> = self
+            .fixed_income_assets
+            .iter()
+            .filter(|bond| bond.asset_index as usize >= dimension)
+            .map(|bond| ConfigError::FixedIncomeAssetOutOfRange {
+                index: bond.asset_index,
+                dimension,
+            })
+            .collect();
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// All errors on one line, for a `Status` message.
+pub fn describe_errors(errors: &[ConfigError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use aegis_athena_contracts::simulation::{FixedIncomeSpec, HedgeConfig};
+
+    use super::*;
+
+    fn hedged_config(hedge_asset_indices: Vec<u32>, hedge_weights: Vec<f64>) -> EvolutionConfig {
+        EvolutionConfig {
+            hedge: Some(HedgeConfig {
+                hedge_asset_indices,
+                hedge_weights,
+                hedge_ratio: 1.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hedge_indices_are_checked_against_the_dimension() {
+        let config = hedged_config(vec![0, 3], vec![0.5, 0.5]);
+        assert_eq!(config.validate_for_dimension(4), Ok(()));
+        assert_eq!(
+            config.validate_for_dimension(3),
+            Err(vec![ConfigError::HedgeAssetOutOfRange { index: 3, dimension: 3 }])
+        );
+    }
+
+    #[test]
+    fn bond_indices_are_checked_against_the_dimension() {
+        let portfolio = Portfolio {
+            weights: vec![0.5, 0.5],
+            fixed_income_assets: vec![FixedIncomeSpec {
+                asset_index: 2,
+                duration: 5.0,
+                convexity: 30.0,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(portfolio.validate(), Ok(()));
+        assert_eq!(portfolio.validate_for_dimension(3), Ok(()));
+        assert_eq!(
+            portfolio.validate_for_dimension(2),
+            Err(vec![ConfigError::FixedIncomeAssetOutOfRange { index: 2, dimension: 2 }])
+        );
+    }
+
+    #[test]
+    fn samplers_need_two_periods() {
+        let bootstrap = EvolutionConfig {
+            simulation_mode: SimulationMode::Bootstrap as i32,
+            periods_to_sample: 1,
+            ..Default::default()
+        };
+        assert!(
+            bootstrap
+                .validate()
+                .unwrap_err()
+                .contains(&ConfigError::TooFewPeriodsToSample(1))
+        );
+        // replays bring their own periods, periods_to_sample is left at its default
+        let replay = EvolutionConfig {
+            simulation_mode: SimulationMode::HistoricalReplay as i32,
+            ..Default::default()
+        };
+        assert!(
+            !replay
+                .validate()
+                .unwrap_err()
+                .contains(&ConfigError::TooFewPeriodsToSample(0))
+        );
+    }
+
+    #[test]
+    fn hedge_ratio_must_be_finite() {
+        let mut config = hedged_config(vec![0], vec![1.0]);
+        config.hedge.as_mut().unwrap().hedge_ratio = f64::NAN;
+        let errors = config.validate().unwrap_err();
+        assert!(errors.iter().any(|e| matches!(e, ConfigError::NonFiniteHedgeRatio(ratio) if ratio.is_nan())));
+    }
+
+    #[test]
+    fn hedge_weights_must_match_the_indices() {
+        let errors = hedged_config(vec![0, 1], vec![1.0]).validate().unwrap_err();
+        assert!(errors.contains(&ConfigError::HedgeLengthMismatch {
+            hedge_weights: 1,
+            hedge_asset_indices: 2,
+        }));
+    }
+}
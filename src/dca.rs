@@ -0,0 +1,41 @@
+// Dollar-cost averaging: `money_to_invest` goes in as `n_installments` equal installments, one
+// every `installment_frequency_periods` periods starting with the first, instead of all at once.
+// Capital waiting for its installment sits in cash and earns nothing.
+use aegis_athena_contracts::simulation::DcaConfig;
+
+use crate::performance::PortfolioPerformance;
+
+/// Fills the DCA fields of `perf`: terminal wealth of the DCA entry, of the lump sum over the
+/// same periods, and the average entry cost (the portfolio's value index when each installment
+/// went in, starting at 1.0). Installments that would fall past the last period stay in cash.
+pub fn apply_dca(perf: &mut PortfolioPerformance, dca: &DcaConfig, money_to_invest: f64) {
+    if dca.n_installments == 0 || dca.installment_frequency_periods == 0 {
+        panic!(
+            "Configuration Error: DCA needs at least one installment and a frequency of at least one period \
+             (found {} installments every {} periods).",
+            dca.n_installments, dca.installment_frequency_periods
+        );
+    }
+
+    let installment = money_to_invest / f64::from(dca.n_installments);
+    let frequency = dca.installment_frequency_periods as usize;
+    let mut cash = money_to_invest;
+    let mut invested = 0.0;
+    let mut price = 1.0; // compounded value of 1 dollar put in at the start
+    let mut units = 0.0;
+    let mut installments_made = 0;
+
+    for (period, ret) in perf.portfolio_returns.iter().enumerate() {
+        if installments_made < dca.n_installments && period % frequency == 0 {
+            cash -= installment;
+            invested += installment;
+            units += installment / price;
+            installments_made += 1;
+        }
+        price *= 1.0 + ret / money_to_invest;
+    }
+
+    perf.dca_terminal_wealth = Some(units * price + cash);
+    perf.lump_sum_terminal_wealth = Some(money_to_invest * price);
+    perf.dca_average_entry_cost = (units > 0.0).then(|| invested / units);
+}
@@ -0,0 +1,73 @@
+// Connection draining for blue-green / rolling deployments: once draining, new batches are
+// turned away with UNAVAILABLE (clients retry against the new instance) while the ones already
+// running are allowed to finish.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tonic::Status;
+
+#[derive(Debug, Default)]
+pub struct ConnectionDrain {
+    draining: AtomicBool,
+    in_flight: AtomicU32,
+    idle: Notify, // signalled whenever in_flight drops to 0
+}
+
+/// Held for the duration of a batch, see `ConnectionDrain::enter`.
+#[derive(Debug)]
+pub struct InFlightGuard {
+    drain: Arc<ConnectionDrain>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.drain.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.drain.idle.notify_waiters();
+        }
+    }
+}
+
+impl ConnectionDrain {
+    /// Registers a new batch, or refuses it if the server is draining.
+    pub fn enter(self: &Arc<Self>) -> Result<InFlightGuard, Status> {
+        // count first, so a drain starting between the two steps still waits for us or sees us leave
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        let guard = InFlightGuard { drain: Arc::clone(self) };
+        if self.draining.load(Ordering::Acquire) {
+            return Err(Status::unavailable("Server is draining, retry on another instance"));
+        }
+        Ok(guard)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    pub fn in_flight(&self) -> u32 {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Stops accepting batches and waits up to `timeout` for the running ones to finish.
+    /// Returns how many of them finished in that time. Draining is never switched back off,
+    /// the instance is expected to shut down afterwards.
+    pub async fn drain(&self, timeout: Duration) -> u32 {
+        self.draining.store(true, Ordering::Release);
+        let running = self.in_flight();
+        let _ = tokio::time::timeout(timeout, async {
+            loop {
+                // registered before checking, so a notification in between isn't lost
+                let idle = self.idle.notified();
+                tokio::pin!(idle);
+                idle.as_mut().enable();
+                if self.in_flight() == 0 {
+                    break;
+                }
+                idle.await;
+            }
+        })
+        .await;
+        running.saturating_sub(self.in_flight())
+    }
+}
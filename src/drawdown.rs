@@ -0,0 +1,183 @@
+// Time to recover from a drawdown: start each path at (1 - drawdown) of the previous peak and
+// compound sampled portfolio returns until the peak is regained or we run out of periods.
+use rayon::prelude::*;
+
+use crate::error::SimulationError;
+use crate::performance::simple_return;
+use crate::sampling::{Scenario, ScenarioSampler};
+use crate::stats::sorted_quantile;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveryDistribution {
+    pub mean_recovery_periods: f64,   // over the paths that recovered, NaN if none did
+    pub median_recovery_periods: f64, // same
+    pub probability_of_no_recovery: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RecoveryPath {
+    Running { wealth: f64, elapsed: usize }, // wealth as a fraction of the peak
+    Recovered(usize),
+    NotRecovered,
+}
+
+impl RecoveryPath {
+    fn start(wealth: f64) -> Self {
+        if wealth >= 1.0 {
+            RecoveryPath::Recovered(0)
+        } else {
+            RecoveryPath::Running { wealth, elapsed: 0 }
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(self, RecoveryPath::Running { .. })
+    }
+
+    // Compounds the scenario's periods until the peak is regained or max_periods have passed
+    fn advance(&mut self, scenario: &Scenario, weights: &[f64], max_periods: usize) {
+        let RecoveryPath::Running { mut wealth, mut elapsed } = *self else {
+            return;
+        };
+        if scenario.is_empty() {
+            *self = RecoveryPath::NotRecovered; // nothing to compound, we'd loop forever
+            return;
+        }
+        for row in scenario.iter().take(max_periods - elapsed) {
+            let period_return = row
+                .iter()
+                .zip(weights)
+                .map(|(log_return, weight)| simple_return(*log_return) * weight)
+                .sum::<f64>();
+            wealth *= 1.0 + period_return;
+            elapsed += 1;
+            if wealth >= 1.0 {
+                *self = RecoveryPath::Recovered(elapsed);
+                return;
+            }
+        }
+        *self = if elapsed >= max_periods {
+            RecoveryPath::NotRecovered
+        } else {
+            RecoveryPath::Running { wealth, elapsed }
+        };
+    }
+}
+
+/// Periods until the portfolio is back at its peak, over `n_paths` independent paths.
+/// Scenarios are drawn back to back, so a path longer than `periods_to_sample` simply
+/// continues into the next draw. Fails only if the sampler does.
+pub fn simulate_drawdown_recovery(
+    sampler: &dyn ScenarioSampler,
+    weights: &[f64],
+    current_drawdown: f64,
+    max_recovery_periods: usize,
+    n_paths: usize,
+) -> Result<RecoveryDistribution, SimulationError> {
+    if !(0.0..1.0).contains(&current_drawdown) {
+        panic!(
+            "Configuration Error: current_drawdown must be in [0, 1) (found {}).",
+            current_drawdown
+        );
+    }
+    if weights.len() != sampler.dimension() {
+        panic!(
+            "Configuration Error: {} weights for a sampler of {} assets.",
+            weights.len(),
+            sampler.dimension()
+        );
+    }
+
+    let mut paths = vec![RecoveryPath::start(1.0 - current_drawdown); n_paths];
+    loop {
+        let running = paths.iter().filter(|path| path.is_running()).count();
+        if running == 0 {
+            break;
+        }
+        // One scenario per running path, drawn here rather than in the parallel section: samplers
+        // may block or fail (pools, retries) and rayon workers are no place for either
+        let scenarios = (0..running)
+            .map(|_| sampler.try_sample_returns())
+            .collect::<Result<Vec<Scenario>, SimulationError>>()?;
+        let work: Vec<(&mut RecoveryPath, Scenario)> =
+            paths.iter_mut().filter(|path| path.is_running()).zip(scenarios).collect();
+        work.into_par_iter()
+            .for_each(|(path, scenario)| path.advance(&scenario, weights, max_recovery_periods));
+    }
+
+    let mut recovered: Vec<f64> = paths
+        .iter()
+        .filter_map(|path| match path {
+            RecoveryPath::Recovered(periods) => Some(*periods as f64),
+            _ => None,
+        })
+        .collect();
+    recovered.sort_unstable_by(|a, b| a.total_cmp(b));
+    let probability_of_no_recovery = (n_paths - recovered.len()) as f64 / n_paths.max(1) as f64;
+    let mean_recovery_periods = if recovered.is_empty() {
+        f64::NAN
+    } else {
+        recovered.iter().sum::<f64>() / recovered.len() as f64
+    };
+
+    Ok(RecoveryDistribution {
+        mean_recovery_periods,
+        median_recovery_periods: sorted_quantile(&recovered, 0.5),
+        probability_of_no_recovery,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The same log-return every period
+    struct ConstantSampler {
+        log_return: f64,
+        periods: usize,
+    }
+
+    impl ScenarioSampler for ConstantSampler {
+        fn sample_returns(&self) -> Scenario {
+            vec![vec![self.log_return]; self.periods]
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn paths_continue_across_scenarios() {
+        // 0.8 -> 0.88 -> 0.968 -> 1.0648, over scenarios of 2 periods
+        let sampler = ConstantSampler {
+            log_return: 1.1f64.ln(),
+            periods: 2,
+        };
+        let distribution = simulate_drawdown_recovery(&sampler, &[1.0], 0.2, 10, 50).unwrap();
+        assert_eq!(distribution.mean_recovery_periods, 3.0);
+        assert_eq!(distribution.median_recovery_periods, 3.0);
+        assert_eq!(distribution.probability_of_no_recovery, 0.0);
+    }
+
+    #[test]
+    fn flat_returns_never_recover() {
+        let sampler = ConstantSampler {
+            log_return: 0.0,
+            periods: 3,
+        };
+        let distribution = simulate_drawdown_recovery(&sampler, &[1.0], 0.1, 10, 20).unwrap();
+        assert!(distribution.mean_recovery_periods.is_nan());
+        assert_eq!(distribution.probability_of_no_recovery, 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "2 weights for a sampler of 1 assets")]
+    fn weights_must_match_the_sampler() {
+        let sampler = ConstantSampler {
+            log_return: 0.0,
+            periods: 3,
+        };
+        let _ = simulate_drawdown_recovery(&sampler, &[0.5, 0.5], 0.1, 10, 20);
+    }
+}
@@ -0,0 +1,111 @@
+// Decoding of `portfolios_blob`.
+//
+// Two layouts are accepted:
+// - `Bincode`: the original format, a single bincode-serialized `Vec<Portfolio>`.
+// - `BincodeFramed`: `FRAMED_MAGIC`, a u64 LE portfolio count, then for each portfolio a
+//   u32 LE length followed by that many bytes of bincode-serialized `Portfolio`.
+//
+// The framed layout lets us decode every portfolio independently (and hence in parallel),
+// which matters once blobs reach tens of thousands of portfolios.
+// Old clients keep working since a legacy blob starts with a u64 length, not the magic.
+use std::fmt;
+
+use aegis_athena_contracts::simulation::Portfolio;
+use rayon::prelude::*;
+
+pub const FRAMED_MAGIC: &[u8; 4] = b"ATHF";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingFormat {
+    Bincode,
+    BincodeFramed,
+}
+
+impl EncodingFormat {
+    pub fn detect(blob: &[u8]) -> Self {
+        if blob.starts_with(FRAMED_MAGIC) {
+            EncodingFormat::BincodeFramed
+        } else {
+            EncodingFormat::Bincode
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Truncated { offset: usize },
+    Portfolio { index: usize, source: bincode::Error },
+    Blob(bincode::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated { offset } => {
+                write!(f, "Framed blob is truncated at byte {}", offset)
+            }
+            DecodeError::Portfolio { index, source } => {
+                write!(f, "Failed to deserialize portfolio {}: {}", index, source)
+            }
+            DecodeError::Blob(e) => write!(f, "Failed to deserialize portfolios: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub fn decode_portfolios(blob: &[u8]) -> Result<Vec<Portfolio>, DecodeError> {
+    match EncodingFormat::detect(blob) {
+        EncodingFormat::Bincode => bincode::deserialize(blob).map_err(DecodeError::Blob),
+        EncodingFormat::BincodeFramed => decode_framed(blob),
+    }
+}
+
+fn read_bytes<const N: usize>(blob: &[u8], offset: usize) -> Result<[u8; N], DecodeError> {
+    blob.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(DecodeError::Truncated { offset })
+}
+
+fn decode_framed(blob: &[u8]) -> Result<Vec<Portfolio>, DecodeError> {
+    let mut offset = FRAMED_MAGIC.len();
+    let count = u64::from_le_bytes(read_bytes::<8>(blob, offset)?) as usize;
+    offset += 8;
+
+    // Sequential pass over the frame headers only (cheap), the actual decoding is parallel
+    let mut frames = Vec::with_capacity(count.min(blob.len()));
+    for _ in 0..count {
+        let len = u32::from_le_bytes(read_bytes::<4>(blob, offset)?) as usize;
+        offset += 4;
+        let frame = blob
+            .get(offset..offset + len)
+            .ok_or(DecodeError::Truncated { offset })?;
+        frames.push(frame);
+        offset += len;
+    }
+
+    frames
+        .par_iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            bincode::deserialize(frame).map_err(|source| DecodeError::Portfolio { index, source })
+        })
+        .collect()
+}
+
+pub fn encode_portfolios_framed(portfolios: &[Portfolio]) -> Result<Vec<u8>, bincode::Error> {
+    let frames = portfolios
+        .par_iter()
+        .map(bincode::serialize)
+        .collect::<Result<Vec<Vec<u8>>, _>>()?;
+
+    let total_len = FRAMED_MAGIC.len() + 8 + frames.iter().map(|f| 4 + f.len()).sum::<usize>();
+    let mut blob = Vec::with_capacity(total_len);
+    blob.extend_from_slice(FRAMED_MAGIC);
+    blob.extend_from_slice(&(frames.len() as u64).to_le_bytes());
+    for frame in frames {
+        blob.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        blob.extend_from_slice(&frame);
+    }
+    Ok(blob)
+}
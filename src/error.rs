@@ -0,0 +1,43 @@
+// Errors that abort a whole simulation batch (as opposed to ConfigError, which is caught
+// before one starts, and per-portfolio failures under error_recovery_mode).
+use std::fmt;
+
+use tonic::Status;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationError {
+    /// Asset index, periods with a valid (non-NaN) return, periods required.
+    InsufficientAssetData(usize, u32, u32),
+    /// The sampler (or its pool) could not produce a scenario in time.
+    SamplerUnavailable(String),
+    /// The sampler kept returning scenarios without any period, this many times in a row.
+    EmptyScenarios(u32),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::InsufficientAssetData(asset, actual, required) => write!(
+                f,
+                "Asset {} has {} periods of valid returns, at least {} are required",
+                asset, actual, required
+            ),
+            SimulationError::SamplerUnavailable(reason) => write!(f, "Sampler unavailable: {}", reason),
+            SimulationError::EmptyScenarios(attempts) => {
+                write!(f, "Sampler returned {} empty scenarios in a row", attempts)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+impl From<SimulationError> for Status {
+    fn from(error: SimulationError) -> Self {
+        match error {
+            SimulationError::InsufficientAssetData(..) => Status::failed_precondition(error.to_string()),
+            SimulationError::SamplerUnavailable(_) => Status::unavailable(error.to_string()),
+            SimulationError::EmptyScenarios(_) => Status::failed_precondition(error.to_string()),
+        }
+    }
+}
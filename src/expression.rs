@@ -0,0 +1,219 @@
+// Tiny arithmetic language for client-defined metrics, e.g. "sharpe_ratio / (vol_of_vol + 0.01)".
+//
+// Grammar (usual precedence, left associative):
+//   expr   := term (('+' | '-') term)*
+//   term   := factor (('*' | '/') factor)*
+//   factor := number | metric_name | '-' factor | '(' expr ')'
+//
+// Metric names are checked at parse time against `PortfolioPerformance::METRIC_NAMES`, so a
+// typo is reported before any simulation work is done.
+use std::fmt;
+
+use crate::performance::PortfolioPerformance;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionError {
+    UnexpectedCharacter { position: usize, found: char },
+    UnexpectedEnd,
+    UnexpectedToken { position: usize },
+    UnknownMetric(String),
+    InvalidNumber(String),
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionError::UnexpectedCharacter { position, found } => {
+                write!(f, "Unexpected character '{}' at position {}", found, position)
+            }
+            ExpressionError::UnexpectedEnd => write!(f, "Unexpected end of expression"),
+            ExpressionError::UnexpectedToken { position } => {
+                write!(f, "Unexpected token at position {}", position)
+            }
+            ExpressionError::UnknownMetric(name) => write!(
+                f,
+                "Unknown metric '{}' (available: {})",
+                name,
+                PortfolioPerformance::METRIC_NAMES.join(", ")
+            ),
+            ExpressionError::InvalidNumber(raw) => write!(f, "Invalid number literal '{}'", raw),
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(f64),
+    Metric(&'static str),
+    Negate(Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, ExpressionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, cursor: 0 };
+        let expr = parser.expr()?;
+        match parser.tokens.get(parser.cursor) {
+            None => Ok(expr),
+            Some((position, _)) => Err(ExpressionError::UnexpectedToken {
+                position: *position,
+            }),
+        }
+    }
+
+    pub fn evaluate(&self, perf: &PortfolioPerformance) -> f64 {
+        match self {
+            Expr::Literal(value) => *value,
+            Expr::Metric(name) => perf
+                .metric(name)
+                .expect("metric names are validated at parse time"),
+            Expr::Negate(inner) => -inner.evaluate(perf),
+            Expr::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(perf), rhs.evaluate(perf));
+                match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Subtract => lhs - rhs,
+                    BinaryOp::Multiply => lhs * rhs,
+                    BinaryOp::Divide => lhs / rhs, // inf/NaN on zero, same as the built-in metrics
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Operator(BinaryOp),
+    OpenParen,
+    CloseParen,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '+' => Token::Operator(BinaryOp::Add),
+            '-' => Token::Operator(BinaryOp::Subtract),
+            '*' => Token::Operator(BinaryOp::Multiply),
+            '/' => Token::Operator(BinaryOp::Divide),
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            c if c.is_ascii_digit() || c == '.' => {
+                while i + 1 < chars.len() {
+                    let next = chars[i + 1];
+                    let exponent_sign = (next == '-' || next == '+')
+                        && matches!(chars[i], 'e' | 'E');
+                    if next.is_ascii_digit() || next == '.' || next == 'e' || next == 'E' || exponent_sign {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let raw: String = chars[start..=i].iter().collect();
+                Token::Number(
+                    raw.parse()
+                        .map_err(|_| ExpressionError::InvalidNumber(raw.clone()))?,
+                )
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                while i + 1 < chars.len() && (chars[i + 1].is_ascii_alphanumeric() || chars[i + 1] == '_') {
+                    i += 1;
+                }
+                Token::Identifier(chars[start..=i].iter().collect())
+            }
+            found => {
+                return Err(ExpressionError::UnexpectedCharacter {
+                    position: start,
+                    found,
+                });
+            }
+        };
+        tokens.push((start, token));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    cursor: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.cursor).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), ExpressionError> {
+        let token = self
+            .tokens
+            .get(self.cursor)
+            .cloned()
+            .ok_or(ExpressionError::UnexpectedEnd)?;
+        self.cursor += 1;
+        Ok(token)
+    }
+
+    fn expr(&mut self) -> Result<Expr, ExpressionError> {
+        let mut lhs = self.term()?;
+        while let Some(Token::Operator(op @ (BinaryOp::Add | BinaryOp::Subtract))) = self.peek() {
+            let op = *op;
+            self.cursor += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, ExpressionError> {
+        let mut lhs = self.factor()?;
+        while let Some(Token::Operator(op @ (BinaryOp::Multiply | BinaryOp::Divide))) = self.peek() {
+            let op = *op;
+            self.cursor += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.factor()?));
+        }
+        Ok(lhs)
+    }
+
+    fn factor(&mut self) -> Result<Expr, ExpressionError> {
+        match self.next()? {
+            (_, Token::Number(value)) => Ok(Expr::Literal(value)),
+            (_, Token::Identifier(name)) => PortfolioPerformance::METRIC_NAMES
+                .iter()
+                .find(|known| **known == name)
+                .map(|known| Expr::Metric(known))
+                .ok_or(ExpressionError::UnknownMetric(name)),
+            (_, Token::Operator(BinaryOp::Subtract)) => Ok(Expr::Negate(Box::new(self.factor()?))),
+            (_, Token::OpenParen) => {
+                let inner = self.expr()?;
+                match self.next()? {
+                    (_, Token::CloseParen) => Ok(inner),
+                    (position, _) => Err(ExpressionError::UnexpectedToken { position }),
+                }
+            }
+            (position, _) => Err(ExpressionError::UnexpectedToken { position }),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// Interest rate sensitivity of the bond sleeves of a portfolio, from each bond's (modified)
+// duration and convexity as given in `Portfolio.fixed_income_assets`.
+use aegis_athena_contracts::simulation::FixedIncomeSpec;
+
+use crate::performance::PortfolioPerformance;
+
+const BASIS_POINT: f64 = 1e-4;
+
+/// Dollar value of a 1bp parallel rate rise, as a positive loss:
+/// `sum(w_i * DV01_i * money_to_invest)` with `DV01_i = duration_i * 1bp` per dollar.
+pub fn portfolio_dv01(fixed_income_assets: &[FixedIncomeSpec], weights: &[f64], money_to_invest: f64) -> f64 {
+    fixed_income_assets
+        .iter()
+        .map(|bond| weight_of(bond, weights) * bond.duration * BASIS_POINT * money_to_invest)
+        .sum()
+}
+
+/// Weighted convexity, `sum(w_i * convexity_i)`. Non-bond assets contribute nothing.
+pub fn portfolio_convexity(fixed_income_assets: &[FixedIncomeSpec], weights: &[f64]) -> f64 {
+    fixed_income_assets
+        .iter()
+        .map(|bond| weight_of(bond, weights) * bond.convexity)
+        .sum()
+}
+
+/// Fills `portfolio_dv01` and `portfolio_convexity`, left as None for portfolios without bonds.
+pub fn apply_rate_sensitivity(
+    perf: &mut PortfolioPerformance,
+    fixed_income_assets: &[FixedIncomeSpec],
+    weights: &[f64],
+    money_to_invest: f64,
+) {
+    if fixed_income_assets.is_empty() {
+        return;
+    }
+    perf.portfolio_dv01 = Some(portfolio_dv01(fixed_income_assets, weights, money_to_invest));
+    perf.portfolio_convexity = Some(portfolio_convexity(fixed_income_assets, weights));
+}
+
+fn weight_of(bond: &FixedIncomeSpec, weights: &[f64]) -> f64 {
+    let index = bond.asset_index as usize;
+    *weights.get(index).unwrap_or_else(|| {
+        panic!(
+            "Configuration Error: fixed income asset {} is out of range for {} weights.",
+            index,
+            weights.len()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bond(asset_index: u32, duration: f64, convexity: f64) -> FixedIncomeSpec {
+        FixedIncomeSpec {
+            asset_index,
+            duration,
+            convexity,
+        }
+    }
+
+    #[test]
+    fn bond_only_dv01_is_the_weighted_average_dv01() {
+        let bonds = [bond(0, 2.0, 5.0), bond(1, 7.0, 60.0), bond(2, 15.0, 250.0)];
+        let weights = [0.5, 0.3, 0.2];
+        let money = 1_000_000.0;
+        // per-dollar DV01 of each bond, duration * 1bp
+        let average_dv01 = 0.5 * 2.0e-4 + 0.3 * 7.0e-4 + 0.2 * 15.0e-4;
+        assert!((portfolio_dv01(&bonds, &weights, money) - average_dv01 * money).abs() < 1e-9);
+        assert!((portfolio_convexity(&bonds, &weights) - (2.5 + 18.0 + 50.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn portfolios_without_bonds_keep_none() {
+        let mut perf = crate::performance::compute_portfolio_performance(
+            &[vec![0.01], vec![-0.01], vec![0.02]],
+            &[1.0],
+            100.0,
+            0.0,
+            365.0,
+        );
+        apply_rate_sensitivity(&mut perf, &[], &[1.0], 100.0);
+        assert_eq!(perf.portfolio_dv01, None);
+        apply_rate_sensitivity(&mut perf, &[bond(0, 4.0, 20.0)], &[1.0], 100.0);
+        assert!((perf.portfolio_dv01.unwrap() - 0.04).abs() < 1e-12);
+        assert_eq!(perf.portfolio_convexity, Some(20.0));
+    }
+}
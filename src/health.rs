@@ -0,0 +1,63 @@
+// Internal service health, for dashboards that want more than the gRPC health check's
+// SERVING / NOT_SERVING.
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use aegis_athena_contracts::simulation::GetHealthResponse;
+
+use crate::stats::sorted_quantile;
+
+// Latency percentiles are over the most recent batches only, so a slow hour doesn't linger
+const LATENCY_WINDOW: usize = 1_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceHealth {
+    pub rayon_pool_threads: usize, // size of the global pool, not how many workers are busy
+    pub pending_requests: u32,
+    pub cache_hit_rate: f64,
+    pub p99_latency_ms: f64,
+}
+
+impl From<ServiceHealth> for GetHealthResponse {
+    fn from(health: ServiceHealth) -> Self {
+        GetHealthResponse {
+            rayon_pool_threads: health.rayon_pool_threads as u32,
+            pending_requests: health.pending_requests,
+            cache_hit_rate: health.cache_hit_rate,
+            p99_latency_ms: health.p99_latency_ms,
+        }
+    }
+}
+
+/// Rolling window of `run_batch` latencies.
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    recent_ms: Mutex<VecDeque<f64>>,
+}
+
+impl LatencyTracker {
+    pub fn record(&self, elapsed: Duration) {
+        let mut recent = self.recent_ms.lock().expect("latency lock poisoned");
+        if recent.len() == LATENCY_WINDOW {
+            recent.pop_front();
+        }
+        recent.push_back(elapsed.as_secs_f64() * 1_000.0);
+    }
+
+    /// 0 until the first batch has completed.
+    pub fn quantile_ms(&self, p: f64) -> f64 {
+        let mut sorted: Vec<f64> = self
+            .recent_ms
+            .lock()
+            .expect("latency lock poisoned")
+            .iter()
+            .copied()
+            .collect();
+        if sorted.is_empty() {
+            return 0.0;
+        }
+        sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+        sorted_quantile(&sorted, p)
+    }
+}
@@ -0,0 +1,83 @@
+// Hedging effectiveness: overlay a hedge leg (futures, inverse ETFs, ...) on the portfolio and
+// measure how much of its variance goes away, 1 - var(hedged) / var(unhedged).
+use aegis_athena_contracts::simulation::HedgeConfig;
+
+use crate::performance::{PortfolioPerformance, adaptive_epsilon, annualized_sharpe};
+use crate::stats::mean_and_std;
+
+/// Fills the hedged_* fields of `perf`, which must come from the same `returns`.
+/// The hedge leg is `hedge_ratio * sum_j hedge_weights[j] * simple_return(asset hedge_asset_indices[j])`.
+pub fn apply_hedge(
+    perf: &mut PortfolioPerformance,
+    returns: &[Vec<f64>],
+    hedge: &HedgeConfig,
+    money_to_invest: f64,
+    risk_free_rate: f64,
+) {
+    if hedge.hedge_weights.len() != hedge.hedge_asset_indices.len() {
+        panic!(
+            "Configuration Error: hedge has {} weights for {} asset indices.",
+            hedge.hedge_weights.len(),
+            hedge.hedge_asset_indices.len()
+        );
+    }
+
+    let hedged_returns: Vec<f64> = returns
+        .iter()
+        .zip(&perf.portfolio_returns)
+        .map(|(row, unhedged)| {
+            let hedge_leg = hedge
+                .hedge_asset_indices
+                .iter()
+                .zip(&hedge.hedge_weights)
+                .map(|(&asset, weight)| (row[asset as usize].exp() - 1.0) * weight)
+                .sum::<f64>();
+            unhedged + hedge.hedge_ratio * hedge_leg * money_to_invest
+        })
+        .collect();
+
+    let (_, unhedged_std) = mean_and_std(&perf.portfolio_returns);
+    let (hedged_mean, hedged_std) = mean_and_std(&hedged_returns);
+    let hedged_variance = hedged_std.powi(2);
+
+    let (_, _, hedged_sharpe) =
+        annualized_sharpe(hedged_mean, hedged_std, perf.periods_per_year, money_to_invest, risk_free_rate);
+
+    let unhedged_variance = unhedged_std.powi(2);
+    // a variance in dollars squared
+    let hedging_effectiveness = if unhedged_variance >= adaptive_epsilon(money_to_invest).powi(2) {
+        1.0 - hedged_variance / unhedged_variance
+    } else {
+        0.0 // nothing to hedge in the first place
+    };
+
+    perf.hedged_sharpe = Some(hedged_sharpe);
+    perf.hedged_var = Some(hedged_variance);
+    perf.hedging_effectiveness = Some(hedging_effectiveness);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::compute_portfolio_performance;
+
+    #[test]
+    fn a_perfect_negative_correlation_hedge_removes_all_variance() {
+        let money = 1_000.0;
+        // asset 1 returns exactly the opposite of asset 0 every period
+        let returns: Vec<Vec<f64>> = [0.01, -0.02, 0.03, 0.0, -0.015]
+            .iter()
+            .map(|simple: &f64| vec![simple.ln_1p(), (-simple).ln_1p()])
+            .collect();
+        let mut perf = compute_portfolio_performance(&returns, &[1.0, 0.0], money, 0.02, 365.0);
+        let hedge = HedgeConfig {
+            hedge_asset_indices: vec![1],
+            hedge_weights: vec![1.0],
+            hedge_ratio: 1.0,
+            ..Default::default()
+        };
+        apply_hedge(&mut perf, &returns, &hedge, money, 0.02);
+        assert!((perf.hedging_effectiveness.unwrap() - 1.0).abs() < 1e-9);
+        assert!(perf.hedged_var.unwrap() < 1e-9);
+    }
+}
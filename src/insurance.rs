@@ -0,0 +1,99 @@
+// Portfolio insurance: a rolling protective put struck at `protection_level` (a per-period
+// return, e.g. -0.05) bought every period for a prorated share of `put_cost_annual`.
+use aegis_athena_contracts::simulation::ProtectivePutConfig;
+
+use crate::performance::{PortfolioPerformance, annualized_sharpe};
+use crate::stats::mean_and_std;
+
+/// Fills the insured_* fields of `perf`. Each period pays the premium and gets back
+/// `max(0, protection_level - period_return)`, so no period ends below roughly
+/// `protection_level - premium`.
+pub fn apply_protective_put(
+    perf: &mut PortfolioPerformance,
+    put: &ProtectivePutConfig,
+    money_to_invest: f64,
+    risk_free_rate: f64,
+) {
+    if !put.put_cost_annual.is_finite() || put.put_cost_annual < 0.0 {
+        panic!(
+            "Configuration Error: put_cost_annual must be finite and >= 0 (found {}).",
+            put.put_cost_annual
+        );
+    }
+    if !put.protection_level.is_finite() {
+        panic!(
+            "Configuration Error: protection_level must be finite (found {}).",
+            put.protection_level
+        );
+    }
+
+    let premium_per_period = put.put_cost_annual / perf.periods_per_year * money_to_invest;
+    let insured_returns = insured_period_returns(&perf.portfolio_returns, put, premium_per_period, money_to_invest);
+
+    let (insured_mean, insured_std) = mean_and_std(&insured_returns);
+    let (insured_return, _, insured_sharpe) =
+        annualized_sharpe(insured_mean, insured_std, perf.periods_per_year, money_to_invest, risk_free_rate);
+
+    perf.insured_sharpe = Some(insured_sharpe);
+    perf.insured_return = Some(insured_return);
+    perf.insurance_cost_total = Some(premium_per_period * insured_returns.len() as f64);
+}
+
+// Dollar returns per period once the premium is paid and the put's payoff collected
+fn insured_period_returns(
+    portfolio_returns: &[f64],
+    put: &ProtectivePutConfig,
+    premium_per_period: f64,
+    money_to_invest: f64,
+) -> Vec<f64> {
+    portfolio_returns
+        .iter()
+        .map(|ret| {
+            let payoff = (put.protection_level - ret / money_to_invest).max(0.0) * money_to_invest;
+            ret - premium_per_period + payoff
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::compute_portfolio_performance;
+
+    #[test]
+    fn a_free_put_that_never_pays_changes_nothing() {
+        let returns: Vec<Vec<f64>> = [0.01, -0.02, 0.03, 0.0].iter().map(|r: &f64| vec![r.ln_1p()]).collect();
+        let mut perf = compute_portfolio_performance(&returns, &[1.0], 1_000.0, 0.02, 365.0);
+        let put = ProtectivePutConfig {
+            protection_level: -0.5,
+            put_cost_annual: 0.0,
+        };
+        apply_protective_put(&mut perf, &put, 1_000.0, 0.02);
+        assert!((perf.insured_sharpe.unwrap() - perf.sharpe_ratio).abs() < 1e-12);
+        assert!((perf.insured_return.unwrap() - perf.annualized_return).abs() < 1e-9);
+        assert_eq!(perf.insurance_cost_total, Some(0.0));
+    }
+
+    #[test]
+    fn insured_periods_never_end_below_the_protection_level_minus_the_premium() {
+        let money = 1_000.0;
+        let returns: Vec<Vec<f64>> = [0.01, -0.20, 0.03, -0.10].iter().map(|r: &f64| vec![r.ln_1p()]).collect();
+        let mut perf = compute_portfolio_performance(&returns, &[1.0], money, 0.02, 365.0);
+        let put = ProtectivePutConfig {
+            protection_level: -0.05,
+            put_cost_annual: 0.04,
+        };
+        // 4 periods a year, so 1% of the money per period
+        let premium = put.put_cost_annual / perf.periods_per_year * money;
+        let insured = insured_period_returns(&perf.portfolio_returns, &put, premium, money);
+        let worst = insured.iter().copied().fold(f64::INFINITY, f64::min);
+        assert!((worst / money - (put.protection_level - 0.01)).abs() < 1e-9);
+        // both losing periods hit the floor, the others only pay the premium
+        assert!((insured[1] - insured[3]).abs() < 1e-9);
+        assert!((insured[0] - (perf.portfolio_returns[0] - premium)).abs() < 1e-9);
+
+        apply_protective_put(&mut perf, &put, money, 0.02);
+        assert!((perf.insurance_cost_total.unwrap() - 4.0 * premium).abs() < 1e-9);
+        assert!(perf.insured_return.unwrap() > perf.annualized_return);
+    }
+}
@@ -0,0 +1,22 @@
+// gRPC interceptors, run on the request metadata before the body is decoded.
+use tonic::{Request, Status};
+
+/// Rejects requests whose declared `content-length` exceeds `max_bytes`, before anything is
+/// buffered. HTTP/2 clients often don't send the header at all; those are covered by the
+/// server's `max_decoding_message_size`, which aborts decoding as soon as the limit is crossed.
+pub fn request_size_limit(max_bytes: usize) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+    move |request: Request<()>| {
+        let declared = request
+            .metadata()
+            .get("content-length")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok());
+        match declared {
+            Some(length) if length > max_bytes => Err(Status::resource_exhausted(format!(
+                "Request body of {} bytes exceeds the {} byte limit",
+                length, max_bytes
+            ))),
+            _ => Ok(request),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Athena: the simulation runner behind Aegis. The binary in main.rs only wires up the gRPC server.
+pub mod analytics;
+pub mod audit;
+pub mod cache;
+pub mod checkpoint;
+pub mod config;
+pub mod credit;
+pub mod dca;
+pub mod drain;
+pub mod drawdown;
+pub mod encoding;
+pub mod error;
+pub mod expression;
+pub mod fixed_income;
+pub mod health;
+pub mod hedging;
+pub mod insurance;
+pub mod interceptors;
+pub mod linalg;
+pub mod lp;
+pub mod optimizer;
+pub mod performance;
+pub mod portfolio;
+pub mod precision;
+pub mod prefetch;
+pub mod progress;
+pub mod rebalancing;
+pub mod response_format;
+pub mod runtime_model;
+pub mod sampler_pool;
+pub mod sampler_state;
+pub mod sampling;
+pub mod scenario_tree;
+pub mod scheduler;
+pub mod search;
+pub mod server_config;
+pub mod service;
+pub mod stability;
+pub mod stats;
+pub mod stress;
+pub mod summary;
+pub mod tearsheet;
+pub mod vine;
+pub mod whatif;
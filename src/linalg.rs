@@ -0,0 +1,328 @@
+// Small dense linear algebra helpers. Matrices are row-major `Vec<Vec<f64>>`,
+// same layout as the scenario returns, so nothing needs converting at the call site.
+use std::fmt;
+
+use crate::performance::FLOAT_COMPARISON_EPSILON;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinalgError {
+    NotSquare { rows: usize, cols: usize },
+    NotPositiveDefinite { pivot_index: usize, pivot_value: f64 },
+    Singular,
+}
+
+impl fmt::Display for LinalgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinalgError::NotSquare { rows, cols } => {
+                write!(f, "Matrix must be square (found {}x{}).", rows, cols)
+            }
+            LinalgError::NotPositiveDefinite {
+                pivot_index,
+                pivot_value,
+            } => write!(
+                f,
+                "Matrix is not positive definite (pivot {} is {}).",
+                pivot_index, pivot_value
+            ),
+            LinalgError::Singular => write!(f, "Matrix is singular."),
+        }
+    }
+}
+
+impl std::error::Error for LinalgError {}
+
+fn ensure_square(mat: &[Vec<f64>]) -> Result<usize, LinalgError> {
+    let n = mat.len();
+    match mat.iter().find(|row| row.len() != n) {
+        Some(row) => Err(LinalgError::NotSquare {
+            rows: n,
+            cols: row.len(),
+        }),
+        None => Ok(n),
+    }
+}
+
+/// Cholesky–Banachiewicz decomposition. Returns the lower triangular `L` with `L * L^T = mat`.
+/// A pivot counts as zero relative to the largest diagonal entry, so the answer doesn't depend
+/// on the units (daily return covariances are ~1e-4, dollar ones can be ~1e6).
+pub fn cholesky(mat: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, LinalgError> {
+    let n = ensure_square(mat)?;
+    let mut l = vec![vec![0.0; n]; n];
+    let largest_diagonal = (0..n).map(|i| mat[i][i].abs()).fold(0.0, f64::max);
+    let pivot_tolerance = FLOAT_COMPARISON_EPSILON * largest_diagonal;
+
+    for i in 0..n {
+        for j in 0..=i {
+            let dot = (0..j).map(|k| l[i][k] * l[j][k]).sum::<f64>();
+            if i == j {
+                let pivot = mat[i][i] - dot;
+                if pivot.is_nan() || pivot <= pivot_tolerance {
+                    return Err(LinalgError::NotPositiveDefinite {
+                        pivot_index: i,
+                        pivot_value: pivot,
+                    });
+                }
+                l[i][j] = pivot.sqrt();
+            } else {
+                l[i][j] = (mat[i][j] - dot) / l[j][j];
+            }
+        }
+    }
+
+    Ok(l)
+}
+
+/// Householder QR of an `m x n` matrix. Returns `(Q, R)` with `Q` being `m x m` orthogonal
+/// and `R` being `m x n` upper triangular.
+pub fn qr_decompose(mat: &[Vec<f64>]) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let m = mat.len();
+    let n = mat.first().map_or(0, Vec::len);
+
+    let mut r = mat.to_vec();
+    let mut q = identity(m);
+
+    for k in 0..n.min(m.saturating_sub(1)) {
+        // Build the Householder vector for column k below the diagonal
+        let norm = (k..m).map(|i| r[i][k].powi(2)).sum::<f64>().sqrt();
+        if norm < FLOAT_COMPARISON_EPSILON {
+            continue; // column already zero, nothing to reflect
+        }
+        let alpha = if r[k][k] > 0.0 { -norm } else { norm };
+
+        let mut v = vec![0.0; m];
+        v[k] = r[k][k] - alpha;
+        for i in (k + 1)..m {
+            v[i] = r[i][k];
+        }
+        let v_norm_sq = v.iter().map(|x| x * x).sum::<f64>();
+        if v_norm_sq < FLOAT_COMPARISON_EPSILON {
+            continue;
+        }
+
+        // R <- H R
+        for j in 0..n {
+            let dot = (k..m).map(|i| v[i] * r[i][j]).sum::<f64>();
+            let factor = 2.0 * dot / v_norm_sq;
+            for i in k..m {
+                r[i][j] -= factor * v[i];
+            }
+        }
+        // Q <- Q H
+        for row in q.iter_mut() {
+            let dot = (k..m).map(|i| row[i] * v[i]).sum::<f64>();
+            let factor = 2.0 * dot / v_norm_sq;
+            for i in k..m {
+                row[i] -= factor * v[i];
+            }
+        }
+    }
+
+    // Clean up the round-off left below the diagonal
+    for (i, row) in r.iter_mut().enumerate() {
+        for value in row.iter_mut().take(i.min(n)) {
+            *value = 0.0;
+        }
+    }
+
+    (q, r)
+}
+
+/// Solves `L x = b` by forward substitution, `l` being lower triangular (e.g. from `cholesky`).
+pub fn solve_triangular(l: &[Vec<f64>], b: &[f64]) -> Vec<f64> {
+    let n = b.len();
+    let mut x = vec![0.0; n];
+
+    for i in 0..n {
+        let dot = (0..i).map(|k| l[i][k] * x[k]).sum::<f64>();
+        x[i] = (b[i] - dot) / l[i][i];
+    }
+
+    x
+}
+
+/// Solves the square system `a x = b` by Gaussian elimination with partial pivoting.
+pub fn solve_linear_system(a: &[Vec<f64>], b: &[f64]) -> Result<Vec<f64>, LinalgError> {
+    let n = ensure_square(a)?;
+    // Augmented matrix [a | b]
+    let mut m: Vec<Vec<f64>> = a
+        .iter()
+        .zip(b)
+        .map(|(row, b_i)| row.iter().copied().chain(std::iter::once(*b_i)).collect())
+        .collect();
+
+    // Relative tolerance, covariances of daily returns are tiny in absolute terms
+    let scale = a.iter().flatten().fold(0.0_f64, |acc, v| acc.max(v.abs()));
+    let tolerance = FLOAT_COMPARISON_EPSILON * scale.max(f64::MIN_POSITIVE);
+
+    for col in 0..n {
+        let pivot_row = (col..n)
+            .max_by(|&i, &j| m[i][col].abs().total_cmp(&m[j][col].abs()))
+            .expect("non-empty pivot range");
+        if m[pivot_row][col].abs() < tolerance {
+            return Err(LinalgError::Singular);
+        }
+        m.swap(col, pivot_row);
+
+        for row in (col + 1)..n {
+            let factor = m[row][col] / m[col][col];
+            if factor == 0.0 {
+                continue;
+            }
+            for k in col..=n {
+                m[row][k] -= factor * m[col][k];
+            }
+        }
+    }
+
+    // Back substitution
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let dot = ((i + 1)..n).map(|k| m[i][k] * x[k]).sum::<f64>();
+        x[i] = (m[i][n] - dot) / m[i][i];
+    }
+    Ok(x)
+}
+
+/// Plain `a * b`. Panics if the inner dimensions do not agree.
+pub fn matrix_multiply(a: &[Vec<f64>], b: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let inner = b.len();
+    let cols = b.first().map_or(0, Vec::len);
+
+    a.iter()
+        .map(|row| {
+            if row.len() != inner {
+                panic!(
+                    "Dimension Error: cannot multiply a matrix with {} columns by one with {} rows.",
+                    row.len(),
+                    inner
+                );
+            }
+            (0..cols)
+                .map(|j| row.iter().zip(b).map(|(a_ik, b_k)| a_ik * b_k[j]).sum())
+                .collect()
+        })
+        .collect()
+}
+
+pub fn transpose(mat: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let cols = mat.first().map_or(0, Vec::len);
+    (0..cols)
+        .map(|j| mat.iter().map(|row| row[j]).collect())
+        .collect()
+}
+
+pub fn identity(n: usize) -> Vec<Vec<f64>> {
+    (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f64 = 1e-10;
+
+    fn assert_matrices_close(actual: &[Vec<f64>], expected: &[Vec<f64>]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual_row, expected_row) in actual.iter().zip(expected) {
+            assert_eq!(actual_row.len(), expected_row.len());
+            for (a, e) in actual_row.iter().zip(expected_row) {
+                assert!((a - e).abs() < TOLERANCE, "{:?} != {:?}", actual, expected);
+            }
+        }
+    }
+
+    fn covariance() -> Vec<Vec<f64>> {
+        vec![
+            vec![4.0, 2.0, 0.6],
+            vec![2.0, 5.0, 1.5],
+            vec![0.6, 1.5, 3.0],
+        ]
+    }
+
+    #[test]
+    fn cholesky_reconstructs_the_matrix() {
+        let l = cholesky(&covariance()).expect("positive definite");
+        for (i, row) in l.iter().enumerate() {
+            assert!(row[i + 1..].iter().all(|v| *v == 0.0), "L is not lower triangular");
+        }
+        assert_matrices_close(&matrix_multiply(&l, &transpose(&l)), &covariance());
+    }
+
+    #[test]
+    fn cholesky_rejects_non_positive_definite() {
+        let indefinite = vec![vec![1.0, 2.0], vec![2.0, 1.0]];
+        assert!(matches!(cholesky(&indefinite), Err(LinalgError::NotPositiveDefinite { pivot_index: 1, .. })));
+        assert_eq!(
+            cholesky(&[vec![1.0, 0.0]]),
+            Err(LinalgError::NotSquare { rows: 1, cols: 2 })
+        );
+    }
+
+    #[test]
+    fn cholesky_pivots_are_judged_relative_to_the_scale() {
+        // the same well-conditioned matrix in tiny units still decomposes
+        let tiny: Vec<Vec<f64>> = covariance()
+            .iter()
+            .map(|row| row.iter().map(|v| v * 1e-12).collect())
+            .collect();
+        let l = cholesky(&tiny).expect("positive definite at any scale");
+        assert!((matrix_multiply(&l, &transpose(&l))[2][2] - 3e-12).abs() < 1e-24);
+
+        // and a near-singular one in large units is still caught
+        let near_singular = vec![vec![1e6, 1e6], vec![1e6, 1e6 + 1e-4]];
+        assert!(matches!(
+            cholesky(&near_singular),
+            Err(LinalgError::NotPositiveDefinite { pivot_index: 1, .. })
+        ));
+        assert!(matches!(
+            cholesky(&[vec![0.0]]),
+            Err(LinalgError::NotPositiveDefinite { pivot_index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn qr_reconstructs_the_matrix() {
+        let mat = vec![
+            vec![12.0, -51.0],
+            vec![6.0, 167.0],
+            vec![-4.0, 24.0],
+        ];
+        let (q, r) = qr_decompose(&mat);
+        assert_matrices_close(&matrix_multiply(&q, &r), &mat);
+        assert_matrices_close(&matrix_multiply(&transpose(&q), &q), &identity(3));
+        assert_eq!(r[1][0], 0.0);
+        assert_eq!(r[2][0], 0.0);
+        assert_eq!(r[2][1], 0.0);
+    }
+
+    #[test]
+    fn solves_triangular_and_general_systems() {
+        let b = vec![1.0, -2.0, 0.5];
+
+        let l = cholesky(&covariance()).expect("positive definite");
+        let y = solve_triangular(&l, &b);
+        let ly: Vec<f64> = matrix_multiply(&l, &transpose(&[y])).into_iter().flatten().collect();
+        assert_matrices_close(&[ly], &[b.clone()]);
+
+        let x = solve_linear_system(&covariance(), &b).expect("non-singular");
+        let ax: Vec<f64> = matrix_multiply(&covariance(), &transpose(&[x])).into_iter().flatten().collect();
+        assert_matrices_close(&[ax], &[b]);
+    }
+
+    #[test]
+    fn singular_system_is_an_error() {
+        let singular = vec![vec![1.0, 2.0], vec![2.0, 4.0]];
+        assert_eq!(solve_linear_system(&singular, &[1.0, 2.0]), Err(LinalgError::Singular));
+    }
+
+    #[test]
+    fn multiply_and_transpose_shapes() {
+        let a = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+        assert_eq!(transpose(&a), vec![vec![1.0, 4.0], vec![2.0, 5.0], vec![3.0, 6.0]]);
+        assert_eq!(matrix_multiply(&a, &transpose(&a)), vec![vec![14.0, 32.0], vec![32.0, 77.0]]);
+        assert_eq!(matrix_multiply(&identity(2), &a), a);
+    }
+}
@@ -0,0 +1,213 @@
+// Dense two-phase simplex for the small-to-medium LPs the optimizers build (a few thousand
+// variables at most). Problems are `min c^T x` subject to linear rows and `x >= 0`.
+use std::fmt;
+
+const TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    LessEq,
+    Equal,
+    GreaterEq,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearProgram {
+    pub objective: Vec<f64>,
+    pub constraints: Vec<(Vec<f64>, Relation, f64)>, // coefficients, relation, right-hand side
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LpError {
+    Infeasible,
+    Unbounded,
+    IterationLimit(usize),
+}
+
+impl fmt::Display for LpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LpError::Infeasible => write!(f, "Linear program is infeasible."),
+            LpError::Unbounded => write!(f, "Linear program is unbounded."),
+            LpError::IterationLimit(limit) => write!(f, "Simplex did not converge within {} pivots.", limit),
+        }
+    }
+}
+
+impl std::error::Error for LpError {}
+
+// Tableau rows with the rhs in the last column, plus the reduced cost row (last entry = -objective)
+struct Tableau {
+    rows: Vec<Vec<f64>>,
+    cost: Vec<f64>,
+    basis: Vec<usize>,
+}
+
+impl Tableau {
+    fn width(&self) -> usize {
+        self.cost.len() - 1
+    }
+
+    fn pivot(&mut self, row: usize, col: usize) {
+        let pivot_value = self.rows[row][col];
+        for value in self.rows[row].iter_mut() {
+            *value /= pivot_value;
+        }
+        let pivot_row = self.rows[row].clone();
+        let eliminate = |target: &mut Vec<f64>| {
+            let factor = target[col];
+            if factor != 0.0 {
+                for (value, p) in target.iter_mut().zip(&pivot_row) {
+                    *value -= factor * p;
+                }
+            }
+        };
+        for (i, other) in self.rows.iter_mut().enumerate() {
+            if i != row {
+                eliminate(other);
+            }
+        }
+        eliminate(&mut self.cost);
+        self.basis[row] = col;
+    }
+
+    // Dantzig's rule (most negative reduced cost), ties in the ratio test go to the lowest basic index
+    fn optimize(&mut self, allowed: &[bool]) -> Result<(), LpError> {
+        let width = self.width();
+        let limit = 50 * (self.rows.len() + width).max(1);
+        for _ in 0..limit {
+            let entering = (0..width)
+                .filter(|&j| allowed[j] && self.cost[j] < -TOLERANCE)
+                .min_by(|&a, &b| self.cost[a].total_cmp(&self.cost[b]));
+            let Some(col) = entering else {
+                return Ok(());
+            };
+            let leaving = (0..self.rows.len())
+                .filter(|&i| self.rows[i][col] > TOLERANCE)
+                .min_by(|&a, &b| {
+                    let ratio_a = self.rows[a][width] / self.rows[a][col];
+                    let ratio_b = self.rows[b][width] / self.rows[b][col];
+                    ratio_a.total_cmp(&ratio_b).then(self.basis[a].cmp(&self.basis[b]))
+                });
+            let Some(row) = leaving else {
+                return Err(LpError::Unbounded);
+            };
+            self.pivot(row, col);
+        }
+        Err(LpError::IterationLimit(limit))
+    }
+}
+
+/// Solves `lp`, returning the optimal `x`.
+pub fn minimize(lp: &LinearProgram) -> Result<Vec<f64>, LpError> {
+    let n = lp.objective.len();
+    if let Some((coefficients, _, _)) = lp.constraints.iter().find(|(c, _, _)| c.len() != n) {
+        panic!(
+            "Configuration Error: constraint has {} coefficients for {} variables.",
+            coefficients.len(),
+            n
+        );
+    }
+
+    // Flip rows so every rhs is non-negative, then count the extra columns
+    let constraints: Vec<(Vec<f64>, Relation, f64)> = lp
+        .constraints
+        .iter()
+        .map(|(coefficients, relation, rhs)| {
+            if *rhs >= 0.0 {
+                (coefficients.clone(), *relation, *rhs)
+            } else {
+                let flipped = match relation {
+                    Relation::LessEq => Relation::GreaterEq,
+                    Relation::Equal => Relation::Equal,
+                    Relation::GreaterEq => Relation::LessEq,
+                };
+                (coefficients.iter().map(|c| -c).collect(), flipped, -rhs)
+            }
+        })
+        .collect();
+    let n_slack = constraints.iter().filter(|(_, r, _)| *r != Relation::Equal).count();
+    let n_artificial = constraints.iter().filter(|(_, r, _)| *r != Relation::LessEq).count();
+    let width = n + n_slack + n_artificial;
+
+    let mut rows = Vec::with_capacity(constraints.len());
+    let mut basis = Vec::with_capacity(constraints.len());
+    let mut is_artificial = vec![false; width];
+    let (mut next_slack, mut next_artificial) = (n, n + n_slack);
+    for (coefficients, relation, rhs) in &constraints {
+        let mut row = vec![0.0; width + 1];
+        row[..n].copy_from_slice(coefficients);
+        row[width] = *rhs;
+        match relation {
+            Relation::LessEq => {
+                row[next_slack] = 1.0;
+                basis.push(next_slack);
+                next_slack += 1;
+            }
+            Relation::GreaterEq | Relation::Equal => {
+                if *relation == Relation::GreaterEq {
+                    row[next_slack] = -1.0;
+                    next_slack += 1;
+                }
+                row[next_artificial] = 1.0;
+                is_artificial[next_artificial] = true;
+                basis.push(next_artificial);
+                next_artificial += 1;
+            }
+        }
+        rows.push(row);
+    }
+
+    // Phase 1: minimize the sum of the artificials, from the all-artificial basis
+    let mut cost = vec![0.0; width + 1];
+    for (j, artificial) in is_artificial.iter().enumerate() {
+        if *artificial {
+            cost[j] = 1.0;
+        }
+    }
+    for (row, &basic) in rows.iter().zip(&basis) {
+        if is_artificial[basic] {
+            for (c, value) in cost.iter_mut().zip(row) {
+                *c -= value;
+            }
+        }
+    }
+    let mut tableau = Tableau { rows, cost, basis };
+    tableau.optimize(&vec![true; width])?;
+    let rhs_scale = constraints.iter().fold(1.0_f64, |acc, (_, _, rhs)| acc.max(rhs.abs()));
+    if -tableau.cost[width] > TOLERANCE * rhs_scale {
+        return Err(LpError::Infeasible);
+    }
+
+    // Pivot leftover (zero-valued) artificials out, rows where that's impossible are redundant
+    for row in 0..tableau.rows.len() {
+        if is_artificial[tableau.basis[row]] {
+            if let Some(col) = (0..width).find(|&j| !is_artificial[j] && tableau.rows[row][j].abs() > TOLERANCE) {
+                tableau.pivot(row, col);
+            }
+        }
+    }
+
+    // Phase 2: the real objective, artificials may no longer enter
+    let mut cost = vec![0.0; width + 1];
+    cost[..n].copy_from_slice(&lp.objective);
+    for (row, &basic) in tableau.rows.iter().zip(&tableau.basis) {
+        let factor = cost[basic];
+        if factor != 0.0 {
+            for (c, value) in cost.iter_mut().zip(row) {
+                *c -= factor * value;
+            }
+        }
+    }
+    tableau.cost = cost;
+    let allowed: Vec<bool> = is_artificial.iter().map(|a| !a).collect();
+    tableau.optimize(&allowed)?;
+
+    let mut x = vec![0.0; n];
+    for (row, &basic) in tableau.rows.iter().zip(&tableau.basis) {
+        if basic < n {
+            x[basic] = row[width];
+        }
+    }
+    Ok(x)
+}
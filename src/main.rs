@@ -1,114 +1,116 @@
-use crate::Sampler;
-use crate::service::SimulationServiceImpl; 
-use rand::Rng;
-use rand::SeedableRng;
-use aegis_athena_contracts::simulation::simulation_service_server::{SimulationService, SimulationServiceServer};
-use aegis_athena_contracts::simulation::{Portfolio, SimulationBatchRequest, SimulationBatchResult, SimulationScenario};
+use athena::audit::AuditLog;
+use athena::interceptors::request_size_limit;
+use athena::sampler_state::{load_sampler, save_sampler};
+use athena::server_config::ServerConfig;
+use athena::service::SimulationServiceImpl;
+use aegis_athena_contracts::sampling::Sampler;
+use aegis_athena_contracts::simulation::admin_service_server::AdminServiceServer;
+use aegis_athena_contracts::simulation::simulation_service_server::SimulationServiceServer;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::service::interceptor::InterceptedService;
 use tonic::transport::Server;
-use tonic::{Request, Response, Status}; 
-use rayon::prelude::*;
-
-pub const FLOAT_COMPARISON_EPSILON: f64 = 1e-9; // it would be a good idea to add this to athena-contracts or some central thing
-fn compute_portfolio_performance(
-    returns: &[Vec<f64>],
-    weights: &[f64],
-    money_to_invest: f64,
-    risk_free_rate: f64,
-    time_horizon_in_days: f64,
-) -> PortfolioPerformance {
-    // --- Edge Case Checks ---
-    // Check 1: Invalid Configuration for Time/Money (Panic)
-    if time_horizon_in_days.abs() < FLOAT_COMPARISON_EPSILON {
-        panic!("Configuration Error: time_horizon_in_days cannot be zero.");
-    }
-    if money_to_invest.abs() < FLOAT_COMPARISON_EPSILON {
-        panic!("Configuration Error: money_to_invest cannot be zero.");
-    }
-
-    let number_of_periods = returns.len() as f64;
-
-    // Check 2: Insufficient Return Periods for Volatility/Sharpe (Panic)
-    if number_of_periods < 2.0 {
-        panic!(
-        "Configuration Error: Cannot compute volatility or Sharpe ratio with fewer than 2 return periods (found {}). \
-         Check 'periods_to_sample' in Sampler configuration.",
-         returns.len()
-     );
-    }
-
-    // --- Main Calculation (Now guaranteed N >= 2) ---
-    let portfolio_returns = returns
-        .par_iter()
-        .map(|row| {
-            row.par_iter()
-                .zip(weights.par_iter())
-                .map(|(log_return, weight)| {
-                    ((log_return.exp() - 1.0) * *weight) * money_to_invest
-                })
-                .sum::<f64>()
-        })
-        .collect::<Vec<f64>>();
-
-    let average_return = portfolio_returns.iter().sum::<f64>() / number_of_periods;
-
-    // Calculate variance (N-1 in denominator is now safe)
-    let variance = portfolio_returns
-        .iter()
-        .map(|ret| (ret - average_return).powi(2))
-        .sum::<f64>()
-        / (number_of_periods - 1.0);
-    let volatility = variance.sqrt(); // Standard deviation (dollar terms)
-
-    // Annualizing!
-    let time_horizon_in_years = time_horizon_in_days / 365.0;
-    let periods_per_year = number_of_periods / time_horizon_in_years;
-
-    let annualized_return = average_return * periods_per_year;
-    let annualized_volatility = volatility * periods_per_year.sqrt();
-    let percent_annualized_volatility = annualized_volatility / money_to_invest;
-
-    // Adjust risk-free rate
-    let risk_free_return = money_to_invest * risk_free_rate; // Annual dollar risk-free
-
-    // Calculate Sharpe
-    let sharpe_ratio = if annualized_volatility.abs() >= FLOAT_COMPARISON_EPSILON {
-        // CASE 1: Volatility is significantly NON-ZERO
-        (annualized_return - risk_free_return) / annualized_volatility
-    } else {
-        // CASE 2: Volatility IS effectively ZERO
-        // Throwaway cause that's a useless portfolio (just cap it at 0. sharpe tadum-tsh)
-        0.0
-    };
-
-    PortfolioPerformance {
-        portfolio_returns,
-        annualized_return,
-        percent_annualized_volatility,
-        sharpe_ratio,
-    }
-}
+use tracing::info;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize tracing/logging to capture logs.
     tracing_subscriber::fmt::init();
 
-    // Define the address where the Athena simulation service will listen.
-    let addr = "0.0.0.0:50051".parse()?;
+    // Server-wide settings, from ATHENA_* environment variables.
+    let server_config = ServerConfig::from_env();
 
-    // Create an instance of your Sampler.
-    let sampler = Sampler::default();
+    // Define the address where the Athena simulation service will listen.
+    let addr = server_config.listen_addr;
+
+    // Create an instance of your Sampler, picking up where the last run left off if it was saved.
+    let mut sampler = Sampler::default();
+    if let Some(state_path) = &server_config.sampler_state_path {
+        if load_sampler(&mut sampler, state_path).await? {
+            println!("Restored sampler state from {}", state_path.display());
+        }
+    }
 
-    // Instantiate your simulation service with the sampler.
-    let simulation_service = SimulationServiceImpl { sampler };
+    // Instantiate your simulation service with the sampler, timing a warmup batch so that
+    // EstimateRuntime reflects this machine.
+    let mut simulation_service = SimulationServiceImpl::new(sampler)
+        .with_calibrated_runtime_model()
+        .with_max_batch_memory_bytes(server_config.max_batch_memory_bytes())
+        .with_result_cache_capacity(server_config.result_cache_max_entries);
+    if server_config.sampler_pool_size > 0 {
+        simulation_service = simulation_service.with_sampler_pool(
+            server_config.sampler_pool_size,
+            Duration::from_millis(server_config.sampler_pool_timeout_ms),
+        );
+    }
+    if server_config.prefetch_scenarios > 0 {
+        simulation_service = simulation_service.with_scenario_prefetch(server_config.prefetch_scenarios);
+        simulation_service.prefetch_scenarios(server_config.prefetch_scenarios);
+    }
+    if let Some(audit_log_path) = server_config.audit_log_path.clone() {
+        tokio::fs::create_dir_all(&audit_log_path).await?;
+        println!("Recording sampled scenarios to {}", audit_log_path.display());
+        simulation_service = simulation_service
+            .with_audit_log(AuditLog::spawn(audit_log_path, server_config.audit_queue_capacity));
+    }
+    if let Some(checkpoint_path) = server_config.checkpoint_path.clone() {
+        tokio::fs::create_dir_all(&checkpoint_path).await?;
+        println!("Writing batch checkpoints to {}", checkpoint_path.display());
+        simulation_service = simulation_service.with_checkpoint_dir(checkpoint_path);
+    }
 
     println!("Athena Simulation Service listening on {}", addr);
 
+    // Cached results nobody asks for again would otherwise stay until evicted by newer ones
+    let result_cache = Arc::clone(&simulation_service.result_cache);
+    let purge_every = Duration::from_secs(server_config.result_cache_purge_secs.max(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(purge_every);
+        loop {
+            ticker.tick().await;
+            let purged = result_cache.purge_expired();
+            if purged > 0 {
+                info!(purged, "purged expired cached results");
+            }
+        }
+    });
+
+    // Log the service internals once a minute
+    let monitored = simulation_service.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            ticker.tick().await;
+            info!(health = ?monitored.health_status(), "service health");
+        }
+    });
+
+    // Oversized requests are refused up front (content-length) or while decoding (streamed bodies).
+    let max_request_bytes = server_config.max_request_bytes();
+    // The service's sampler is shared, so this sees the state it ends up in, not the startup one
+    let persisted_sampler = Arc::clone(&simulation_service.sampler);
+    let admin_server = AdminServiceServer::new(simulation_service.clone());
+    let simulation_server =
+        SimulationServiceServer::new(simulation_service).max_decoding_message_size(max_request_bytes);
+
     // Build and serve the gRPC server.
     Server::builder()
-        .add_service(SimulationServiceServer::new(simulation_service))
-        .serve(addr)
+        .add_service(InterceptedService::new(
+            simulation_server,
+            request_size_limit(max_request_bytes),
+        ))
+        .add_service(admin_server)
+        .serve_with_shutdown(addr, async {
+            // Ctrl-C / SIGINT, stop accepting and let in-flight requests finish
+            tokio::signal::ctrl_c().await.ok();
+            println!("Shutting down");
+        })
         .await?;
 
+    if let Some(state_path) = &server_config.sampler_state_path {
+        save_sampler(&persisted_sampler, state_path).await?;
+        println!("Saved sampler state to {}", state_path.display());
+    }
+
     Ok(())
 }
@@ -0,0 +1,815 @@
+// Portfolio construction routines that work on estimated moments (expected returns,
+// covariance) rather than on simulated scenarios.
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::linalg::{LinalgError, solve_linear_system};
+use crate::lp::{LinearProgram, LpError, Relation, minimize};
+use crate::performance::FLOAT_COMPARISON_EPSILON;
+use crate::stats::sample_covariance;
+
+fn quadratic_form(x: &[f64], mat: &[Vec<f64>]) -> f64 {
+    x.iter()
+        .zip(mat)
+        .map(|(x_i, row)| x_i * row.iter().zip(x).map(|(m_ij, x_j)| m_ij * x_j).sum::<f64>())
+        .sum()
+}
+
+/// Tracking error variance `(w - w_target)^T cov (w - w_target)`.
+pub fn tracking_error_variance(weights: &[f64], target_weights: &[f64], cov: &[Vec<f64>]) -> f64 {
+    let active: Vec<f64> = weights.iter().zip(target_weights).map(|(w, t)| w - t).collect();
+    quadratic_form(&active, cov)
+}
+
+// Best weights restricted to `support`, keeping the same total weight as the target.
+// KKT system: [cov_SS 1; 1^T 0] [w_S; -lambda] = [(cov w_target)_S; sum(w_target)]
+fn best_weights_on_support(
+    support: &[usize],
+    target_weights: &[f64],
+    cov: &[Vec<f64>],
+) -> Result<Vec<f64>, LinalgError> {
+    let k = support.len();
+    let cov_target: Vec<f64> = cov
+        .iter()
+        .map(|row| row.iter().zip(target_weights).map(|(c, t)| c * t).sum())
+        .collect();
+
+    let mut kkt = vec![vec![0.0; k + 1]; k + 1];
+    let mut rhs = vec![0.0; k + 1];
+    for (a, &i) in support.iter().enumerate() {
+        for (b, &j) in support.iter().enumerate() {
+            kkt[a][b] = cov[i][j];
+        }
+        kkt[a][k] = 1.0;
+        kkt[k][a] = 1.0;
+        rhs[a] = cov_target[i];
+    }
+    rhs[k] = target_weights.iter().sum();
+
+    let solution = solve_linear_system(&kkt, &rhs)?;
+    let mut weights = vec![0.0; target_weights.len()];
+    for (a, &i) in support.iter().enumerate() {
+        weights[i] = solution[a];
+    }
+    Ok(weights)
+}
+
+/// Replicates `target_weights` with at most `max_positions` non-zero weights by greedy forward
+/// selection: at each step add the asset that lowers the tracking error variance the most, then
+/// re-solve the equality-constrained QP on the selected support. With
+/// `max_positions >= n_assets` this recovers the target exactly.
+pub fn minimize_tracking_error(
+    target_weights: &[f64],
+    cov: &[Vec<f64>],
+    n_assets: usize,
+    max_positions: usize,
+) -> Vec<f64> {
+    if target_weights.len() != n_assets || cov.len() != n_assets {
+        panic!(
+            "Configuration Error: expected {} assets, got {} target weights and a {}x{} covariance.",
+            n_assets,
+            target_weights.len(),
+            cov.len(),
+            cov.len()
+        );
+    }
+    if max_positions >= n_assets {
+        return target_weights.to_vec(); // nothing to trim
+    }
+
+    let mut support: Vec<usize> = Vec::with_capacity(max_positions);
+    let mut best_weights = vec![0.0; n_assets];
+
+    for _ in 0..max_positions {
+        let candidate = (0..n_assets)
+            .into_par_iter()
+            .filter(|asset| !support.contains(asset))
+            .filter_map(|asset| {
+                let mut trial = support.clone();
+                trial.push(asset);
+                // a singular KKT system just means this asset adds nothing new
+                let weights = best_weights_on_support(&trial, target_weights, cov).ok()?;
+                let objective = tracking_error_variance(&weights, target_weights, cov);
+                Some((asset, weights, objective))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+
+        match candidate {
+            Some((asset, weights, _)) => {
+                support.push(asset);
+                best_weights = weights;
+            }
+            None => break,
+        }
+    }
+
+    best_weights
+}
+
+// Minimum variance weights for one target return, fully invested, shorting allowed.
+// KKT system: [cov mu 1; mu^T 0 0; 1^T 0 0] [w; -l1; -l2] = [0; target; 1]
+fn frontier_weights(expected_returns: &[f64], cov: &[Vec<f64>], target: f64) -> Result<Vec<f64>, LinalgError> {
+    let n = expected_returns.len();
+    let mut kkt = vec![vec![0.0; n + 2]; n + 2];
+    for i in 0..n {
+        kkt[i][..n].copy_from_slice(&cov[i]);
+        kkt[i][n] = expected_returns[i];
+        kkt[i][n + 1] = 1.0;
+        kkt[n][i] = expected_returns[i];
+        kkt[n + 1][i] = 1.0;
+    }
+    let mut rhs = vec![0.0; n + 2];
+    rhs[n] = target;
+    rhs[n + 1] = 1.0;
+
+    let mut solution = solve_linear_system(&kkt, &rhs)?;
+    solution.truncate(n);
+    Ok(solution)
+}
+
+// Minimum variance weights on `free` (every other weight at 0), fully invested and, when
+// `target` is given, returning it. Also returns the multipliers y_b, y_r of the KKT system
+// [cov_FF 1 mu_F; 1^T 0 0; mu_F^T 0 0] [w_F; y_b; y_r] = [0; 1; target].
+fn min_variance_on_free_set(
+    expected_returns: &[f64],
+    cov: &[Vec<f64>],
+    free: &[usize],
+    target: Option<f64>,
+) -> Result<(Vec<f64>, f64, f64), LinalgError> {
+    let k = free.len();
+    let size = if target.is_some() { k + 2 } else { k + 1 };
+    let mut kkt = vec![vec![0.0; size]; size];
+    let mut rhs = vec![0.0; size];
+    for (a, &i) in free.iter().enumerate() {
+        for (b, &j) in free.iter().enumerate() {
+            kkt[a][b] = cov[i][j];
+        }
+        kkt[a][k] = 1.0;
+        kkt[k][a] = 1.0;
+        if target.is_some() {
+            kkt[a][k + 1] = expected_returns[i];
+            kkt[k + 1][a] = expected_returns[i];
+        }
+    }
+    rhs[k] = 1.0;
+    if let Some(target) = target {
+        rhs[k + 1] = target;
+    }
+
+    let solution = solve_linear_system(&kkt, &rhs)?;
+    let mut weights = vec![0.0; expected_returns.len()];
+    for (a, &i) in free.iter().enumerate() {
+        weights[i] = solution[a];
+    }
+    Ok((weights, solution[k], if target.is_some() { solution[k + 1] } else { 0.0 }))
+}
+
+// Primal active-set method (Nocedal & Wright, algorithm 16.3) for
+//   min w^T cov w  s.t.  1^T w = 1,  mu^T w = target (if given),  w >= 0
+// from `start`, which must be feasible. Assets outside `universe` stay at 0. The working set is
+// the positions at 0: each iteration solves the KKT system on the others, then either moves
+// toward its solution (stopping where a weight hits 0, which joins the working set) or, once
+// there, releases the bound with the most negative multiplier. Done when none is negative.
+fn long_only_min_variance(
+    expected_returns: &[f64],
+    cov: &[Vec<f64>],
+    universe: &[usize],
+    target: Option<f64>,
+    start: Vec<f64>,
+) -> Result<Vec<f64>, LinalgError> {
+    let mut weights = start;
+    let mut at_bound: Vec<bool> = universe.iter().map(|&i| weights[i] <= 0.0).collect();
+    // every iteration adds or releases one bound, a warm start needs only a few of them
+    for _ in 0..10 * universe.len() + 10 {
+        let free: Vec<usize> = universe
+            .iter()
+            .zip(&at_bound)
+            .filter(|(_, bound)| !**bound)
+            .map(|(&i, _)| i)
+            .collect();
+        let (optimum, y_budget, y_return) = min_variance_on_free_set(expected_returns, cov, &free, target)?;
+        let step: Vec<f64> = optimum.iter().zip(&weights).map(|(o, w)| o - w).collect();
+
+        if step.iter().all(|s| s.abs() < FLOAT_COMPARISON_EPSILON) {
+            // multiplier of w_i >= 0 is (cov w)_i + y_b + y_r mu_i
+            let most_negative = universe
+                .iter()
+                .enumerate()
+                .filter(|(slot, _)| at_bound[*slot])
+                .map(|(slot, &i)| {
+                    let marginal_variance = cov[i].iter().zip(&optimum).map(|(c, w)| c * w).sum::<f64>();
+                    (slot, marginal_variance + y_budget + y_return * expected_returns[i])
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match most_negative {
+                Some((slot, multiplier)) if multiplier < -FLOAT_COMPARISON_EPSILON => at_bound[slot] = false,
+                _ => return Ok(optimum),
+            }
+            weights = optimum;
+        } else {
+            // longest step toward the optimum that keeps every weight >= 0
+            let mut length = 1.0;
+            let mut blocking = None;
+            for (slot, &i) in universe.iter().enumerate() {
+                if !at_bound[slot] && step[i] < 0.0 && -weights[i] / step[i] < length {
+                    length = -weights[i] / step[i];
+                    blocking = Some(slot);
+                }
+            }
+            for (w, s) in weights.iter_mut().zip(&step) {
+                *w += length * s;
+            }
+            if let Some(slot) = blocking {
+                at_bound[slot] = true;
+                weights[universe[slot]] = 0.0;
+            }
+        }
+    }
+    Ok(weights) // still feasible, only the last refinement is missing
+}
+
+// Long-only minimum variance weights for one target return, warm-started from `previous`, the
+// optimum at an adjacent target, when there is one.
+fn long_only_frontier_weights(
+    expected_returns: &[f64],
+    cov: &[Vec<f64>],
+    target: f64,
+    previous: Option<&(f64, Vec<f64>)>,
+) -> Result<Vec<f64>, LinalgError> {
+    let n = expected_returns.len();
+    let by_return = |a: &usize, b: &usize| expected_returns[*a].total_cmp(&expected_returns[*b]);
+    let (Some(lowest_asset), Some(highest_asset)) = ((0..n).min_by(by_return), (0..n).max_by(by_return)) else {
+        return Err(LinalgError::Singular); // no assets
+    };
+    let lowest = expected_returns[lowest_asset];
+    let highest = expected_returns[highest_asset];
+
+    if target <= lowest + FLOAT_COMPARISON_EPSILON || target >= highest - FLOAT_COMPARISON_EPSILON {
+        // at either end only the assets with that expected return can be held, and the budget
+        // alone pins the return
+        let universe: Vec<usize> = (0..n)
+            .filter(|&i| (expected_returns[i] - target).abs() <= FLOAT_COMPARISON_EPSILON)
+            .collect();
+        let mut start = vec![0.0; n];
+        for &i in &universe {
+            start[i] = 1.0 / universe.len() as f64;
+        }
+        return long_only_min_variance(expected_returns, cov, &universe, None, start);
+    }
+
+    let mut start = vec![0.0; n];
+    match previous {
+        // shift from the adjacent optimum toward the extreme asset on the target's side, just
+        // far enough to reach the target
+        Some((previous_target, previous_weights)) => {
+            let toward = if target >= *previous_target { highest_asset } else { lowest_asset };
+            let share = (target - previous_target) / (expected_returns[toward] - previous_target);
+            for (s, w) in start.iter_mut().zip(previous_weights) {
+                *s = w * (1.0 - share);
+            }
+            start[toward] += share;
+        }
+        None => {
+            let share = (target - lowest) / (highest - lowest);
+            start[highest_asset] = share;
+            start[lowest_asset] = 1.0 - share;
+        }
+    }
+    let universe: Vec<usize> = (0..n).collect();
+    long_only_min_variance(expected_returns, cov, &universe, Some(target), start)
+}
+
+/// Efficient frontier (fully invested) as `(target_return, volatility)` pairs, at `n_points`
+/// targets evenly spaced between the lowest and highest expected asset return. One QP per
+/// point: with shorting allowed that is a single KKT solve, with `long_only` an active-set
+/// solve warm-started from the previous point's optimum, whose active set rarely changes
+/// between adjacent targets (the segments of the critical line). Points are split into one
+/// contiguous run per rayon thread, each run warm-starting along itself.
+/// Fails if the covariance is singular, or all expected returns are equal with shorting allowed.
+pub fn compute_efficient_frontier_parallel(
+    expected_returns: &[f64],
+    cov: &[Vec<f64>],
+    n_points: usize,
+    long_only: bool,
+) -> Result<Vec<(f64, f64)>, LinalgError> {
+    let n = expected_returns.len();
+    if cov.len() != n {
+        panic!(
+            "Configuration Error: {} expected returns for a {}x{} covariance.",
+            n,
+            cov.len(),
+            cov.len()
+        );
+    }
+
+    let lowest = expected_returns.iter().copied().fold(f64::INFINITY, f64::min);
+    let highest = expected_returns.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let step = if n_points > 1 { (highest - lowest) / (n_points - 1) as f64 } else { 0.0 };
+    let targets: Vec<f64> = (0..n_points).map(|point| lowest + step * point as f64).collect();
+
+    if !long_only {
+        return targets
+            .par_iter()
+            .map(|&target| {
+                let weights = frontier_weights(expected_returns, cov, target)?;
+                Ok((target, quadratic_form(&weights, cov).max(0.0).sqrt()))
+            })
+            .collect();
+    }
+
+    let run_length = n_points.div_ceil(rayon::current_num_threads()).max(1);
+    let runs = targets
+        .par_chunks(run_length)
+        .map(|run| {
+            let mut points = Vec::with_capacity(run.len());
+            let mut previous: Option<(f64, Vec<f64>)> = None;
+            for &target in run {
+                let weights = long_only_frontier_weights(expected_returns, cov, target, previous.as_ref())?;
+                points.push((target, quadratic_form(&weights, cov).max(0.0).sqrt()));
+                previous = Some((target, weights));
+            }
+            Ok(points)
+        })
+        .collect::<Result<Vec<Vec<(f64, f64)>>, LinalgError>>()?;
+    Ok(runs.into_iter().flatten().collect())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinCvarSolution {
+    pub weights: Vec<f64>,
+    pub value_at_risk: f64,             // alpha at the optimum, as a positive loss
+    pub conditional_value_at_risk: f64, // the minimized objective
+}
+
+/// Long-only, fully invested portfolio with the smallest CVaR at `confidence` whose mean
+/// scenario return is at least `target_return`, via the Rockafellar-Uryasev (2000) LP:
+///
+///   min  alpha + 1 / ((1 - q) S) * sum_k z_k
+///   s.t. z_k >= -r_k^T w - alpha,  z_k >= 0,  sum(w) = 1,  mean(r)^T w >= target,  w >= 0
+///
+/// `scenarios` holds one row of asset returns per scenario. alpha is free, so it is split into
+/// alpha+ - alpha- for the simplex.
+pub fn optimize_min_cvar(
+    scenarios: &[Vec<f64>],
+    n_assets: usize,
+    target_return: f64,
+    confidence: f64,
+) -> Result<MinCvarSolution, LpError> {
+    if scenarios.is_empty() || scenarios.iter().any(|row| row.len() != n_assets) {
+        panic!(
+            "Configuration Error: min-CVaR needs at least one scenario of {} asset returns.",
+            n_assets
+        );
+    }
+    if !(0.0..1.0).contains(&confidence) {
+        panic!("Configuration Error: confidence must be in [0, 1) (found {}).", confidence);
+    }
+
+    let n_scenarios = scenarios.len();
+    // variables: w (n_assets), alpha+, alpha-, z (n_scenarios)
+    let alpha_plus = n_assets;
+    let alpha_minus = n_assets + 1;
+    let z = |k: usize| n_assets + 2 + k;
+    let n_vars = n_assets + 2 + n_scenarios;
+
+    let mut objective = vec![0.0; n_vars];
+    objective[alpha_plus] = 1.0;
+    objective[alpha_minus] = -1.0;
+    let tail_weight = 1.0 / ((1.0 - confidence) * n_scenarios as f64);
+    for k in 0..n_scenarios {
+        objective[z(k)] = tail_weight;
+    }
+
+    let mut constraints = Vec::with_capacity(n_scenarios + 2);
+    for (k, returns) in scenarios.iter().enumerate() {
+        // -r_k^T w - alpha - z_k <= 0
+        let mut row = vec![0.0; n_vars];
+        for (coefficient, r) in row.iter_mut().zip(returns) {
+            *coefficient = -r;
+        }
+        row[alpha_plus] = -1.0;
+        row[alpha_minus] = 1.0;
+        row[z(k)] = -1.0;
+        constraints.push((row, Relation::LessEq, 0.0));
+    }
+    let mut budget = vec![0.0; n_vars];
+    budget[..n_assets].fill(1.0);
+    constraints.push((budget, Relation::Equal, 1.0));
+    let mut target = vec![0.0; n_vars];
+    for returns in scenarios {
+        for (coefficient, r) in target.iter_mut().zip(returns) {
+            *coefficient += r / n_scenarios as f64;
+        }
+    }
+    constraints.push((target, Relation::GreaterEq, target_return));
+
+    let x = minimize(&LinearProgram { objective: objective.clone(), constraints })?;
+    let conditional_value_at_risk = x.iter().zip(&objective).map(|(x_j, c_j)| x_j * c_j).sum();
+    Ok(MinCvarSolution {
+        weights: x[..n_assets].to_vec(),
+        value_at_risk: x[alpha_plus] - x[alpha_minus],
+        conditional_value_at_risk,
+    })
+}
+
+/// Round-up heuristic for impractically small positions: weights with `|w| < min_position_size`
+/// are zeroed and the rest rescaled to the original total. Returns how many were zeroed.
+/// If every position is too small the largest one is kept, rather than zeroing the portfolio.
+pub fn enforce_min_position_size(weights: &mut [f64], min_position_size: f64) -> u32 {
+    let total = weights.iter().sum::<f64>();
+    let largest = weights
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .map(|(idx, _)| idx);
+
+    let mut zeroed = 0;
+    for (idx, weight) in weights.iter_mut().enumerate() {
+        if *weight != 0.0 && weight.abs() < min_position_size && Some(idx) != largest {
+            *weight = 0.0;
+            zeroed += 1;
+        }
+    }
+
+    let remaining = weights.iter().sum::<f64>();
+    if zeroed > 0 && remaining.abs() >= FLOAT_COMPARISON_EPSILON {
+        let scale = total / remaining;
+        for weight in weights.iter_mut() {
+            *weight *= scale;
+        }
+    }
+    zeroed
+}
+
+/// Michaud resampled efficiency: bootstrap `returns` (one row of asset returns per
+/// observation) `n_resamples` times, solve the minimum-variance portfolio at `target_return`
+/// on each resample's mean and covariance, and average the weights. The average is much less
+/// sensitive to estimation error than the single-sample optimum. Resamples whose covariance
+/// comes out singular are skipped, the error is returned only if all of them are.
+/// `covariance_ddof` is passed to `sample_covariance` (1 matches numpy.cov).
+pub fn michaud_resample(
+    returns: &[Vec<f64>],
+    n_resamples: usize,
+    target_return: f64,
+    covariance_ddof: u32,
+) -> Result<Vec<f64>, LinalgError> {
+    let n_observations = returns.len();
+    let n_assets = returns.first().map_or(0, Vec::len);
+    if n_observations < 2 || n_resamples == 0 {
+        panic!(
+            "Configuration Error: resampling needs at least 2 observations and 1 resample (found {} and {}).",
+            n_observations, n_resamples
+        );
+    }
+    if covariance_ddof as usize >= n_observations {
+        panic!(
+            "Configuration Error: covariance_ddof must be below the number of observations (found {} for {}).",
+            covariance_ddof, n_observations
+        );
+    }
+
+    let resampled: Vec<Result<Vec<f64>, LinalgError>> = (0..n_resamples)
+        .into_par_iter()
+        .map_init(rand::rng, |rng, _| {
+            let sample: Vec<Vec<f64>> = (0..n_observations)
+                .map(|_| returns[rng.random_range(0..n_observations)].clone())
+                .collect();
+            let expected_returns: Vec<f64> = (0..n_assets)
+                .map(|j| sample.iter().map(|row| row[j]).sum::<f64>() / n_observations as f64)
+                .collect();
+            let cov = sample_covariance(&sample, covariance_ddof);
+            frontier_weights(&expected_returns, &cov, target_return)
+        })
+        .collect();
+
+    let mut sum = vec![0.0; n_assets];
+    let mut solved = 0;
+    let mut last_error = None;
+    for weights in resampled {
+        match weights {
+            Ok(weights) => {
+                for (total, w) in sum.iter_mut().zip(&weights) {
+                    *total += w;
+                }
+                solved += 1;
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) if solved == 0 => Err(e),
+        _ => Ok(sum.into_iter().map(|total| total / f64::from(solved)).collect()),
+    }
+}
+
+/// Closest weights (in the L2 sense) with zero beta: the projection of `weights` onto the
+/// hyperplane `sum(w_i beta_i) = 0`, `w - (beta.w / beta.beta) beta`. All-zero betas are
+/// already neutral and come back unchanged. The total weight is not preserved.
+pub fn make_market_neutral(weights: &[f64], betas: &[f64]) -> Vec<f64> {
+    if weights.len() != betas.len() {
+        panic!(
+            "Configuration Error: {} weights for {} betas.",
+            weights.len(),
+            betas.len()
+        );
+    }
+    let beta_norm_sq = betas.iter().map(|b| b * b).sum::<f64>();
+    if beta_norm_sq < FLOAT_COMPARISON_EPSILON {
+        return weights.to_vec();
+    }
+    let portfolio_beta = weights.iter().zip(betas).map(|(w, b)| w * b).sum::<f64>();
+    let step = portfolio_beta / beta_norm_sq;
+    weights.iter().zip(betas).map(|(w, b)| w - step * b).collect()
+}
+
+/// Ex-ante Sharpe (risk-free rate 0) of `weights` under `expected_returns` and `cov`.
+fn ex_ante_sharpe(weights: &[f64], expected_returns: &[f64], cov: &[Vec<f64>]) -> f64 {
+    let mean = weights.iter().zip(expected_returns).map(|(w, mu)| w * mu).sum::<f64>();
+    let volatility = quadratic_form(weights, cov).max(0.0).sqrt();
+    if volatility < FLOAT_COMPARISON_EPSILON { f64::NEG_INFINITY } else { mean / volatility }
+}
+
+/// Tangency (max ex-ante Sharpe) portfolio holding at most `max_assets` positions, fully
+/// invested. Heuristic: assets ranked by expected return, then for every k <= `max_assets` the
+/// unconstrained tangency weights `cov_S^-1 mu_S` on the top k are solved and the subset with
+/// the best Sharpe wins. That is `max_assets` linear solves, cheap for the K <= 20, N <= 100
+/// mandates this is meant for. Subsets with a singular covariance are skipped.
+pub fn cardinality_constrained_optimize(
+    expected_returns: &[f64],
+    cov: &[Vec<f64>],
+    max_assets: usize,
+) -> Result<Vec<f64>, LinalgError> {
+    let n = expected_returns.len();
+    if cov.len() != n || max_assets == 0 {
+        panic!(
+            "Configuration Error: {} expected returns for a {}x{} covariance, max_assets {}.",
+            n,
+            cov.len(),
+            cov.len(),
+            max_assets
+        );
+    }
+    let mut ranked: Vec<usize> = (0..n).collect();
+    ranked.sort_unstable_by(|&a, &b| expected_returns[b].total_cmp(&expected_returns[a]));
+
+    let candidates: Vec<Result<Vec<f64>, LinalgError>> = (1..=max_assets.min(n))
+        .into_par_iter()
+        .map(|k| {
+            let support = &ranked[..k];
+            let cov_s: Vec<Vec<f64>> = support
+                .iter()
+                .map(|&i| support.iter().map(|&j| cov[i][j]).collect())
+                .collect();
+            let mu_s: Vec<f64> = support.iter().map(|&i| expected_returns[i]).collect();
+            let direction = solve_linear_system(&cov_s, &mu_s)?;
+            let total = direction.iter().sum::<f64>();
+            if total.abs() < FLOAT_COMPARISON_EPSILON {
+                return Err(LinalgError::Singular); // can't be scaled to fully invested
+            }
+            let mut weights = vec![0.0; n];
+            for (&i, d) in support.iter().zip(&direction) {
+                weights[i] = d / total;
+            }
+            Ok(weights)
+        })
+        .collect();
+
+    let mut best: Option<(Vec<f64>, f64)> = None;
+    let mut last_error = LinalgError::Singular;
+    for candidate in candidates {
+        match candidate {
+            Ok(weights) => {
+                let sharpe = ex_ante_sharpe(&weights, expected_returns, cov);
+                if best.as_ref().is_none_or(|(_, best_sharpe)| sharpe > *best_sharpe) {
+                    best = Some((weights, sharpe));
+                }
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    best.map(|(weights, _)| weights).ok_or(last_error)
+}
+
+/// Jacobian `dw/dmu` of the fully invested mean-variance optimum (`max mu.w - w' cov w / 2`,
+/// risk aversion 1) around `base_weights`, by QP sensitivity: positions at zero (bound active)
+/// stay there, and on the free set F
+/// `J_FF = cov_F^-1 - cov_F^-1 1 1' cov_F^-1 / (1' cov_F^-1 1)`, zero outside F. Rows sum to 0
+/// since a change in expected returns only moves weight between assets. A large norm means the
+/// optimum leans hard on the return estimates, typically when a few assets carry the portfolio.
+pub fn return_sensitivity_jacobian(base_weights: &[f64], cov: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, LinalgError> {
+    let n = base_weights.len();
+    if cov.len() != n {
+        panic!(
+            "Configuration Error: {} weights for a {}x{} covariance.",
+            n,
+            cov.len(),
+            cov.len()
+        );
+    }
+    let free: Vec<usize> = (0..n).filter(|&i| base_weights[i].abs() > FLOAT_COMPARISON_EPSILON).collect();
+    let cov_f: Vec<Vec<f64>> = free.iter().map(|&i| free.iter().map(|&j| cov[i][j]).collect()).collect();
+
+    // cov_F^-1 column by column (it's symmetric, so these are also its rows)
+    let inverse = (0..free.len())
+        .map(|k| {
+            let unit: Vec<f64> = (0..free.len()).map(|i| if i == k { 1.0 } else { 0.0 }).collect();
+            solve_linear_system(&cov_f, &unit)
+        })
+        .collect::<Result<Vec<Vec<f64>>, LinalgError>>()?;
+    let row_sums: Vec<f64> = inverse.iter().map(|row| row.iter().sum()).collect(); // cov_F^-1 1
+    let total = row_sums.iter().sum::<f64>(); // 1' cov_F^-1 1, > 0 for a positive definite cov_F
+
+    let mut jacobian = vec![vec![0.0; n]; n];
+    for (a, &i) in free.iter().enumerate() {
+        for (b, &j) in free.iter().enumerate() {
+            jacobian[i][j] = inverse[a][b] - row_sums[a] * row_sums[b] / total;
+        }
+    }
+    Ok(jacobian)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnSensitivity {
+    pub jacobian: Vec<Vec<f64>>,  // from return_sensitivity_jacobian
+    pub weight_changes: Vec<f64>, // J delta, empty without a delta_returns
+}
+
+/// The Jacobian around `base_weights` and the first order change in the optimal weights for a
+/// shift `delta_returns` in the expected returns, `J delta`. An empty `delta_returns` skips the latter.
+pub fn return_sensitivity_analysis(
+    base_weights: &[f64],
+    cov: &[Vec<f64>],
+    delta_returns: &[f64],
+) -> Result<ReturnSensitivity, LinalgError> {
+    if !delta_returns.is_empty() && delta_returns.len() != base_weights.len() {
+        panic!(
+            "Configuration Error: {} return shifts for {} weights.",
+            delta_returns.len(),
+            base_weights.len()
+        );
+    }
+    let jacobian = return_sensitivity_jacobian(base_weights, cov)?;
+    let weight_changes = if delta_returns.is_empty() {
+        Vec::new()
+    } else {
+        jacobian
+            .iter()
+            .map(|row| row.iter().zip(delta_returns).map(|(j, d)| j * d).sum())
+            .collect()
+    };
+    Ok(ReturnSensitivity {
+        jacobian,
+        weight_changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Asset 1 is dominated by 0 and 2 and the unconstrained frontier shorts it below a 12% target
+    fn three_assets() -> (Vec<f64>, Vec<Vec<f64>>) {
+        let vols = [0.2, 0.3, 0.25];
+        let corr = [[1.0, 0.9, 0.2], [0.9, 1.0, 0.3], [0.2, 0.3, 1.0]];
+        let cov = (0..3)
+            .map(|i| (0..3).map(|j| vols[i] * vols[j] * corr[i][j]).collect())
+            .collect();
+        (vec![0.05, 0.10, 0.15], cov)
+    }
+
+    // The budget and the target leave one degree of freedom, w_2 on a fine grid
+    fn brute_force_long_only_volatility(expected_returns: &[f64], cov: &[Vec<f64>], target: f64) -> f64 {
+        let (mu_0, mu_1, mu_2) = (expected_returns[0], expected_returns[1], expected_returns[2]);
+        (0..=100_000)
+            .filter_map(|k| {
+                let w_2 = k as f64 / 100_000.0;
+                let w_1 = (target - mu_2 * w_2 - mu_0 * (1.0 - w_2)) / (mu_1 - mu_0);
+                let w_0 = 1.0 - w_2 - w_1;
+                (w_0 >= -1e-12 && w_1 >= -1e-12).then(|| quadratic_form(&[w_0, w_1, w_2], cov).sqrt())
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn long_only_frontier_matches_a_brute_force_search() {
+        let (expected_returns, cov) = three_assets();
+        let frontier = compute_efficient_frontier_parallel(&expected_returns, &cov, 11, true).unwrap();
+        assert_eq!(frontier.len(), 11);
+        assert!((frontier[0].1 - 0.2).abs() < 1e-12); // all in asset 0
+        assert!((frontier[10].1 - 0.25).abs() < 1e-12); // all in asset 2
+        for (target, volatility) in frontier {
+            let expected = brute_force_long_only_volatility(&expected_returns, &cov, target);
+            assert!((volatility - expected).abs() < 1e-6, "{}: {} vs {}", target, volatility, expected);
+        }
+    }
+
+    #[test]
+    fn warm_and_cold_starts_agree() {
+        let (expected_returns, cov) = three_assets();
+        let mut previous = None;
+        for k in 0..=10 {
+            let target = 0.05 + 0.01 * k as f64;
+            let warm = long_only_frontier_weights(&expected_returns, &cov, target, previous.as_ref()).unwrap();
+            let cold = long_only_frontier_weights(&expected_returns, &cov, target, None).unwrap();
+            for (w, c) in warm.iter().zip(&cold) {
+                assert!(*w >= -1e-12 && (w - c).abs() < 1e-9);
+            }
+            previous = Some((target, warm));
+        }
+    }
+
+    #[test]
+    fn shorting_only_lowers_the_frontier() {
+        let (expected_returns, cov) = three_assets();
+        let long_only = compute_efficient_frontier_parallel(&expected_returns, &cov, 11, true).unwrap();
+        let shorting = compute_efficient_frontier_parallel(&expected_returns, &cov, 11, false).unwrap();
+        for ((target, constrained), (_, unconstrained)) in long_only.iter().zip(&shorting) {
+            assert!(unconstrained <= &(constrained + 1e-12), "{}", target);
+        }
+        assert!(shorting[3].1 < long_only[3].1 - 0.01); // 8%, shorting asset 1
+        assert!((shorting[7].1 - long_only[7].1).abs() < 1e-9); // 12%, already long-only
+    }
+
+    fn frobenius_norm(matrix: &[Vec<f64>]) -> f64 {
+        matrix.iter().flatten().map(|x| x * x).sum::<f64>().sqrt()
+    }
+
+    #[test]
+    fn concentrated_portfolios_are_sensitive_to_return_estimates() {
+        let (vol, rho) = (0.2_f64, 0.98);
+        let variance = vol * vol;
+        let cov = vec![
+            vec![variance, rho * variance, 0.0],
+            vec![rho * variance, variance, 0.0],
+            vec![0.0, 0.0, variance],
+        ];
+        let delta = [0.01, 0.0, 0.0];
+
+        // everything in two near-duplicates: a small return edge swings the weight between them
+        let concentrated = return_sensitivity_analysis(&[0.5, 0.5, 0.0], &cov, &delta).unwrap();
+        // on the free set {0, 1}, J = [[1, -1], [-1, 1]] / (2 vol^2 (1 - rho))
+        let expected_norm = 1.0 / (variance * (1.0 - rho));
+        assert!((frobenius_norm(&concentrated.jacobian) - expected_norm).abs() < 1e-6 * expected_norm);
+        assert_eq!(concentrated.jacobian[2], vec![0.0; 3]);
+        assert!((concentrated.weight_changes[0] + concentrated.weight_changes[1]).abs() < 1e-9);
+
+        let identity_cov: Vec<Vec<f64>> =
+            (0..3).map(|i| (0..3).map(|j| if i == j { variance } else { 0.0 }).collect()).collect();
+        let diversified = return_sensitivity_analysis(&[1.0 / 3.0; 3], &identity_cov, &[]).unwrap();
+        assert!(diversified.weight_changes.is_empty());
+        assert!(frobenius_norm(&concentrated.jacobian) > 10.0 * frobenius_norm(&diversified.jacobian));
+    }
+
+    // Uncorrelated assets: on a support S the KKT system gives w_i = t_i + lambda / var_i with
+    // lambda = (sum(t) - sum_S t) / sum_S 1 / var_i, and zero off the support
+    fn diagonal_tracking_problem() -> (Vec<f64>, Vec<Vec<f64>>) {
+        let variances = [0.04, 0.09, 0.01];
+        let cov = (0..3)
+            .map(|i| (0..3).map(|j| if i == j { variances[i] } else { 0.0 }).collect())
+            .collect();
+        (vec![0.5, 0.3, 0.2], cov)
+    }
+
+    #[test]
+    fn tracking_with_every_position_reproduces_the_target() {
+        let (target, cov) = diagonal_tracking_problem();
+        assert_eq!(minimize_tracking_error(&target, &cov, 3, 3), target);
+        // the KKT system on the full support agrees
+        let weights = best_weights_on_support(&[0, 1, 2], &target, &cov).unwrap();
+        assert!(weights.iter().zip(&target).all(|(w, t)| (w - t).abs() < 1e-12));
+        assert!(tracking_error_variance(&weights, &target, &cov) < 1e-20);
+    }
+
+    #[test]
+    fn tracking_with_fewer_positions_solves_the_kkt_system() {
+        let (target, cov) = diagonal_tracking_problem();
+        let weights = minimize_tracking_error(&target, &cov, 3, 2);
+
+        // asset 2 is the cheapest to drop, the other two absorb its 0.2 by inverse variance
+        let lambda = 0.2 / (1.0 / 0.04 + 1.0 / 0.09);
+        let expected = [0.5 + lambda / 0.04, 0.3 + lambda / 0.09, 0.0];
+        assert!(weights.iter().zip(&expected).all(|(w, e)| (w - e).abs() < 1e-9));
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+
+        // and no other pair of assets tracks better
+        let objective = tracking_error_variance(&weights, &target, &cov);
+        for support in [[0, 2], [1, 2]] {
+            let other = best_weights_on_support(&support, &target, &cov).unwrap();
+            assert!(objective < tracking_error_variance(&other, &target, &cov));
+        }
+    }
+
+    #[test]
+    fn market_neutral_weights_have_no_beta_left() {
+        let weights = [0.4, 0.3, 0.2, 0.1];
+        let betas = [1.2, 0.8, 1.5, -0.3];
+        let neutral = make_market_neutral(&weights, &betas);
+        let beta = |w: &[f64]| w.iter().zip(&betas).map(|(w, b)| w * b).sum::<f64>();
+        assert!(beta(&weights) > 0.5);
+        assert!(beta(&neutral).abs() < 1e-12);
+        // the move is along beta only, a projection: neutral again does nothing
+        let again = make_market_neutral(&neutral, &betas);
+        assert!(again.iter().zip(&neutral).all(|(a, n)| (a - n).abs() < 1e-12));
+        assert_eq!(make_market_neutral(&weights, &[0.0; 4]), weights);
+    }
+}
@@ -0,0 +1,1204 @@
+use std::fmt;
+
+use aegis_athena_contracts::simulation::PortfolioMetrics;
+use rayon::prelude::*;
+
+use crate::rebalancing::{RebalancingStrategy, band_portfolio_returns};
+use crate::stats::{RunningStats, mean_and_std, normal_inv_cdf, sorted_quantile};
+
+pub const FLOAT_COMPARISON_EPSILON: f64 = 1e-9; // it would be a good idea to add this to athena-contracts or some central thing
+
+/// Tolerance for "is this dollar amount zero", relative to the money invested. A fixed 1e-9 is
+/// far below the round-off of a 1e15 fund's dollar returns, so near-zero values there never
+/// compare as zero.
+pub fn adaptive_epsilon(money: f64) -> f64 {
+    money.abs() * FLOAT_COMPARISON_EPSILON
+}
+
+/// Annualized `(return, volatility, Sharpe)` of dollar returns with per-period mean `period_mean`
+/// and standard deviation `period_std`. Shared by the plain, hedged and insured figures so their
+/// Sharpes stay comparable. No sign guard on `risk_free_rate`, negative rates are a thing.
+/// Volatility that is effectively zero makes for a useless portfolio, its Sharpe is capped at 0.
+pub fn annualized_sharpe(
+    period_mean: f64,
+    period_std: f64,
+    periods_per_year: f64,
+    money_to_invest: f64,
+    risk_free_rate: f64,
+) -> (f64, f64, f64) {
+    let annualized_return = period_mean * periods_per_year;
+    let annualized_volatility = period_std * periods_per_year.sqrt();
+    let risk_free_return = money_to_invest * risk_free_rate; // annual dollar risk-free
+    let sharpe_ratio = if annualized_volatility.abs() >= adaptive_epsilon(money_to_invest) {
+        (annualized_return - risk_free_return) / annualized_volatility
+    } else {
+        0.0
+    };
+    (annualized_return, annualized_volatility, sharpe_ratio)
+}
+
+pub const VAR_CONFIDENCE: f64 = 0.95;
+
+// Anything beyond this is almost certainly a numerical artefact, not a great portfolio
+pub const DEFAULT_MAX_SHARPE: f64 = 20.0;
+
+// Relative, the contributions are summed in a different order than the portfolio returns
+const SHARPE_CONTRIBUTION_TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    NonFinite { field: &'static str, value: f64 },
+    NegativeVolatility(f64),
+    ImplausibleSharpe { sharpe_ratio: f64, max_sharpe: f64 },
+    SharpeContributionMismatch { sum: f64, sharpe_ratio: f64 },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NonFinite { field, value } => write!(f, "{} is not finite ({})", field, value),
+            ValidationError::NegativeVolatility(vol) => {
+                write!(f, "percent_annualized_volatility is negative ({})", vol)
+            }
+            ValidationError::ImplausibleSharpe {
+                sharpe_ratio,
+                max_sharpe,
+            } => write!(f, "|sharpe_ratio| = {} is above the plausible maximum {}", sharpe_ratio.abs(), max_sharpe),
+            ValidationError::SharpeContributionMismatch { sum, sharpe_ratio } => {
+                write!(f, "sharpe_contributions sum to {}, not sharpe_ratio ({})", sum, sharpe_ratio)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Metrics for a single portfolio evaluated against a single sampled scenario.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortfolioPerformance {
+    pub portfolio_returns: Vec<f64>,
+    pub annualized_return: f64,
+    pub percent_annualized_volatility: f64,
+    pub sharpe_ratio: f64,
+    pub vol_of_vol: f64, // std of per-period |returns|, as a fraction of money_to_invest
+    pub vol_of_vol_annualized: f64,
+    pub periods_per_year: f64, // used to annualize, kept so results can be compared/rescaled later
+    pub hedged_sharpe: Option<f64>,
+    pub hedged_var: Option<f64>, // variance of the hedged per-period dollar returns
+    pub hedging_effectiveness: Option<f64>,
+    pub var_historical: f64, // 95% one-period VaR from the sampled returns, as a positive dollar loss
+    pub var_ci_lower: f64,   // 95% confidence interval of var_historical (Cornish-Fisher delta method)
+    pub var_ci_upper: f64,
+    pub max_period_return: f64, // best single-period dollar return
+    pub min_period_return: f64, // worst single-period dollar return
+    pub max_period_return_fraction: f64, // same two, as fractions of money_to_invest
+    pub min_period_return_fraction: f64,
+    pub insured_sharpe: Option<f64>,
+    pub insured_return: Option<f64>, // annualized dollars, net of the put premium
+    pub insurance_cost_total: Option<f64>, // premium paid over the whole horizon, in dollars
+    pub valid_return_counts: Vec<u32>, // per asset, periods with a non-NaN return
+    pub original_periods_per_year: f64, // as computed from the config, `annualize` leaves it alone
+    pub num_rebalancing_events: u32, // constant weights rebalance between every pair of periods
+    pub portfolio_dv01: Option<f64>, // dollars lost per 1bp rate rise, bond portfolios only
+    pub portfolio_convexity: Option<f64>,
+    pub n_positive_periods: u32, // periods with a dollar return > 0
+    pub n_negative_periods: u32, // and < 0, flat periods are in neither
+    pub ulcer_index: f64, // RMS of the drawdowns of the compounded wealth, as fractions of the peak
+    pub pain_ratio: f64,  // annualized return as a fraction of money_to_invest over ulcer_index
+    pub dca_terminal_wealth: Option<f64>, // dollars, filled in by dca::apply_dca when DCA is configured
+    pub lump_sum_terminal_wealth: Option<f64>, // same periods, everything invested up front
+    pub dca_average_entry_cost: Option<f64>, // value index at entry, 1.0 = the starting value
+    pub stability_score: Option<f64>, // -std of the Sharpe under weight noise, with compute_stability
+    pub sharpe_contributions: Vec<f64>, // per asset, sums to sharpe_ratio, empty if the weights drift
+}
+
+impl PortfolioPerformance {
+    /// Scalar metrics addressable by name (custom metric expressions, exports).
+    pub const METRIC_NAMES: &'static [&'static str] = &[
+        "annualized_return",
+        "percent_annualized_volatility",
+        "sharpe_ratio",
+        "vol_of_vol",
+        "vol_of_vol_annualized",
+        "var_historical",
+        "var_ci_lower",
+        "var_ci_upper",
+        "max_period_return",
+        "min_period_return",
+        "max_period_return_fraction",
+        "min_period_return_fraction",
+        "ulcer_index",
+        "pain_ratio",
+    ];
+
+    pub fn metric(&self, name: &str) -> Option<f64> {
+        match name {
+            "annualized_return" => Some(self.annualized_return),
+            "percent_annualized_volatility" => Some(self.percent_annualized_volatility),
+            "sharpe_ratio" => Some(self.sharpe_ratio),
+            "vol_of_vol" => Some(self.vol_of_vol),
+            "vol_of_vol_annualized" => Some(self.vol_of_vol_annualized),
+            "var_historical" => Some(self.var_historical),
+            "var_ci_lower" => Some(self.var_ci_lower),
+            "var_ci_upper" => Some(self.var_ci_upper),
+            "max_period_return" => Some(self.max_period_return),
+            "min_period_return" => Some(self.min_period_return),
+            "max_period_return_fraction" => Some(self.max_period_return_fraction),
+            "min_period_return_fraction" => Some(self.min_period_return_fraction),
+            "ulcer_index" => Some(self.ulcer_index),
+            "pain_ratio" => Some(self.pain_ratio),
+            _ => None,
+        }
+    }
+
+    /// Index and dollar value of the worst single-period return, (0, NaN) without any periods.
+    pub fn worst_period(&self) -> (usize, f64) {
+        self.portfolio_returns
+            .iter()
+            .copied()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((0, f64::NAN))
+    }
+
+    /// Same for the best one.
+    pub fn best_period(&self) -> (usize, f64) {
+        self.portfolio_returns
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((0, f64::NAN))
+    }
+
+    /// Header matching `to_csv_row`: `portfolio_id`, the `METRIC_NAMES` in order, then
+    /// `periods_per_year`, the optional hedging / insurance / rate / DCA / stability metrics and
+    /// `num_rebalancing_events`, `n_positive_periods` and `n_negative_periods`.
+    pub fn csv_header() -> &'static str {
+        "portfolio_id,annualized_return,percent_annualized_volatility,sharpe_ratio,vol_of_vol,\
+         vol_of_vol_annualized,var_historical,var_ci_lower,var_ci_upper,max_period_return,\
+         min_period_return,max_period_return_fraction,min_period_return_fraction,ulcer_index,\
+         pain_ratio,periods_per_year,\
+         hedged_sharpe,hedged_var,hedging_effectiveness,insured_sharpe,insured_return,\
+         insurance_cost_total,portfolio_dv01,portfolio_convexity,dca_terminal_wealth,\
+         lump_sum_terminal_wealth,dca_average_entry_cost,stability_score,num_rebalancing_events,\
+         n_positive_periods,n_negative_periods"
+    }
+
+    /// One CSV line (no trailing newline) in `csv_header` order. Unset optional metrics are left
+    /// empty, and `portfolio_id` is quoted if it needs to be.
+    pub fn to_csv_row(&self, portfolio_id: &str) -> String {
+        let id = if portfolio_id.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", portfolio_id.replace('"', "\"\""))
+        } else {
+            portfolio_id.to_string()
+        };
+        let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+
+        let mut fields = vec![id];
+        fields.extend(Self::METRIC_NAMES.iter().map(|name| {
+            self.metric(name).expect("METRIC_NAMES are all known").to_string()
+        }));
+        fields.push(self.periods_per_year.to_string());
+        fields.extend(
+            [
+                self.hedged_sharpe,
+                self.hedged_var,
+                self.hedging_effectiveness,
+                self.insured_sharpe,
+                self.insured_return,
+                self.insurance_cost_total,
+                self.portfolio_dv01,
+                self.portfolio_convexity,
+                self.dca_terminal_wealth,
+                self.lump_sum_terminal_wealth,
+                self.dca_average_entry_cost,
+                self.stability_score,
+            ]
+            .map(optional),
+        );
+        fields.push(self.num_rebalancing_events.to_string());
+        fields.push(self.n_positive_periods.to_string());
+        fields.push(self.n_negative_periods.to_string());
+        fields.join(",")
+    }
+
+    /// Annualized volatility in dollars (`percent_annualized_volatility` is a fraction of the money).
+    pub fn annualized_dollar_volatility(&self) -> f64 {
+        let (_, volatility) = mean_and_std(&self.portfolio_returns);
+        volatility * self.periods_per_year.sqrt()
+    }
+
+    /// Annual dollar risk-free return used for the Sharpe, backed out of the Sharpe itself
+    /// (the rate isn't stored). Meaningless without volatility, the Sharpe is then just 0.
+    pub fn implied_risk_free_return(&self) -> f64 {
+        self.annualized_return - self.sharpe_ratio * self.annualized_dollar_volatility()
+    }
+
+    /// Re-annualizes as if the sampled periods had spanned `new_horizon_days` instead.
+    /// The dollar risk-free return is backed out of the current Sharpe, so it is carried over
+    /// unchanged. Going back to the original horizon restores the original figures.
+    pub fn annualize(&mut self, new_horizon_days: f64) {
+        if new_horizon_days.is_nan() || new_horizon_days <= 0.0 {
+            panic!(
+                "Configuration Error: cannot annualize over a horizon of {} days.",
+                new_horizon_days
+            );
+        }
+        let number_of_periods = self.portfolio_returns.len() as f64;
+        let new_periods_per_year = number_of_periods / (new_horizon_days / 365.0);
+        if new_periods_per_year == self.periods_per_year {
+            return; // same horizon, and the Sharpe contributions are still valid
+        }
+        let scale = new_periods_per_year / self.periods_per_year;
+
+        let annualized_volatility = self.annualized_dollar_volatility();
+        let risk_free_return = self.implied_risk_free_return();
+
+        self.annualized_return *= scale;
+        self.pain_ratio *= scale;
+        self.percent_annualized_volatility *= scale.sqrt();
+        self.vol_of_vol_annualized *= scale.sqrt();
+        let new_annualized_volatility = annualized_volatility * scale.sqrt();
+        // Zero vol stays a Sharpe of 0, same as compute_portfolio_performance. The fractional
+        // volatility against a fixed epsilon is the dollar one against adaptive_epsilon(money)
+        if self.percent_annualized_volatility.abs() >= FLOAT_COMPARISON_EPSILON {
+            self.sharpe_ratio = (self.annualized_return - risk_free_return) / new_annualized_volatility;
+        }
+        self.periods_per_year = new_periods_per_year;
+        // the risk-free share of each contribution doesn't rescale like the rest, and it isn't kept
+        self.sharpe_contributions.clear();
+    }
+
+    /// Levers (or de-levers) the portfolio so its annualized volatility hits `target_vol`,
+    /// returning the rescaled performance and the exposure `scale` applied. Dollar figures and
+    /// fractions of the money scale linearly (variances by `scale²`), period counts don't move.
+    /// The extra exposure is assumed financed at the risk-free rate, so only the excess return
+    /// scales (`rf + scale·(R − rf)`) and the Sharpe ratios are left as they are.
+    pub fn scale_to_target_vol(&self, target_vol: f64) -> (PortfolioPerformance, f64) {
+        if !target_vol.is_finite() || target_vol <= 0.0 {
+            panic!("Configuration Error: cannot scale to a target volatility of {}.", target_vol);
+        }
+        // a fraction of the money, so already scale-free (dollar volatility vs adaptive_epsilon)
+        if self.percent_annualized_volatility.abs() < FLOAT_COMPARISON_EPSILON {
+            panic!("Configuration Error: cannot scale a portfolio without volatility to a target volatility.");
+        }
+        let scale = target_vol / self.percent_annualized_volatility;
+        let money_to_invest = self.annualized_dollar_volatility() / self.percent_annualized_volatility;
+        let risk_free_return = self.implied_risk_free_return();
+
+        let mut scaled = self.clone();
+        scaled.portfolio_returns.iter_mut().for_each(|ret| *ret *= scale);
+        scaled.annualized_return = risk_free_return + scale * (self.annualized_return - risk_free_return);
+        for value in [
+            &mut scaled.percent_annualized_volatility,
+            &mut scaled.vol_of_vol,
+            &mut scaled.vol_of_vol_annualized,
+            &mut scaled.var_historical,
+            &mut scaled.var_ci_lower,
+            &mut scaled.var_ci_upper,
+            &mut scaled.max_period_return,
+            &mut scaled.min_period_return,
+            &mut scaled.max_period_return_fraction,
+            &mut scaled.min_period_return_fraction,
+        ] {
+            *value *= scale;
+        }
+        for value in [
+            &mut scaled.insured_return,
+            &mut scaled.insurance_cost_total,
+            &mut scaled.portfolio_dv01,
+            &mut scaled.portfolio_convexity,
+        ] {
+            *value = value.map(|v| v * scale);
+        }
+        scaled.hedged_var = scaled.hedged_var.map(|var| var * scale * scale);
+        // Drawdowns compound, so the ulcer index doesn't scale linearly and is recomputed
+        scaled.ulcer_index = ulcer_index(&scaled.portfolio_returns, money_to_invest);
+        scaled.pain_ratio = pain_ratio(scaled.annualized_return, money_to_invest, scaled.ulcer_index);
+        // Same for the DCA path, and the installment schedule isn't kept around to redo it
+        scaled.dca_terminal_wealth = None;
+        scaled.lump_sum_terminal_wealth = None;
+        scaled.dca_average_entry_cost = None;
+        (scaled, scale)
+    }
+
+    /// Same checks as `validate`, without collecting the details.
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
+
+    /// Every degenerate value, using `DEFAULT_MAX_SHARPE` as the plausibility bound.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        self.validate_with_max_sharpe(DEFAULT_MAX_SHARPE)
+    }
+
+    pub fn validate_with_max_sharpe(&self, max_sharpe: f64) -> Result<(), Vec<ValidationError>> {
+        let mut errors: Vec<ValidationError> = Self::METRIC_NAMES
+            .iter()
+            .filter_map(|name| {
+                let value = self.metric(name)?;
+                // a portfolio that never drew down has an infinite pain ratio, that's not degenerate
+                if *name == "pain_ratio" && value == f64::INFINITY {
+                    return None;
+                }
+                (!value.is_finite()).then_some(ValidationError::NonFinite { field: *name, value })
+            })
+            .collect();
+
+        let optional = [
+            ("hedged_sharpe", self.hedged_sharpe),
+            ("hedged_var", self.hedged_var),
+            ("hedging_effectiveness", self.hedging_effectiveness),
+            ("insured_sharpe", self.insured_sharpe),
+            ("insured_return", self.insured_return),
+            ("insurance_cost_total", self.insurance_cost_total),
+            ("dca_terminal_wealth", self.dca_terminal_wealth),
+            ("lump_sum_terminal_wealth", self.lump_sum_terminal_wealth),
+            ("dca_average_entry_cost", self.dca_average_entry_cost),
+            ("stability_score", self.stability_score),
+            ("periods_per_year", Some(self.periods_per_year)),
+        ];
+        errors.extend(optional.into_iter().filter_map(|(field, value)| {
+            let value = value?;
+            (!value.is_finite()).then_some(ValidationError::NonFinite { field, value })
+        }));
+        if let Some(value) = self.portfolio_returns.iter().copied().find(|ret| !ret.is_finite()) {
+            errors.push(ValidationError::NonFinite {
+                field: "portfolio_returns",
+                value,
+            });
+        }
+
+        if self.percent_annualized_volatility < 0.0 {
+            errors.push(ValidationError::NegativeVolatility(self.percent_annualized_volatility));
+        }
+        let contribution_sum = self.sharpe_contributions.iter().sum::<f64>();
+        let contribution_tolerance = SHARPE_CONTRIBUTION_TOLERANCE * self.sharpe_ratio.abs().max(1.0);
+        if !self.sharpe_contributions.is_empty() && (contribution_sum - self.sharpe_ratio).abs() > contribution_tolerance {
+            errors.push(ValidationError::SharpeContributionMismatch {
+                sum: contribution_sum,
+                sharpe_ratio: self.sharpe_ratio,
+            });
+        }
+        if self.sharpe_ratio.abs() >= max_sharpe {
+            errors.push(ValidationError::ImplausibleSharpe {
+                sharpe_ratio: self.sharpe_ratio,
+                max_sharpe,
+            });
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Both performances must come from the same scenario and the same `money_to_invest`.
+    pub fn relative_to(&self, benchmark: &PortfolioPerformance, money_to_invest: f64) -> RelativePerformance {
+        if self.portfolio_returns.len() != benchmark.portfolio_returns.len() {
+            panic!(
+                "Configuration Error: cannot compare performances over {} and {} periods, they must come from the same scenario.",
+                self.portfolio_returns.len(),
+                benchmark.portfolio_returns.len()
+            );
+        }
+
+        let active_returns: Vec<f64> = self
+            .portfolio_returns
+            .iter()
+            .zip(&benchmark.portfolio_returns)
+            .map(|(p, b)| p - b)
+            .collect();
+        let number_of_periods = active_returns.len() as f64;
+        let average_active = active_returns.iter().sum::<f64>() / number_of_periods;
+        let active_variance = active_returns
+            .iter()
+            .map(|ret| (ret - average_active).powi(2))
+            .sum::<f64>()
+            / (number_of_periods - 1.0);
+
+        let excess_return = self.annualized_return - benchmark.annualized_return;
+        let tracking_error = active_variance.sqrt() * self.periods_per_year.sqrt();
+        let information_ratio = if tracking_error.abs() >= adaptive_epsilon(money_to_invest) {
+            excess_return / tracking_error
+        } else {
+            0.0 // same as the zero-vol Sharpe, nothing to say about a perfect tracker
+        };
+
+        // Capture ratios: how much of the benchmark's up (down) moves the portfolio followed
+        let capture = |benchmark_went_up: bool| {
+            let (portfolio_sum, benchmark_sum, count) = self
+                .portfolio_returns
+                .iter()
+                .zip(&benchmark.portfolio_returns)
+                .filter(|(_, b)| if benchmark_went_up { **b > 0.0 } else { **b < 0.0 })
+                .fold((0.0, 0.0, 0usize), |(ps, bs, n), (p, b)| (ps + p, bs + b, n + 1));
+            (count > 0 && benchmark_sum.abs() >= adaptive_epsilon(money_to_invest))
+                .then(|| portfolio_sum / benchmark_sum)
+        };
+
+        RelativePerformance {
+            excess_return,
+            tracking_error,
+            information_ratio,
+            relative_sharpe: self.sharpe_ratio - benchmark.sharpe_ratio,
+            upside_capture: capture(true),
+            downside_capture: capture(false),
+        }
+    }
+
+    /// Convert to the wire representation shared with Aegis.
+    pub fn to_protobuf(&self) -> PortfolioMetrics {
+        self.clone().into()
+    }
+}
+
+/// Portfolio vs benchmark, both evaluated on the same scenario. Dollar quantities (excess return,
+/// tracking error) follow `annualized_return`; the ratios are unitless.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelativePerformance {
+    pub excess_return: f64,
+    pub tracking_error: f64,
+    pub information_ratio: f64,
+    pub relative_sharpe: f64,
+    pub upside_capture: Option<f64>,   // None if the benchmark never went up
+    pub downside_capture: Option<f64>, // None if the benchmark never went down
+}
+
+// Field names are kept identical on both sides so the round trip is lossless. These impls live
+// here rather than in aegis_athena_contracts because the contracts crate can't name
+// PortfolioPerformance without depending on this crate; the orphan rule allows both directions
+// here since PortfolioPerformance is local.
+impl From<PortfolioPerformance> for PortfolioMetrics {
+    fn from(perf: PortfolioPerformance) -> Self {
+        PortfolioMetrics {
+            portfolio_returns: perf.portfolio_returns,
+            annualized_return: perf.annualized_return,
+            percent_annualized_volatility: perf.percent_annualized_volatility,
+            sharpe_ratio: perf.sharpe_ratio,
+            vol_of_vol: perf.vol_of_vol,
+            vol_of_vol_annualized: perf.vol_of_vol_annualized,
+            periods_per_year: perf.periods_per_year,
+            hedged_sharpe: perf.hedged_sharpe,
+            hedged_var: perf.hedged_var,
+            hedging_effectiveness: perf.hedging_effectiveness,
+            var_historical: perf.var_historical,
+            var_ci_lower: perf.var_ci_lower,
+            var_ci_upper: perf.var_ci_upper,
+            max_period_return: perf.max_period_return,
+            min_period_return: perf.min_period_return,
+            max_period_return_fraction: perf.max_period_return_fraction,
+            min_period_return_fraction: perf.min_period_return_fraction,
+            insured_sharpe: perf.insured_sharpe,
+            insured_return: perf.insured_return,
+            insurance_cost_total: perf.insurance_cost_total,
+            valid_return_counts: perf.valid_return_counts,
+            original_periods_per_year: perf.original_periods_per_year,
+            num_rebalancing_events: perf.num_rebalancing_events,
+            portfolio_dv01: perf.portfolio_dv01,
+            portfolio_convexity: perf.portfolio_convexity,
+            n_positive_periods: perf.n_positive_periods,
+            n_negative_periods: perf.n_negative_periods,
+            ulcer_index: perf.ulcer_index,
+            pain_ratio: perf.pain_ratio,
+            dca_terminal_wealth: perf.dca_terminal_wealth,
+            lump_sum_terminal_wealth: perf.lump_sum_terminal_wealth,
+            dca_average_entry_cost: perf.dca_average_entry_cost,
+            stability_score: perf.stability_score,
+            sharpe_contributions: perf.sharpe_contributions,
+        }
+    }
+}
+
+impl From<PortfolioMetrics> for PortfolioPerformance {
+    fn from(metrics: PortfolioMetrics) -> Self {
+        PortfolioPerformance {
+            portfolio_returns: metrics.portfolio_returns,
+            annualized_return: metrics.annualized_return,
+            percent_annualized_volatility: metrics.percent_annualized_volatility,
+            sharpe_ratio: metrics.sharpe_ratio,
+            vol_of_vol: metrics.vol_of_vol,
+            vol_of_vol_annualized: metrics.vol_of_vol_annualized,
+            periods_per_year: metrics.periods_per_year,
+            hedged_sharpe: metrics.hedged_sharpe,
+            hedged_var: metrics.hedged_var,
+            hedging_effectiveness: metrics.hedging_effectiveness,
+            var_historical: metrics.var_historical,
+            var_ci_lower: metrics.var_ci_lower,
+            var_ci_upper: metrics.var_ci_upper,
+            max_period_return: metrics.max_period_return,
+            min_period_return: metrics.min_period_return,
+            max_period_return_fraction: metrics.max_period_return_fraction,
+            min_period_return_fraction: metrics.min_period_return_fraction,
+            insured_sharpe: metrics.insured_sharpe,
+            insured_return: metrics.insured_return,
+            insurance_cost_total: metrics.insurance_cost_total,
+            valid_return_counts: metrics.valid_return_counts,
+            original_periods_per_year: metrics.original_periods_per_year,
+            num_rebalancing_events: metrics.num_rebalancing_events,
+            portfolio_dv01: metrics.portfolio_dv01,
+            portfolio_convexity: metrics.portfolio_convexity,
+            n_positive_periods: metrics.n_positive_periods,
+            n_negative_periods: metrics.n_negative_periods,
+            ulcer_index: metrics.ulcer_index,
+            pain_ratio: metrics.pain_ratio,
+            dca_terminal_wealth: metrics.dca_terminal_wealth,
+            lump_sum_terminal_wealth: metrics.lump_sum_terminal_wealth,
+            dca_average_entry_cost: metrics.dca_average_entry_cost,
+            stability_score: metrics.stability_score,
+            sharpe_contributions: metrics.sharpe_contributions,
+        }
+    }
+}
+
+// Historical VaR and a confidence interval around it.
+//
+// The sample quantile is asymptotically normal with variance p(1-p) / (n f(q)^2), f being the
+// return density at the quantile. Rather than assume f is normal, we read it off the
+// Cornish-Fisher expansion x(z) = mean + std * cf(z): its density is φ(z) / (std * cf'(z)),
+// which picks up the skewness and excess kurtosis of the sampled returns.
+fn historical_var_with_ci(
+    portfolio_returns: &[f64],
+    average_return: f64,
+    volatility: f64,
+    epsilon: f64,
+) -> (f64, f64, f64) {
+    let mut sorted = portfolio_returns.to_vec();
+    sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+    let tail_probability = 1.0 - VAR_CONFIDENCE;
+    let var_historical = -sorted_quantile(&sorted, tail_probability);
+
+    if volatility < epsilon {
+        return (var_historical, var_historical, var_historical); // degenerate, no sampling error
+    }
+
+    let n = portfolio_returns.len() as f64;
+    let central_moment = |k: i32| {
+        portfolio_returns
+            .iter()
+            .map(|ret| (ret - average_return).powi(k))
+            .sum::<f64>()
+            / n
+    };
+    let m2 = central_moment(2);
+    let skewness = central_moment(3) / m2.powf(1.5);
+    let excess_kurtosis = central_moment(4) / (m2 * m2) - 3.0;
+
+    let z = normal_inv_cdf(tail_probability);
+    let cf_slope = 1.0 + z * skewness / 3.0 + (z * z - 1.0) * excess_kurtosis / 8.0
+        - (6.0 * z * z - 5.0) * skewness * skewness / 36.0;
+    // Strong skew/kurtosis can make the expansion non-monotone, fall back to the normal density there
+    let cf_slope = if cf_slope > FLOAT_COMPARISON_EPSILON { cf_slope } else { 1.0 };
+    let standard_normal_density = (-0.5 * z * z).exp() / (2.0 * std::f64::consts::PI).sqrt();
+    let density = standard_normal_density / (volatility * cf_slope);
+
+    let standard_error = (tail_probability * (1.0 - tail_probability) / n).sqrt() / density;
+    let half_width = normal_inv_cdf(0.5 + VAR_CONFIDENCE / 2.0) * standard_error;
+    (var_historical, var_historical - half_width, var_historical + half_width)
+}
+
+/// Per asset, the number of periods with a valid (non-NaN) return. NaN marks missing data.
+pub fn valid_return_counts(returns: &[Vec<f64>]) -> Vec<u32> {
+    let n_assets = returns.first().map_or(0, Vec::len);
+    let mut counts = vec![0u32; n_assets];
+    for row in returns {
+        for (count, log_return) in counts.iter_mut().zip(row) {
+            if !log_return.is_nan() {
+                *count += 1;
+            }
+        }
+    }
+    counts
+}
+
+// Periods per chunk of the parallel scans, big enough that a chunk is worth a rayon task
+const SCAN_CHUNK_SIZE: usize = 4096;
+
+// Two-pass parallel inclusive scan for an associative `op` with identity `identity`: every chunk
+// is reduced in parallel, a (short) sequential scan over the chunk totals gives each chunk the
+// carry from everything before it, then the chunks are scanned in parallel from their carry.
+fn parallel_inclusive_scan<F>(values: &[f64], identity: f64, op: F) -> Vec<f64>
+where
+    F: Fn(f64, f64) -> f64 + Sync,
+{
+    let chunk_totals: Vec<f64> = values
+        .par_chunks(SCAN_CHUNK_SIZE)
+        .map(|chunk| chunk.iter().fold(identity, |acc, value| op(acc, *value)))
+        .collect();
+    let carries: Vec<f64> = chunk_totals
+        .iter()
+        .scan(identity, |running, total| {
+            let carry = *running;
+            *running = op(*running, *total);
+            Some(carry)
+        })
+        .collect();
+
+    let mut scanned = vec![identity; values.len()];
+    scanned
+        .par_chunks_mut(SCAN_CHUNK_SIZE)
+        .zip(values.par_chunks(SCAN_CHUNK_SIZE))
+        .zip(carries.par_iter())
+        .for_each(|((out, chunk), carry)| {
+            let mut acc = *carry;
+            for (slot, value) in out.iter_mut().zip(chunk) {
+                acc = op(acc, *value);
+                *slot = acc;
+            }
+        });
+    scanned
+}
+
+// Wealth compounds the per-period returns from 1.0, each drawdown is measured from the running
+// peak. Both running quantities are prefix scans (product, then max), done in parallel.
+fn ulcer_index(portfolio_returns: &[f64], money_to_invest: f64) -> f64 {
+    if portfolio_returns.is_empty() {
+        return 0.0;
+    }
+    let growth: Vec<f64> = portfolio_returns.par_iter().map(|ret| 1.0 + ret / money_to_invest).collect();
+    let wealth = parallel_inclusive_scan(&growth, 1.0, |a, b| a * b);
+    let peaks = parallel_inclusive_scan(&wealth, f64::NEG_INFINITY, f64::max);
+    let sum_squared_drawdowns = wealth
+        .par_iter()
+        .zip(&peaks)
+        .map(|(wealth, peak)| (1.0 - wealth / peak.max(1.0)).powi(2)) // the starting 1.0 is a peak too
+        .sum::<f64>();
+    (sum_squared_drawdowns / portfolio_returns.len() as f64).sqrt()
+}
+
+/// Annualized return per unit of ulcer index, higher is better. Unlike the Sharpe it only
+/// penalises time spent under water, not upside volatility, which is why trend-following
+/// portfolios (steady gains, a few sharp but quickly recovered losses) tend to look better on
+/// it than on their Sharpe. Infinite when the portfolio never drew down.
+pub fn pain_ratio(annualized_return: f64, money_to_invest: f64, ulcer_index: f64) -> f64 {
+    if ulcer_index < FLOAT_COMPARISON_EPSILON {
+        f64::INFINITY
+    } else {
+        annualized_return / money_to_invest / ulcer_index
+    }
+}
+
+// Sharpe_i = (w_i mu_i - rf_i) / sigma_portfolio with mu_i the asset's annualized dollar return
+// per unit of weight. The risk-free return is split by weight (evenly for a zero-net book) so
+// the contributions add up to the Sharpe even when the weights don't sum to 1.
+fn sharpe_contributions(
+    returns: &[Vec<f64>],
+    weights: &[f64],
+    money_to_invest: f64,
+    risk_free_return: f64,
+    periods_per_year: f64,
+    annualized_volatility: f64,
+) -> Vec<f64> {
+    if annualized_volatility.abs() < adaptive_epsilon(money_to_invest) {
+        return vec![0.0; weights.len()]; // the Sharpe itself is 0 then
+    }
+    let number_of_periods = returns.len() as f64;
+    let total_weight = weights.iter().sum::<f64>();
+    weights
+        .iter()
+        .enumerate()
+        .map(|(asset, weight)| {
+            let mean_return = returns.iter().map(|row| simple_return(row[asset])).sum::<f64>() / number_of_periods;
+            let annual_dollar_return = weight * mean_return * money_to_invest * periods_per_year;
+            let risk_free_share = if total_weight.abs() >= FLOAT_COMPARISON_EPSILON {
+                weight / total_weight
+            } else {
+                1.0 / weights.len() as f64
+            };
+            (annual_dollar_return - risk_free_return * risk_free_share) / annualized_volatility
+        })
+        .collect()
+}
+
+fn sign_counts(portfolio_returns: &[f64]) -> (u32, u32) {
+    portfolio_returns.iter().fold((0, 0), |(positive, negative), ret| {
+        (positive + u32::from(*ret > 0.0), negative + u32::from(*ret < 0.0))
+    })
+}
+
+// Missing data (NaN) means the asset didn't move that period, rather than poisoning the portfolio
+pub(crate) fn simple_return(log_return: f64) -> f64 {
+    if log_return.is_nan() { 0.0 } else { log_return.exp() - 1.0 }
+}
+
+// w = [1.0]: standalone asset performance, common enough to deserve its own path
+fn is_single_asset(weights: &[f64]) -> bool {
+    weights.len() == 1 && (weights[0] - 1.0).abs() < FLOAT_COMPARISON_EPSILON
+}
+
+// The dot product collapses to a scalar multiply, so skip the nested par_iter and its
+// scheduling overhead entirely (rayon only pays off once there's real work per row).
+fn single_asset_portfolio_returns(returns: &[Vec<f64>], money_to_invest: f64) -> Vec<f64> {
+    returns
+        .iter()
+        .map(|row| simple_return(row[0]) * money_to_invest)
+        .collect()
+}
+
+fn constant_weight_portfolio_returns(returns: &[Vec<f64>], weights: &[f64], money_to_invest: f64) -> Vec<f64> {
+    returns
+        .par_iter()
+        .map(|row| {
+            row.par_iter()
+                .zip(weights.par_iter())
+                .map(|(log_return, weight)| {
+                    (simple_return(*log_return) * *weight) * money_to_invest
+                })
+                .sum::<f64>()
+        })
+        .collect::<Vec<f64>>()
+}
+
+/// Evaluates `weights` against a sampled scenario of log-returns (`periods x assets`).
+///
+/// `risk_free_rate` is an annualized *rate* (0.02 = 2% a year), not a per-period return.
+/// Any finite value is accepted, including negative ones (e.g. -0.005 for EUR/CHF/JPY
+/// style negative-rate environments); the Sharpe numerator is then simply larger than the
+/// raw annualized return.
+pub fn compute_portfolio_performance(
+    returns: &[Vec<f64>],
+    weights: &[f64],
+    money_to_invest: f64,
+    risk_free_rate: f64,
+    time_horizon_in_days: f64,
+) -> PortfolioPerformance {
+    compute_portfolio_performance_with_rebalancing(
+        returns,
+        weights,
+        money_to_invest,
+        risk_free_rate,
+        time_horizon_in_days,
+        RebalancingStrategy::EveryPeriod,
+    )
+}
+
+/// Same as `compute_portfolio_performance`, with the weights maintained by `rebalancing`.
+pub fn compute_portfolio_performance_with_rebalancing(
+    returns: &[Vec<f64>],
+    weights: &[f64],
+    money_to_invest: f64,
+    risk_free_rate: f64,
+    time_horizon_in_days: f64,
+    rebalancing: RebalancingStrategy,
+) -> PortfolioPerformance {
+    // --- Edge Case Checks ---
+    // Check 1: Invalid Configuration for Time/Money (Panic)
+    if time_horizon_in_days.abs() < FLOAT_COMPARISON_EPSILON {
+        panic!("Configuration Error: time_horizon_in_days cannot be zero.");
+    }
+    if money_to_invest.abs() < FLOAT_COMPARISON_EPSILON {
+        panic!("Configuration Error: money_to_invest cannot be zero.");
+    }
+
+    let number_of_periods = returns.len() as f64;
+
+    // Check 2: Insufficient Return Periods for Volatility/Sharpe (Panic)
+    if number_of_periods < 2.0 {
+        panic!(
+        "Configuration Error: Cannot compute volatility or Sharpe ratio with fewer than 2 return periods (found {}). \
+         Check 'periods_to_sample' in Sampler configuration.",
+         returns.len()
+     );
+    }
+
+    // --- Main Calculation (Now guaranteed N >= 2) ---
+    let (portfolio_returns, num_rebalancing_events) = match rebalancing {
+        // a single asset never drifts away from 100%
+        RebalancingStrategy::EveryPeriod if is_single_asset(weights) => {
+            (single_asset_portfolio_returns(returns, money_to_invest), 0)
+        }
+        RebalancingStrategy::EveryPeriod => (
+            constant_weight_portfolio_returns(returns, weights, money_to_invest),
+            returns.len() as u32 - 1,
+        ),
+        RebalancingStrategy::BandRebalancing { threshold } => {
+            band_portfolio_returns(returns, weights, money_to_invest, threshold)
+        }
+    };
+
+    let average_return = portfolio_returns.iter().sum::<f64>() / number_of_periods;
+
+    // Calculate variance (N-1 in denominator is now safe)
+    let variance = portfolio_returns
+        .iter()
+        .map(|ret| (ret - average_return).powi(2))
+        .sum::<f64>()
+        / (number_of_periods - 1.0);
+    let volatility = variance.sqrt(); // Standard deviation (dollar terms)
+
+    // Annualizing!
+    let time_horizon_in_years = time_horizon_in_days / 365.0;
+    let periods_per_year = number_of_periods / time_horizon_in_years;
+
+    let (annualized_return, annualized_volatility, sharpe_ratio) =
+        annualized_sharpe(average_return, volatility, periods_per_year, money_to_invest, risk_free_rate);
+    let percent_annualized_volatility = annualized_volatility / money_to_invest;
+
+    // Vol-of-vol: |r_t| is a cheap proxy for the instantaneous vol of period t, so its
+    // dispersion tells us how unstable the risk profile is (fraction of money invested)
+    let absolute_returns = portfolio_returns.iter().map(|ret| ret.abs());
+    let average_absolute_return = absolute_returns.clone().sum::<f64>() / number_of_periods;
+    let vol_of_vol = (absolute_returns
+        .map(|abs_ret| (abs_ret - average_absolute_return).powi(2))
+        .sum::<f64>()
+        / (number_of_periods - 1.0))
+        .sqrt()
+        / money_to_invest;
+    let vol_of_vol_annualized = vol_of_vol * periods_per_year.sqrt();
+
+    let (var_historical, var_ci_lower, var_ci_upper) =
+        historical_var_with_ci(&portfolio_returns, average_return, volatility, adaptive_epsilon(money_to_invest));
+
+    let valid_return_counts = valid_return_counts(returns);
+
+    // Extreme single-period outcomes, one pass
+    let (min_period_return, max_period_return) = portfolio_returns
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), ret| (min.min(*ret), max.max(*ret)));
+    let max_period_return_fraction = max_period_return / money_to_invest;
+    let min_period_return_fraction = min_period_return / money_to_invest;
+    let (n_positive_periods, n_negative_periods) = sign_counts(&portfolio_returns);
+    let ulcer_index = ulcer_index(&portfolio_returns, money_to_invest);
+    let pain_ratio = pain_ratio(annualized_return, money_to_invest, ulcer_index);
+    // band rebalancing lets the weights drift, so `weights` aren't what earned the returns
+    let sharpe_contributions = match rebalancing {
+        RebalancingStrategy::EveryPeriod => sharpe_contributions(
+            returns,
+            weights,
+            money_to_invest,
+            money_to_invest * risk_free_rate,
+            periods_per_year,
+            annualized_volatility,
+        ),
+        RebalancingStrategy::BandRebalancing { .. } => Vec::new(),
+    };
+
+    PortfolioPerformance {
+        portfolio_returns,
+        annualized_return,
+        percent_annualized_volatility,
+        sharpe_ratio,
+        vol_of_vol,
+        vol_of_vol_annualized,
+        periods_per_year,
+        hedged_sharpe: None, // filled in by hedging::apply_hedge when a hedge is configured
+        hedged_var: None,
+        hedging_effectiveness: None,
+        var_historical,
+        var_ci_lower,
+        var_ci_upper,
+        max_period_return,
+        min_period_return,
+        max_period_return_fraction,
+        min_period_return_fraction,
+        insured_sharpe: None, // filled in by insurance::apply_protective_put when a put is configured
+        insured_return: None,
+        insurance_cost_total: None,
+        valid_return_counts,
+        original_periods_per_year: periods_per_year,
+        num_rebalancing_events,
+        portfolio_dv01: None, // filled in by fixed_income::apply_rate_sensitivity when there are bonds
+        portfolio_convexity: None,
+        n_positive_periods,
+        n_negative_periods,
+        ulcer_index,
+        pain_ratio,
+        dca_terminal_wealth: None, // filled in by dca::apply_dca when DCA is configured
+        lump_sum_terminal_wealth: None,
+        dca_average_entry_cost: None,
+        stability_score: None, // filled in by run_batch when compute_stability is set
+        sharpe_contributions,
+    }
+}
+
+/// A `PortfolioPerformance` plus the running moments needed to extend it with new periods
+/// without going back over the old ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortfolioPerformanceState {
+    pub performance: PortfolioPerformance,
+    pub returns: RunningStats,          // of the per-period dollar returns
+    pub absolute_returns: RunningStats, // of their absolute values, for the vol-of-vol
+}
+
+impl From<PortfolioPerformance> for PortfolioPerformanceState {
+    // One pass over the existing returns, every later update is incremental
+    fn from(performance: PortfolioPerformance) -> Self {
+        let mut returns = RunningStats::default();
+        let mut absolute_returns = RunningStats::default();
+        for ret in &performance.portfolio_returns {
+            returns.push(*ret);
+            absolute_returns.push(ret.abs());
+        }
+        PortfolioPerformanceState {
+            performance,
+            returns,
+            absolute_returns,
+        }
+    }
+}
+
+/// Appends `new_returns` (`periods x assets` log-returns) to `existing` and updates the metrics
+/// as `compute_portfolio_performance` would over all the periods at once, weights held constant.
+/// `time_horizon_in_days` is the horizon spanned by all the periods, old and new.
+///
+/// Mean, volatility, Sharpe, vol-of-vol and the extremes are updated online (Welford). The
+/// historical VaR needs the whole distribution and is recomputed from `portfolio_returns`.
+/// Hedging, insurance, DCA and stability results describe the old periods only and are cleared,
+/// and so are the Sharpe contributions.
+pub fn compute_portfolio_performance_incremental(
+    existing: &mut PortfolioPerformanceState,
+    new_returns: &[Vec<f64>],
+    weights: &[f64],
+    money_to_invest: f64,
+    risk_free_rate: f64,
+    time_horizon_in_days: f64,
+) {
+    if time_horizon_in_days.abs() < FLOAT_COMPARISON_EPSILON {
+        panic!("Configuration Error: time_horizon_in_days cannot be zero.");
+    }
+    if money_to_invest.abs() < FLOAT_COMPARISON_EPSILON {
+        panic!("Configuration Error: money_to_invest cannot be zero.");
+    }
+    let total_periods = existing.returns.count as usize + new_returns.len();
+    if total_periods < 2 {
+        panic!(
+            "Configuration Error: Cannot compute volatility or Sharpe ratio with fewer than 2 return periods (found {}).",
+            total_periods
+        );
+    }
+
+    let new_portfolio_returns = constant_weight_portfolio_returns(new_returns, weights, money_to_invest);
+    let (new_positive, new_negative) = sign_counts(&new_portfolio_returns);
+    existing.performance.n_positive_periods += new_positive;
+    existing.performance.n_negative_periods += new_negative;
+    for ret in &new_portfolio_returns {
+        existing.returns.push(*ret);
+        existing.absolute_returns.push(ret.abs());
+    }
+
+    let perf = &mut existing.performance;
+    let stats = &existing.returns;
+    perf.portfolio_returns.extend(new_portfolio_returns);
+    perf.num_rebalancing_events = if is_single_asset(weights) { 0 } else { total_periods as u32 - 1 };
+
+    let periods_per_year = total_periods as f64 / (time_horizon_in_days / 365.0);
+    let volatility = stats.std_dev();
+    let (annualized_return, annualized_volatility, sharpe_ratio) =
+        annualized_sharpe(stats.mean, volatility, periods_per_year, money_to_invest, risk_free_rate);
+    perf.periods_per_year = periods_per_year;
+    perf.original_periods_per_year = periods_per_year;
+    perf.annualized_return = annualized_return;
+    perf.percent_annualized_volatility = annualized_volatility / money_to_invest;
+    perf.sharpe_ratio = sharpe_ratio;
+    perf.vol_of_vol = existing.absolute_returns.std_dev() / money_to_invest;
+    perf.vol_of_vol_annualized = perf.vol_of_vol * periods_per_year.sqrt();
+
+    (perf.var_historical, perf.var_ci_lower, perf.var_ci_upper) =
+        historical_var_with_ci(&perf.portfolio_returns, stats.mean, volatility, adaptive_epsilon(money_to_invest));
+    perf.max_period_return = stats.max;
+    perf.min_period_return = stats.min;
+    perf.max_period_return_fraction = stats.max / money_to_invest;
+    perf.min_period_return_fraction = stats.min / money_to_invest;
+    // needs every asset's full history, which isn't kept
+    perf.sharpe_contributions.clear();
+    perf.ulcer_index = ulcer_index(&perf.portfolio_returns, money_to_invest);
+    perf.pain_ratio = pain_ratio(perf.annualized_return, money_to_invest, perf.ulcer_index);
+
+    let new_counts = valid_return_counts(new_returns);
+    if perf.valid_return_counts.len() < new_counts.len() {
+        perf.valid_return_counts.resize(new_counts.len(), 0);
+    }
+    for (count, new_count) in perf.valid_return_counts.iter_mut().zip(new_counts) {
+        *count += new_count;
+    }
+
+    perf.hedged_sharpe = None;
+    perf.hedged_var = None;
+    perf.hedging_effectiveness = None;
+    perf.insured_sharpe = None;
+    perf.insured_return = None;
+    perf.insurance_cost_total = None;
+    perf.dca_terminal_wealth = None;
+    perf.lump_sum_terminal_wealth = None;
+    perf.dca_average_entry_cost = None;
+    perf.stability_score = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rand_distr::{Distribution, StandardNormal};
+
+    use super::*;
+
+    // One asset, simple returns of 1%, -2%, 3% and 0% over a year of 4 periods
+    fn quarterly_returns() -> Vec<Vec<f64>> {
+        [0.01, -0.02, 0.03, 0.0].iter().map(|simple: &f64| vec![simple.ln_1p()]).collect()
+    }
+
+    #[test]
+    fn sharpe_with_negative_risk_free_rate() {
+        let money = 1_000.0;
+        let risk_free_rate = -0.005;
+        let perf = compute_portfolio_performance(&quarterly_returns(), &[1.0], money, risk_free_rate, 365.0);
+
+        let dollar_returns = [10.0, -20.0, 30.0, 0.0];
+        let mean = dollar_returns.iter().sum::<f64>() / 4.0;
+        let variance = dollar_returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / 3.0;
+        let annualized_return = mean * 4.0;
+        let annualized_volatility = variance.sqrt() * 2.0;
+        // a negative rate adds to the excess return instead of taking away from it
+        let expected = (annualized_return + 0.005 * money) / annualized_volatility;
+
+        assert!((perf.annualized_return - annualized_return).abs() < 1e-9);
+        assert!((perf.sharpe_ratio - expected).abs() < 1e-9);
+        let zero_rate = compute_portfolio_performance(&quarterly_returns(), &[1.0], money, 0.0, 365.0);
+        assert!(perf.sharpe_ratio > zero_rate.sharpe_ratio);
+        assert!((perf.implied_risk_free_return() - money * risk_free_rate).abs() < 1e-9);
+    }
+
+    #[test]
+    fn scaling_to_target_vol_keeps_the_risk_free_return() {
+        let money = 1_000.0;
+        let perf = compute_portfolio_performance(&quarterly_returns(), &[1.0], money, 0.02, 365.0);
+
+        for target_vol in [0.01, 0.5] {
+            let (scaled, scale) = perf.scale_to_target_vol(target_vol);
+            assert!((scaled.percent_annualized_volatility - target_vol).abs() < 1e-12);
+            // financed at the risk-free rate: only the excess return is levered
+            assert!((scaled.implied_risk_free_return() - money * 0.02).abs() < 1e-9);
+            assert!((scaled.sharpe_ratio - perf.sharpe_ratio).abs() < 1e-12);
+            let excess = perf.annualized_return - money * 0.02;
+            assert!((scaled.annualized_return - (money * 0.02 + scale * excess)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn annualizing_back_to_the_original_horizon_is_a_no_op() {
+        let perf = compute_portfolio_performance(&quarterly_returns(), &[1.0], 1_000.0, 0.02, 365.0);
+
+        let mut same = perf.clone();
+        same.annualize(365.0);
+        assert_eq!(same, perf);
+
+        let mut round_trip = perf.clone();
+        round_trip.annualize(730.0);
+        assert!((round_trip.annualized_return - perf.annualized_return / 2.0).abs() < 1e-9);
+        round_trip.annualize(365.0);
+        for (after, before) in [
+            (round_trip.annualized_return, perf.annualized_return),
+            (round_trip.percent_annualized_volatility, perf.percent_annualized_volatility),
+            (round_trip.sharpe_ratio, perf.sharpe_ratio),
+            (round_trip.vol_of_vol_annualized, perf.vol_of_vol_annualized),
+            (round_trip.pain_ratio, perf.pain_ratio),
+            (round_trip.periods_per_year, perf.periods_per_year),
+            (round_trip.implied_risk_free_return(), perf.implied_risk_free_return()),
+        ] {
+            assert!((after - before).abs() < 1e-9 * before.abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn a_portfolio_relative_to_itself_has_no_active_risk() {
+        let money = 1_000.0;
+        let perf = compute_portfolio_performance(&quarterly_returns(), &[1.0], money, 0.02, 365.0);
+        let relative = perf.relative_to(&perf, money);
+        assert_eq!(relative.excess_return, 0.0);
+        assert_eq!(relative.tracking_error, 0.0);
+        assert_eq!(relative.information_ratio, 0.0);
+        assert_eq!(relative.relative_sharpe, 0.0);
+        // every up and down move followed one for one
+        assert_eq!(relative.upside_capture, Some(1.0));
+        assert_eq!(relative.downside_capture, Some(1.0));
+    }
+
+    #[test]
+    fn sharpe_contributions_add_up_to_the_sharpe() {
+        // three assets over 6 periods, simple returns
+        let returns: Vec<Vec<f64>> = [
+            [0.02, -0.01, 0.005],
+            [-0.03, 0.02, 0.01],
+            [0.04, 0.00, -0.02],
+            [0.01, -0.02, 0.015],
+            [-0.01, 0.03, 0.0],
+            [0.025, 0.01, -0.005],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|simple: &f64| simple.ln_1p()).collect())
+        .collect();
+
+        // long only, levered long-short, and a zero-net book
+        for weights in [[0.5, 0.3, 0.2], [0.8, -0.3, 0.5], [0.5, -0.5, 0.0]] {
+            let perf = compute_portfolio_performance(&returns, &weights, 1_000.0, 0.03, 182.5);
+            assert_eq!(perf.sharpe_contributions.len(), 3);
+            let total = perf.sharpe_contributions.iter().sum::<f64>();
+            assert!(
+                (total - perf.sharpe_ratio).abs() < 1e-9,
+                "{:?}: contributions sum to {} for a Sharpe of {}",
+                weights,
+                total,
+                perf.sharpe_ratio
+            );
+        }
+    }
+
+    #[test]
+    fn garch_returns_have_more_vol_of_vol_than_constant_vol() {
+        // GARCH(1, 1) with the same 1% unconditional daily vol as the constant-vol asset
+        let (alpha, beta, vol) = (0.2, 0.75, 0.01_f64);
+        let omega = vol * vol * (1.0 - alpha - beta);
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut variance = vol * vol;
+        let mut garch = Vec::with_capacity(20_000);
+        let mut constant = Vec::with_capacity(20_000);
+        for _ in 0..20_000 {
+            let shock: f64 = StandardNormal.sample(&mut rng);
+            let ret = variance.sqrt() * shock;
+            garch.push(vec![ret]);
+            variance = omega + alpha * ret * ret + beta * variance;
+            let shock: f64 = StandardNormal.sample(&mut rng);
+            constant.push(vec![vol * shock]);
+        }
+
+        let garch = compute_portfolio_performance(&garch, &[1.0], 1_000.0, 0.0, 20_000.0);
+        let constant = compute_portfolio_performance(&constant, &[1.0], 1_000.0, 0.0, 20_000.0);
+        // relative to each path's own realized vol, so it's the clustering that shows
+        assert!(
+            garch.vol_of_vol / garch.percent_annualized_volatility
+                > constant.vol_of_vol / constant.percent_annualized_volatility
+        );
+        assert!(garch.vol_of_vol > constant.vol_of_vol);
+    }
+
+    #[test]
+    fn batch_plus_incremental_updates_match_computing_all_at_once() {
+        // 12 monthly periods of three assets, deterministic but irregular
+        let returns: Vec<Vec<f64>> = (0..12)
+            .map(|t| (0..3).map(|a| 0.03 * ((t * 7 + a * 3) as f64).sin() + 0.002 * a as f64).collect())
+            .collect();
+        let weights = [0.5, 0.3, 0.2];
+        let (money, rate) = (1_000.0, 0.02);
+
+        let mut state: PortfolioPerformanceState =
+            compute_portfolio_performance(&returns[..6], &weights, money, rate, 182.5).into();
+        compute_portfolio_performance_incremental(&mut state, &returns[6..9], &weights, money, rate, 273.75);
+        compute_portfolio_performance_incremental(&mut state, &returns[9..], &weights, money, rate, 365.0);
+        let incremental = state.performance;
+        let full = compute_portfolio_performance(&returns, &weights, money, rate, 365.0);
+
+        assert_eq!(incremental.portfolio_returns.len(), 12);
+        for (a, b) in incremental.portfolio_returns.iter().zip(&full.portfolio_returns) {
+            assert!((a - b).abs() < 1e-9);
+        }
+        for name in PortfolioPerformance::METRIC_NAMES {
+            let (a, b) = (incremental.metric(name).unwrap(), full.metric(name).unwrap());
+            assert!((a - b).abs() < 1e-9 * b.abs().max(1.0), "{}: {} vs {}", name, a, b);
+        }
+        assert_eq!(incremental.periods_per_year, full.periods_per_year);
+        assert_eq!(incremental.num_rebalancing_events, full.num_rebalancing_events);
+        assert_eq!(incremental.valid_return_counts, full.valid_return_counts);
+        assert_eq!(
+            (incremental.n_positive_periods, incremental.n_negative_periods),
+            (full.n_positive_periods, full.n_negative_periods)
+        );
+    }
+}
@@ -0,0 +1,53 @@
+// Operations on the contracts' `Portfolio` that don't belong in a simulation.
+use std::fmt;
+
+use aegis_athena_contracts::simulation::Portfolio;
+
+use crate::performance::FLOAT_COMPARISON_EPSILON;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PortfolioError {
+    /// Weights sum to (almost) zero, they can't be scaled to sum to 1.
+    ZeroTotalWeight(f64),
+}
+
+impl fmt::Display for PortfolioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortfolioError::ZeroTotalWeight(sum) => {
+                write!(f, "Weights sum to {}, they cannot be normalized", sum)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PortfolioError {}
+
+pub trait NormalizeWeights: Sized {
+    /// Divides every weight by their sum, so they sum to 1. Returns the original sum.
+    /// Weights are left as they were on error.
+    fn normalize_weights(&mut self) -> Result<f64, PortfolioError>;
+
+    /// Same, on a copy.
+    fn normalized(&self) -> Result<Self, PortfolioError>;
+}
+
+impl NormalizeWeights for Portfolio {
+    fn normalize_weights(&mut self) -> Result<f64, PortfolioError> {
+        let sum = self.weights.iter().sum::<f64>();
+        // a NaN sum would poison every weight
+        if sum.is_nan() || sum.abs() < FLOAT_COMPARISON_EPSILON {
+            return Err(PortfolioError::ZeroTotalWeight(sum));
+        }
+        for weight in &mut self.weights {
+            *weight /= sum;
+        }
+        Ok(sum)
+    }
+
+    fn normalized(&self) -> Result<Self, PortfolioError> {
+        let mut portfolio = self.clone();
+        portfolio.normalize_weights()?;
+        Ok(portfolio)
+    }
+}
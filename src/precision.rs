@@ -0,0 +1,108 @@
+// Optional rounding of the metrics we send back. Clients rarely need 17 significant digits of a
+// Sharpe ratio, and shorter numbers make HTTP/JSON transcoded responses noticeably smaller.
+use aegis_athena_contracts::simulation::{PortfolioMetrics, SimulationBatchResult};
+
+// f64 has ~15.9 significant decimal digits, past this rounding is a no-op at best
+const MAX_PRECISION: u32 = 15;
+
+/// Rounds to `precision` decimal places (half away from zero), e.g. 1.23456789 -> 1.2346 at 4.
+pub fn round_to_precision(value: f64, precision: u32) -> f64 {
+    if precision >= MAX_PRECISION || !value.is_finite() {
+        return value;
+    }
+    let scale = 10f64.powi(precision as i32);
+    (value * scale).round() / scale
+}
+
+fn round_all(values: &mut [f64], precision: u32) {
+    for value in values {
+        *value = round_to_precision(*value, precision);
+    }
+}
+
+pub trait RoundMetrics {
+    fn round_metrics(&mut self, precision: u32);
+}
+
+impl RoundMetrics for SimulationBatchResult {
+    // Scenario returns are inputs, not metrics, and are left alone
+    fn round_metrics(&mut self, precision: u32) {
+        round_all(&mut self.sum_returns, precision);
+        round_all(&mut self.sum_volatilities, precision);
+        round_all(&mut self.sum_sharpes, precision);
+        round_all(&mut self.sum_hedged_sharpes, precision);
+        round_all(&mut self.sum_hedging_effectiveness, precision);
+        round_all(&mut self.sum_insured_sharpes, precision);
+        round_all(&mut self.sum_insured_returns, precision);
+        round_all(&mut self.sum_insurance_costs, precision);
+        round_all(&mut self.sum_dca_terminal_wealth, precision);
+        round_all(&mut self.sum_lump_sum_terminal_wealth, precision);
+        round_all(&mut self.sum_stability_scores, precision);
+        round_all(&mut self.sum_portfolio_dv01s, precision);
+        round_all(&mut self.sum_portfolio_convexities, precision);
+        for custom in &mut self.custom_metrics {
+            round_all(&mut custom.values, precision);
+        }
+        if let Some(correlation) = &mut self.portfolio_return_correlation {
+            round_all(&mut correlation.values, precision);
+        }
+    }
+}
+
+impl RoundMetrics for PortfolioMetrics {
+    fn round_metrics(&mut self, precision: u32) {
+        let round = |value: f64| round_to_precision(value, precision);
+        round_all(&mut self.portfolio_returns, precision);
+        round_all(&mut self.sharpe_contributions, precision);
+        self.annualized_return = round(self.annualized_return);
+        self.percent_annualized_volatility = round(self.percent_annualized_volatility);
+        self.sharpe_ratio = round(self.sharpe_ratio);
+        self.vol_of_vol = round(self.vol_of_vol);
+        self.vol_of_vol_annualized = round(self.vol_of_vol_annualized);
+        self.periods_per_year = round(self.periods_per_year);
+        self.var_historical = round(self.var_historical);
+        self.var_ci_lower = round(self.var_ci_lower);
+        self.var_ci_upper = round(self.var_ci_upper);
+        self.max_period_return = round(self.max_period_return);
+        self.min_period_return = round(self.min_period_return);
+        self.max_period_return_fraction = round(self.max_period_return_fraction);
+        self.min_period_return_fraction = round(self.min_period_return_fraction);
+        self.ulcer_index = round(self.ulcer_index);
+        self.pain_ratio = round(self.pain_ratio);
+        for optional in [
+            &mut self.hedged_sharpe,
+            &mut self.hedged_var,
+            &mut self.hedging_effectiveness,
+            &mut self.insured_sharpe,
+            &mut self.insured_return,
+            &mut self.insurance_cost_total,
+            &mut self.portfolio_dv01,
+            &mut self.portfolio_convexity,
+            &mut self.dca_terminal_wealth,
+            &mut self.lump_sum_terminal_wealth,
+            &mut self.dca_average_entry_cost,
+            &mut self.stability_score,
+        ] {
+            *optional = optional.map(round);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_half_away_from_zero_at_the_requested_digit() {
+        assert_eq!(round_to_precision(1.23456789, 4), 1.2346);
+        assert_eq!(round_to_precision(-1.23456789, 4), -1.2346);
+        assert_eq!(round_to_precision(1.23456789, 0), 1.0);
+    }
+
+    #[test]
+    fn leaves_full_precision_and_non_finite_values_alone() {
+        assert_eq!(round_to_precision(1.23456789, MAX_PRECISION), 1.23456789);
+        assert!(round_to_precision(f64::NAN, 4).is_nan());
+        assert_eq!(round_to_precision(f64::INFINITY, 4), f64::INFINITY);
+    }
+}
@@ -0,0 +1,95 @@
+// Scenarios generated ahead of time from the server's default sampler, so the first batches
+// after startup (and batches arriving back to back) don't wait on scenario generation.
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use aegis_athena_contracts::sampling::Sampler;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::error::SimulationError;
+use crate::sampling::{Scenario, ScenarioSampler};
+
+pub struct ScenarioPrefetch {
+    sampler: Arc<Sampler>, // the service's own, not a copy
+    capacity: usize,
+    buffer: Mutex<VecDeque<Scenario>>,
+    refilling: AtomicBool, // one refill at a time, concurrent ones would overshoot the capacity
+}
+
+impl ScenarioPrefetch {
+    pub fn new(sampler: Arc<Sampler>, capacity: usize) -> Self {
+        ScenarioPrefetch {
+            sampler,
+            capacity,
+            buffer: Mutex::new(VecDeque::with_capacity(capacity)),
+            refilling: AtomicBool::new(false),
+        }
+    }
+
+    /// Up to `n` buffered scenarios, oldest first.
+    pub async fn take(&self, n: usize) -> Vec<Scenario> {
+        let mut buffer = self.buffer.lock().await;
+        let available = n.min(buffer.len());
+        buffer.drain(..available).collect()
+    }
+
+    /// Generates up to `n` more scenarios in the background, never past the capacity.
+    pub fn refill(self: &Arc<Self>, n: usize) {
+        if n == 0 || self.refilling.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let prefetch = Arc::clone(self);
+        tokio::spawn(async move {
+            let missing = n.min(prefetch.capacity.saturating_sub(prefetch.buffer.lock().await.len()));
+            let sampler = Arc::clone(&prefetch.sampler);
+            let generated = tokio::task::spawn_blocking(move || {
+                (0..missing).map(|_| ScenarioSampler::sample_returns(sampler.as_ref())).collect::<Vec<Scenario>>()
+            })
+            .await;
+            match generated {
+                Ok(scenarios) => {
+                    let mut buffer = prefetch.buffer.lock().await;
+                    let room = prefetch.capacity.saturating_sub(buffer.len());
+                    buffer.extend(scenarios.into_iter().take(room));
+                }
+                Err(e) => warn!("scenario prefetch panicked: {}", e),
+            }
+            prefetch.refilling.store(false, Ordering::Release);
+        });
+    }
+}
+
+/// Hands out the prefetched scenarios first, then falls back to sampling fresh ones.
+pub struct PrefetchedSampler {
+    prefetched: std::sync::Mutex<VecDeque<Scenario>>,
+    fallback: Arc<dyn ScenarioSampler>,
+}
+
+impl PrefetchedSampler {
+    pub fn new(prefetched: Vec<Scenario>, fallback: Arc<dyn ScenarioSampler>) -> Self {
+        PrefetchedSampler {
+            prefetched: std::sync::Mutex::new(prefetched.into()),
+            fallback,
+        }
+    }
+}
+
+impl ScenarioSampler for PrefetchedSampler {
+    fn sample_returns(&self) -> Scenario {
+        self.try_sample_returns().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_sample_returns(&self) -> Result<Scenario, SimulationError> {
+        let prefetched = self.prefetched.lock().expect("prefetch buffer poisoned").pop_front();
+        match prefetched {
+            Some(scenario) => Ok(scenario),
+            None => self.fallback.try_sample_returns(),
+        }
+    }
+
+    fn dimension(&self) -> usize {
+        self.fallback.dimension()
+    }
+}
@@ -0,0 +1,124 @@
+// Rough progress for long run_batch calls, polled separately through GetBatchProgress.
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use aegis_athena_contracts::simulation::Progress;
+use dashmap::DashMap;
+
+// Finished (or abandoned) jobs stay pollable for a while after they end, then get dropped on the
+// next registration. Jobs still running are never dropped, however long they take.
+const PROGRESS_RETENTION: Duration = Duration::from_secs(3_600);
+
+#[derive(Debug)]
+pub struct BatchProgress {
+    completed: AtomicU32,
+    total: u32,
+    started: Instant,
+    finished: Mutex<Option<Instant>>, // None while the batch runs
+}
+
+impl BatchProgress {
+    /// `completed` is non-zero for batches resumed from a checkpoint.
+    pub fn new(total: u32, completed: u32) -> Self {
+        BatchProgress {
+            completed: AtomicU32::new(completed),
+            total,
+            started: Instant::now(),
+            finished: Mutex::new(None),
+        }
+    }
+
+    pub fn iteration_done(&self) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn to_protobuf(&self) -> Progress {
+        Progress {
+            completed: self.completed.load(Ordering::Relaxed),
+            total: self.total,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+        }
+    }
+
+    fn finish(&self) {
+        self.finished
+            .lock()
+            .expect("progress lock poisoned")
+            .get_or_insert_with(Instant::now);
+    }
+
+    fn expired(&self) -> bool {
+        self.finished
+            .lock()
+            .expect("progress lock poisoned")
+            .is_some_and(|finished| finished.elapsed() >= PROGRESS_RETENTION)
+    }
+}
+
+/// Held by the batch while it runs, marks the job finished when dropped however the batch ends
+/// (completed, failed, timed out or panicked).
+#[derive(Debug)]
+pub struct ProgressGuard {
+    progress: Arc<BatchProgress>,
+}
+
+impl Deref for ProgressGuard {
+    type Target = BatchProgress;
+
+    fn deref(&self) -> &BatchProgress {
+        &self.progress
+    }
+}
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        self.progress.finish();
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ProgressRegistry {
+    jobs: DashMap<String, Arc<BatchProgress>>,
+}
+
+impl ProgressRegistry {
+    pub fn register(&self, job_id: String, total: u32, completed: u32) -> ProgressGuard {
+        self.jobs.retain(|_, progress| !progress.expired());
+        let progress = Arc::new(BatchProgress::new(total, completed));
+        self.jobs.insert(job_id, Arc::clone(&progress));
+        ProgressGuard { progress }
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<Progress> {
+        self.jobs.get(job_id).map(|progress| progress.to_protobuf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumed_jobs_start_from_the_checkpoint() {
+        let registry = ProgressRegistry::default();
+        let progress = registry.register("job".to_string(), 100, 40);
+        progress.iteration_done();
+        let reported = registry.get("job").unwrap();
+        assert_eq!((reported.completed, reported.total), (41, 100));
+    }
+
+    #[test]
+    fn jobs_are_finished_when_the_guard_drops() {
+        let registry = ProgressRegistry::default();
+        let progress = registry.register("job".to_string(), 10, 0);
+        let job = Arc::clone(&registry.jobs.get("job").unwrap());
+        assert!(job.finished.lock().unwrap().is_none());
+        drop(progress);
+        assert!(job.finished.lock().unwrap().is_some());
+        // retention counts from the end of the batch, not from its start
+        assert!(!job.expired());
+        assert!(registry.get("job").is_some());
+    }
+}
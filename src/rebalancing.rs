@@ -0,0 +1,52 @@
+// How weights are maintained over a scenario. Constant weights (the default everywhere) amount
+// to rebalancing back to target at every period; band rebalancing lets them drift with the
+// assets and only trades when one strays too far from its target.
+use crate::performance::simple_return;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RebalancingStrategy {
+    EveryPeriod,
+    /// Rebalance once any weight is more than `threshold` (absolute, 0.05 = 5 points) off target.
+    BandRebalancing { threshold: f64 },
+}
+
+/// Dollar returns per period on `money_to_invest` with weights drifting between rebalances,
+/// and the number of rebalances that were triggered.
+pub fn band_portfolio_returns(
+    returns: &[Vec<f64>],
+    weights: &[f64],
+    money_to_invest: f64,
+    threshold: f64,
+) -> (Vec<f64>, u32) {
+    let mut current_weights = weights.to_vec();
+    let mut num_rebalancing_events = 0;
+
+    let portfolio_returns = returns
+        .iter()
+        .map(|row| {
+            let simple_returns: Vec<f64> = row.iter().map(|r| simple_return(*r)).collect();
+            let period_return = current_weights
+                .iter()
+                .zip(&simple_returns)
+                .map(|(weight, ret)| weight * ret)
+                .sum::<f64>();
+
+            // Let the weights drift: each grows with its asset, relative to the whole portfolio
+            for (weight, ret) in current_weights.iter_mut().zip(&simple_returns) {
+                *weight *= (1.0 + ret) / (1.0 + period_return);
+            }
+            let breached = current_weights
+                .iter()
+                .zip(weights)
+                .any(|(current, target)| (current - target).abs() > threshold);
+            if breached {
+                current_weights.copy_from_slice(weights);
+                num_rebalancing_events += 1;
+            }
+
+            period_return * money_to_invest
+        })
+        .collect();
+
+    (portfolio_returns, num_rebalancing_events)
+}
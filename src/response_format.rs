@@ -0,0 +1,219 @@
+// Encodings of a batch reply other than the typed gRPC message, for clients whose downstream
+// systems want something else. Picked with the `x-response-format` metadata header on
+// `RunBatchEncoded`:
+// - `proto`: the protobuf encoding of `SimulationBatchResult` (the default).
+// - `json` / `msgpack`: its serde representation, same field names as the proto.
+// - `arrow`: an Arrow IPC stream with one record batch and one row per portfolio, ready for
+//   `pyarrow.ipc.open_stream(data).read_pandas()`. Only the per-portfolio fields fit a table,
+//   `crisis_iterations` goes in the schema metadata and the remaining batch-level fields
+//   (last scenario, return correlation) are left out.
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use aegis_athena_contracts::simulation::SimulationBatchResult;
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt32Array};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use prost::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Proto,
+    Json,
+    MessagePack,
+    Arrow,
+}
+
+impl ResponseFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseFormat::Proto => "proto",
+            ResponseFormat::Json => "json",
+            ResponseFormat::MessagePack => "msgpack",
+            ResponseFormat::Arrow => "arrow",
+        }
+    }
+}
+
+impl FromStr for ResponseFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "proto" => Ok(ResponseFormat::Proto),
+            "json" => Ok(ResponseFormat::Json),
+            "msgpack" => Ok(ResponseFormat::MessagePack),
+            "arrow" => Ok(ResponseFormat::Arrow),
+            other => Err(format!(
+                "Unknown response format '{}', expected one of proto, json, msgpack, arrow",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    Json(serde_json::Error),
+    MessagePack(rmp_serde::encode::Error),
+    Arrow(ArrowError),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::Json(e) => write!(f, "Failed to encode the batch result as JSON: {}", e),
+            EncodeError::MessagePack(e) => write!(f, "Failed to encode the batch result as MessagePack: {}", e),
+            EncodeError::Arrow(e) => write!(f, "Failed to encode the batch result as Arrow: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+pub fn encode_batch_result(result: &SimulationBatchResult, format: ResponseFormat) -> Result<Vec<u8>, EncodeError> {
+    match format {
+        ResponseFormat::Proto => Ok(result.encode_to_vec()),
+        ResponseFormat::Json => serde_json::to_vec(result).map_err(EncodeError::Json),
+        // named, so the map keys match the JSON ones rather than being positional
+        ResponseFormat::MessagePack => rmp_serde::to_vec_named(result).map_err(EncodeError::MessagePack),
+        ResponseFormat::Arrow => arrow_stream(result).map_err(EncodeError::Arrow),
+    }
+}
+
+fn arrow_stream(result: &SimulationBatchResult) -> Result<Vec<u8>, ArrowError> {
+    let n_portfolios = result.sum_returns.len();
+    let mut fields = vec![Field::new("portfolio", DataType::UInt32, false)];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(UInt32Array::from_iter_values(0..n_portfolios as u32))];
+
+    let per_portfolio = [
+        ("sum_return", &result.sum_returns),
+        ("sum_volatility", &result.sum_volatilities),
+        ("sum_sharpe", &result.sum_sharpes),
+        ("sum_hedged_sharpe", &result.sum_hedged_sharpes),
+        ("sum_hedging_effectiveness", &result.sum_hedging_effectiveness),
+        ("sum_insured_sharpe", &result.sum_insured_sharpes),
+        ("sum_insured_return", &result.sum_insured_returns),
+        ("sum_insurance_cost", &result.sum_insurance_costs),
+        ("sum_dca_terminal_wealth", &result.sum_dca_terminal_wealth),
+        ("sum_lump_sum_terminal_wealth", &result.sum_lump_sum_terminal_wealth),
+        ("sum_stability_score", &result.sum_stability_scores),
+        ("sum_portfolio_dv01", &result.sum_portfolio_dv01s),
+        ("sum_portfolio_convexity", &result.sum_portfolio_convexities),
+    ];
+    for (name, values) in per_portfolio {
+        fields.push(Field::new(name, DataType::Float64, false));
+        columns.push(Arc::new(Float64Array::from(values.clone())));
+    }
+
+    // portfolio x expression on the wire, one column per expression here
+    let n_custom_metrics = result.custom_metrics.first().map_or(0, |metrics| metrics.values.len());
+    for k in 0..n_custom_metrics {
+        fields.push(Field::new(format!("custom_metric_{}", k), DataType::Float64, false));
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            result.custom_metrics.iter().map(|metrics| metrics.values[k]),
+        )));
+    }
+
+    // empty string on the wire means no error, null is the table equivalent
+    fields.push(Field::new("error", DataType::Utf8, true));
+    columns.push(Arc::new(StringArray::from_iter(
+        result.portfolio_errors.iter().map(|e| (!e.is_empty()).then_some(e.as_str())),
+    )));
+
+    let metadata = HashMap::from([
+        ("crisis_iterations".to_string(), result.crisis_iterations.to_string()),
+        ("sampling_retry_count".to_string(), result.sampling_retry_count.to_string()),
+    ]);
+    let schema = Arc::new(Schema::new(fields).with_metadata(metadata));
+    let batch = RecordBatch::try_new(Arc::clone(&schema), columns)?;
+
+    let mut writer = StreamWriter::try_new(Vec::new(), &schema)?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    writer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow_array::Array;
+    use arrow_ipc::reader::StreamReader;
+
+    use super::*;
+
+    // two portfolios, the second one failed
+    fn batch_result() -> SimulationBatchResult {
+        let zeros = vec![0.0; 2];
+        SimulationBatchResult {
+            sum_returns: vec![12.5, -3.25],
+            sum_volatilities: vec![0.2, 0.3],
+            sum_sharpes: vec![1.5, -0.5],
+            portfolio_errors: vec![String::new(), "weights sum to 0".to_string()],
+            crisis_iterations: 3,
+            // every per-portfolio sum is filled in by a batch, with zeros when not configured
+            sum_hedged_sharpes: zeros.clone(),
+            sum_hedging_effectiveness: zeros.clone(),
+            sum_insured_sharpes: zeros.clone(),
+            sum_insured_returns: zeros.clone(),
+            sum_insurance_costs: zeros.clone(),
+            sum_dca_terminal_wealth: zeros.clone(),
+            sum_lump_sum_terminal_wealth: zeros.clone(),
+            sum_stability_scores: zeros.clone(),
+            sum_portfolio_dv01s: zeros.clone(),
+            sum_portfolio_convexities: zeros,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn proto_round_trips() {
+        let result = batch_result();
+        let bytes = encode_batch_result(&result, ResponseFormat::Proto).unwrap();
+        assert_eq!(SimulationBatchResult::decode(bytes.as_slice()).unwrap(), result);
+    }
+
+    #[test]
+    fn json_and_msgpack_keep_the_proto_field_names() {
+        let json = encode_batch_result(&batch_result(), ResponseFormat::Json).unwrap();
+        let msgpack = encode_batch_result(&batch_result(), ResponseFormat::MessagePack).unwrap();
+        for value in [
+            serde_json::from_slice::<serde_json::Value>(&json).unwrap(),
+            rmp_serde::from_slice::<serde_json::Value>(&msgpack).unwrap(),
+        ] {
+            assert_eq!(value["sum_returns"], serde_json::json!([12.5, -3.25]));
+            assert_eq!(value["portfolio_errors"], serde_json::json!(["", "weights sum to 0"]));
+            assert_eq!(value["crisis_iterations"], serde_json::json!(3));
+        }
+    }
+
+    #[test]
+    fn arrow_has_one_row_per_portfolio() {
+        let bytes = encode_batch_result(&batch_result(), ResponseFormat::Arrow).unwrap();
+        let mut reader = StreamReader::try_new(Cursor::new(bytes), None).unwrap();
+        assert_eq!(reader.schema().metadata()["crisis_iterations"], "3");
+        let batch = reader.next().unwrap().unwrap();
+        assert!(reader.next().is_none());
+        assert_eq!(batch.num_rows(), 2);
+
+        let sum_returns = batch
+            .column_by_name("sum_return")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(sum_returns.values().to_vec(), vec![12.5, -3.25]);
+        // no error is a null, not an empty string
+        let errors = batch
+            .column_by_name("error")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert!(errors.is_null(0));
+        assert_eq!(errors.value(1), "weights sum to 0");
+    }
+}
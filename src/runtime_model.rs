@@ -0,0 +1,126 @@
+// Linear runtime model for client-side planning:
+//   runtime_ms = base_latency_ms + iterations * n_portfolios * ms_per_portfolio_iteration
+// fitted from a short warmup at startup, on this machine and with the server's own sampler.
+use std::time::Instant;
+
+use crate::performance::compute_portfolio_performance;
+use crate::sampling::ScenarioSampler;
+use crate::stats::mean_and_std;
+
+const CALIBRATION_REPEATS: usize = 5;
+const CALIBRATION_PORTFOLIOS: usize = 64;
+const CALIBRATION_ITERATIONS: usize = 4;
+const Z_95: f64 = 1.96;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeModel {
+    pub base_latency_ms: f64,
+    pub ms_per_portfolio_iteration: f64,
+    pub ms_per_portfolio_iteration_std: f64, // spread over the calibration repeats
+}
+
+impl Default for RuntimeModel {
+    // Uncalibrated fallback: 600k portfolio-iterations per second (1/600 ms each) and 1 ms of
+    // fixed cost, with a std as large as the slope itself so the interval stays honest about it
+    fn default() -> Self {
+        RuntimeModel {
+            base_latency_ms: 1.0,
+            ms_per_portfolio_iteration: 1.0 / 600.0,
+            ms_per_portfolio_iteration_std: 1.0 / 600.0,
+        }
+    }
+}
+
+impl RuntimeModel {
+    /// Times a small single-portfolio batch (the fixed cost) and a larger one (the per
+    /// evaluation cost) a few times each. Falls back to `Default` if the sampler produces
+    /// nothing to evaluate (no periods or no assets) or the timings make no sense.
+    pub fn calibrate<S: ScenarioSampler + ?Sized>(sampler: &S) -> Self {
+        let probe = sampler.sample_returns();
+        if probe.first().is_none_or(Vec::is_empty) {
+            return RuntimeModel::default();
+        }
+
+        let time_batch = |n_portfolios: usize, iterations: usize| {
+            let started = Instant::now();
+            for _ in 0..iterations {
+                let scenario = sampler.sample_returns();
+                let n_assets = scenario.first().map_or(0, Vec::len).max(1);
+                let weights = vec![1.0 / n_assets as f64; n_assets];
+                for _ in 0..n_portfolios {
+                    compute_portfolio_performance(&scenario, &weights, 1_000.0, 0.0, 365.0);
+                }
+            }
+            started.elapsed().as_secs_f64() * 1_000.0
+        };
+
+        let mut base_samples = Vec::with_capacity(CALIBRATION_REPEATS);
+        let mut slope_samples = Vec::with_capacity(CALIBRATION_REPEATS);
+        for _ in 0..CALIBRATION_REPEATS {
+            let base = time_batch(1, 1);
+            let large = time_batch(CALIBRATION_PORTFOLIOS, CALIBRATION_ITERATIONS);
+            let extra_work = (CALIBRATION_PORTFOLIOS * CALIBRATION_ITERATIONS - 1) as f64;
+            base_samples.push(base);
+            slope_samples.push(((large - base) / extra_work).max(0.0));
+        }
+
+        let (base_latency_ms, _) = mean_and_std(&base_samples);
+        let (ms_per_portfolio_iteration, ms_per_portfolio_iteration_std) =
+            mean_and_std(&slope_samples);
+        if ![base_latency_ms, ms_per_portfolio_iteration, ms_per_portfolio_iteration_std]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            return RuntimeModel::default();
+        }
+        RuntimeModel {
+            base_latency_ms,
+            ms_per_portfolio_iteration,
+            ms_per_portfolio_iteration_std,
+        }
+    }
+
+    /// Point estimate and a 95% interval, in milliseconds.
+    pub fn estimate_ms(&self, n_portfolios: usize, iterations: usize) -> (u64, u64, u64) {
+        let work = (n_portfolios * iterations) as f64;
+        let at = |slope: f64| (self.base_latency_ms + work * slope.max(0.0)).round() as u64;
+        let spread = Z_95 * self.ms_per_portfolio_iteration_std;
+        (
+            at(self.ms_per_portfolio_iteration),
+            at(self.ms_per_portfolio_iteration - spread),
+            at(self.ms_per_portfolio_iteration + spread),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::Scenario;
+
+    struct FixedSampler(Scenario);
+
+    impl ScenarioSampler for FixedSampler {
+        fn sample_returns(&self) -> Scenario {
+            self.0.clone()
+        }
+
+        fn dimension(&self) -> usize {
+            self.0.first().map_or(0, Vec::len)
+        }
+    }
+
+    #[test]
+    fn empty_scenarios_fall_back_to_the_default() {
+        assert_eq!(RuntimeModel::calibrate(&FixedSampler(Vec::new())), RuntimeModel::default());
+        assert_eq!(RuntimeModel::calibrate(&FixedSampler(vec![Vec::new(); 3])), RuntimeModel::default());
+    }
+
+    #[test]
+    fn calibrated_model_is_usable() {
+        let model = RuntimeModel::calibrate(&FixedSampler(vec![vec![0.01, -0.02]; 30]));
+        assert!(model.base_latency_ms >= 0.0 && model.ms_per_portfolio_iteration >= 0.0);
+        let (estimate, lower, upper) = model.estimate_ms(100, 10);
+        assert!(lower <= estimate && estimate <= upper);
+    }
+}
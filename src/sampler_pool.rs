@@ -0,0 +1,126 @@
+// Pooled samplers, for `Sampler`s that hold a connection to an external market data source.
+// Each batch checks one out in its async handler, before entering blocking code, and keeps it
+// until the batch is done instead of connecting per call.
+use std::convert::Infallible;
+use std::time::Duration;
+
+use aegis_athena_contracts::sampling::Sampler;
+use deadpool::Runtime;
+use deadpool::managed::{self, Metrics, Pool, PoolError, RecycleResult};
+
+use crate::error::SimulationError;
+use crate::sampling::{Scenario, ScenarioSampler};
+
+// Every pooled sampler starts out as a clone of the template, i.e. with its own connection. Idle
+// ones are kept and handed out again rather than recreated.
+pub struct SamplerManager {
+    template: Sampler,
+}
+
+impl managed::Manager for SamplerManager {
+    type Type = Sampler;
+    type Error = Infallible;
+
+    async fn create(&self) -> Result<Sampler, Infallible> {
+        Ok(self.template.clone())
+    }
+
+    async fn recycle(&self, _sampler: &mut Sampler, _metrics: &Metrics) -> RecycleResult<Infallible> {
+        Ok(())
+    }
+}
+
+pub struct SamplerPool {
+    inner: Pool<SamplerManager>,
+    acquire_timeout: Duration,
+    dimension: usize, // every pooled sampler is a clone of the same template
+}
+
+impl SamplerPool {
+    pub fn new(template: Sampler, max_size: usize, acquire_timeout: Duration) -> Self {
+        let dimension = ScenarioSampler::dimension(&template);
+        let inner = Pool::builder(SamplerManager { template })
+            .max_size(max_size.max(1))
+            .wait_timeout(Some(acquire_timeout))
+            .runtime(Runtime::Tokio1)
+            .build()
+            .expect("a runtime is configured, so the timeout is accepted");
+        SamplerPool {
+            inner,
+            acquire_timeout,
+            dimension,
+        }
+    }
+}
+
+/// A sampler checked out of the pool for one batch, handed back when dropped. Sampling from it
+/// needs no runtime, so it can be used from blocking tasks and rayon workers alike.
+pub struct PooledSampler {
+    sampler: managed::Object<SamplerManager>,
+}
+
+impl ScenarioSampler for PooledSampler {
+    fn sample_returns(&self) -> Scenario {
+        Sampler::sample_returns(&self.sampler)
+    }
+
+    fn dimension(&self) -> usize {
+        Sampler::dimension(&self.sampler)
+    }
+}
+
+impl SamplerPool {
+    /// Checks a sampler out, waiting at most `acquire_timeout` for one to be returned once
+    /// `max_size` are in use. Call it from the async handler, before any blocking work.
+    pub async fn acquire(&self) -> Result<PooledSampler, SimulationError> {
+        let sampler = self.inner.get().await.map_err(|e| match e {
+            PoolError::Timeout(_) => SimulationError::SamplerUnavailable(format!(
+                "no sampler became available within {:?}",
+                self.acquire_timeout
+            )),
+            other => SimulationError::SamplerUnavailable(other.to_string()),
+        })?;
+        Ok(PooledSampler { sampler })
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Samplers currently alive, checked out or idle.
+    pub fn size(&self) -> usize {
+        self.inner.status().size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pooled_sampler_samples_from_rayon_workers() {
+        let pool = SamplerPool::new(Sampler::default(), 2, Duration::from_secs(1));
+        let pooled = pool.acquire().await.expect("the pool has room");
+        let dimension = pool.dimension();
+
+        // no tokio runtime on rayon's threads, which used to panic in block_on
+        let widths: Vec<Vec<usize>> = (0..8)
+            .into_par_iter()
+            .map(|_| pooled.sample_returns().iter().map(Vec::len).collect())
+            .collect();
+        assert!(widths.iter().flatten().all(|width| *width == dimension));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn samplers_are_reused_and_bounded() {
+        let pool = SamplerPool::new(Sampler::default(), 1, Duration::from_millis(50));
+        let first = pool.acquire().await.expect("the pool has room");
+        assert!(matches!(pool.acquire().await, Err(SimulationError::SamplerUnavailable(_))));
+
+        drop(first);
+        let _second = pool.acquire().await.expect("the first sampler went back to the pool");
+        assert_eq!(pool.size(), 1);
+    }
+}
@@ -0,0 +1,81 @@
+// Sampler state that should survive a restart (for samplers that carry time-varying state,
+// e.g. a current volatility or regime). The whole sampler is persisted with bincode, so
+// whatever state it holds comes back as it was.
+use std::path::Path;
+
+use aegis_athena_contracts::sampling::Sampler;
+
+pub trait PersistSamplerState {
+    fn save_state(&self) -> Vec<u8>;
+    /// Replaces `self` with the saved sampler. `self` is untouched if `state` doesn't decode.
+    fn load_state(&mut self, state: &[u8]) -> Result<(), bincode::Error>;
+}
+
+impl PersistSamplerState for Sampler {
+    fn save_state(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("an in-memory sampler always serializes")
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), bincode::Error> {
+        *self = bincode::deserialize(state)?;
+        Ok(())
+    }
+}
+
+/// Reads a sampler saved by `save_sampler`. A missing file is not an error, there is just
+/// nothing to restore on a first start.
+pub async fn load_sampler(sampler: &mut Sampler, path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    match tokio::fs::read(path).await {
+        Ok(state) => {
+            sampler.load_state(&state)?;
+            Ok(true)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn save_sampler(sampler: &Sampler, path: &Path) -> std::io::Result<()> {
+    // Write then rename, so a crash mid-write can't leave a truncated state behind
+    let partial = path.with_extension("partial");
+    tokio::fs::write(&partial, sampler.save_state()).await?;
+    tokio::fs::rename(&partial, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_round_trips() {
+        let sampler = Sampler::default();
+        let state = sampler.save_state();
+
+        let mut restored = Sampler::default();
+        restored.load_state(&state).expect("a saved state decodes");
+        assert_eq!(restored.save_state(), state);
+    }
+
+    #[test]
+    fn undecodable_state_leaves_the_sampler_alone() {
+        let mut sampler = Sampler::default();
+        let before = sampler.save_state();
+        assert!(sampler.load_state(&[0xff; 3]).is_err());
+        assert_eq!(sampler.save_state(), before);
+    }
+
+    #[tokio::test]
+    async fn saved_file_restores_the_sampler() {
+        let path = std::env::temp_dir().join(format!("athena-sampler-state-{}.bin", std::process::id()));
+        let sampler = Sampler::default();
+        save_sampler(&sampler, &path).await.expect("temp dir is writable");
+
+        let mut restored = Sampler::default();
+        assert!(load_sampler(&mut restored, &path).await.expect("the file was just written"));
+        assert_eq!(restored.save_state(), sampler.save_state());
+        tokio::fs::remove_file(&path).await.ok();
+
+        // nothing saved yet is a first start, not an error
+        assert!(!load_sampler(&mut restored, &path).await.expect("a missing file is fine"));
+    }
+}
@@ -0,0 +1,1000 @@
+// Server-side scenario generation.
+//
+// The contracts `Sampler` covers the parametric modes shared with Aegis. `SamplerMode` holds
+// the modes that only make sense on the simulation server (e.g. scenarios registered by a
+// client). Both implement `ScenarioSampler` so `run_batch` doesn't care which one it got.
+//
+// Which one a batch gets is decided by `EvolutionConfig.simulation_mode` (how scenarios are
+// generated), independently of `distribution_params` (what the returns look like).
+use std::f64::consts::{FRAC_PI_2, PI, SQRT_2};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use aegis_athena_contracts::sampling::Sampler;
+use rand::Rng;
+use rand_distr::{ChiSquared, Distribution, Gamma, StandardNormal};
+use tracing::warn;
+
+use crate::error::SimulationError;
+use crate::linalg::{LinalgError, cholesky};
+use crate::stats::{mean_and_std, normal_cdf, normal_inv_cdf, sorted_quantile, student_t_cdf, student_t_inv_cdf};
+use crate::vine::{PairCopulaSpec, sample_c_vine, validate_c_vine};
+
+/// One sampled scenario: `periods x assets` log-returns.
+pub type Scenario = Vec<Vec<f64>>;
+
+pub trait ScenarioSampler: Send + Sync {
+    fn sample_returns(&self) -> Scenario;
+
+    /// Number of assets per period in the scenarios this sampler produces.
+    fn dimension(&self) -> usize;
+
+    /// For samplers that can fail (e.g. waiting on a pooled connection). Infallible by default.
+    fn try_sample_returns(&self) -> Result<Scenario, SimulationError> {
+        Ok(self.sample_returns())
+    }
+}
+
+impl ScenarioSampler for Sampler {
+    fn sample_returns(&self) -> Scenario {
+        Sampler::sample_returns(self)
+    }
+
+    fn dimension(&self) -> usize {
+        Sampler::dimension(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScenarioSelection {
+    Cycle,  // in registration order, wrapping around (reproducible)
+    Random, // uniformly with replacement
+}
+
+/// Client-supplied scenarios (`iterations x periods x assets`), replayed as-is.
+#[derive(Debug)]
+pub struct ScenarioSet {
+    scenarios: Arc<Vec<Scenario>>,
+    selection: ScenarioSelection,
+    cursor: AtomicUsize,
+}
+
+impl ScenarioSet {
+    pub fn new(scenarios: Arc<Vec<Scenario>>, selection: ScenarioSelection) -> Self {
+        ScenarioSet {
+            scenarios,
+            selection,
+            cursor: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Marginal distribution of one asset's log-return, applied through its inverse CDF.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarginalSpec {
+    Normal { mean: f64, std_dev: f64 },
+    Laplace { location: f64, scale: f64 },
+    Empirical { sorted_returns: Vec<f64> }, // historical returns, ascending
+    StudentT { location: f64, scale: f64, degrees_of_freedom: f64 },
+}
+
+impl MarginalSpec {
+    pub fn inverse_cdf(&self, u: f64) -> f64 {
+        match self {
+            MarginalSpec::Normal { mean, std_dev } => mean + std_dev * normal_inv_cdf(u),
+            MarginalSpec::Laplace { location, scale } => {
+                let centered = u - 0.5;
+                location - scale * centered.signum() * (1.0 - 2.0 * centered.abs()).ln()
+            }
+            MarginalSpec::Empirical { sorted_returns } => sorted_quantile(sorted_returns, u),
+            MarginalSpec::StudentT {
+                location,
+                scale,
+                degrees_of_freedom,
+            } => location + scale * student_t_inv_cdf(u, *degrees_of_freedom),
+        }
+    }
+}
+
+/// One marginal per asset of the copula (or vine) joining them. The constructors panic on a
+/// mismatch, the service checks it first to answer INVALID_ARGUMENT instead.
+pub fn check_marginals(marginals: &[MarginalSpec], dimension: usize) -> Result<(), String> {
+    if marginals.len() != dimension {
+        return Err(format!("{} marginals for a {}-asset copula", marginals.len(), dimension));
+    }
+    Ok(())
+}
+
+/// Where the uniforms feeding a copula come from.
+#[derive(Debug)]
+pub enum UniformSource {
+    Pseudorandom,
+    /// Halton low-discrepancy sequence, one prime base per asset and one point per period.
+    /// Faster convergence of the batch means than pseudorandom draws for smooth metrics.
+    Halton { bases: Vec<u64>, cursor: AtomicUsize },
+}
+
+impl UniformSource {
+    pub fn halton(dimension: usize) -> Self {
+        UniformSource::Halton {
+            bases: first_primes(dimension),
+            cursor: AtomicUsize::new(1), // index 0 is the all-zero point, i.e. -inf after the normal quantile
+        }
+    }
+
+    fn next_point(&self, dimension: usize, rng: &mut impl Rng) -> Vec<f64> {
+        match self {
+            UniformSource::Pseudorandom => (0..dimension).map(|_| rng.random_range(f64::EPSILON..1.0)).collect(),
+            UniformSource::Halton { bases, cursor } => {
+                let index = cursor.fetch_add(1, Ordering::Relaxed) as u64;
+                bases.iter().map(|base| radical_inverse(index, *base)).collect()
+            }
+        }
+    }
+}
+
+fn first_primes(count: usize) -> Vec<u64> {
+    let mut primes: Vec<u64> = Vec::with_capacity(count);
+    let mut candidate = 2u64;
+    while primes.len() < count {
+        if primes.iter().take_while(|p| *p * *p <= candidate).all(|p| candidate % p != 0) {
+            primes.push(candidate);
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+// Van der Corput: mirror the base-`base` digits of `index` around the radix point
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut factor = inverse_base;
+    let mut value = 0.0;
+    while index > 0 {
+        value += (index % base) as f64 * factor;
+        index /= base;
+        factor *= inverse_base;
+    }
+    value
+}
+
+#[derive(Debug)]
+pub enum SamplerMode {
+    ScenarioSet(ScenarioSet),
+    /// Student-t copula: joint tails fatten as `degrees_of_freedom` drops, unlike the Gaussian
+    /// copula whose tail dependence is zero. Build with `SamplerMode::copula_t`.
+    CopulaT {
+        degrees_of_freedom: f64,
+        correlation_matrix: Vec<Vec<f64>>,
+        marginals: Vec<MarginalSpec>,
+        periods_to_sample: usize,
+        cholesky_factor: Vec<Vec<f64>>, // of correlation_matrix, computed once
+    },
+    /// Gaussian copula over arbitrary marginals, fed by pseudorandom (Monte Carlo) or Halton
+    /// (quasi-Monte Carlo) uniforms. Build with `SamplerMode::gaussian_copula`.
+    GaussianCopula {
+        marginals: Vec<MarginalSpec>,
+        periods_to_sample: usize,
+        cholesky_factor: Vec<Vec<f64>>,
+        uniforms: UniformSource,
+    },
+    /// Gaussian copula whose independent shocks follow an AR(1) from one scenario to the next,
+    /// `e_t = rho e_(t-1) + sqrt(1 - rho^2) eps_t`, so each latent normal is correlated `rho` with
+    /// the same period and asset of the previous scenario. For crises that persist across
+    /// iterations. Build with `SamplerMode::serially_correlated_copula`.
+    SeriallyCorrelatedCopula {
+        marginals: Vec<MarginalSpec>,
+        periods_to_sample: usize,
+        cholesky_factor: Vec<Vec<f64>>,
+        inter_iteration_correlation: f64,
+        previous_shocks: Mutex<Option<Vec<Vec<f64>>>>, // None until the first draw
+    },
+    /// C-vine pair copula construction, see `vine`. Each pair can have its own family, which
+    /// lets e.g. equities share lower tail dependence (Clayton) while other pairs stay Gaussian.
+    /// Build with `SamplerMode::c_vine`.
+    CVine {
+        pair_copulas: Vec<Vec<PairCopulaSpec>>,
+        marginals: Vec<MarginalSpec>,
+        periods_to_sample: usize,
+    },
+    /// Variance-gamma log-returns: Brownian motion with drift `theta` and volatility `sigma`
+    /// run on a gamma clock, `X = theta G + sigma sqrt(G) Z` with `G ~ Gamma(dt / nu, nu)`.
+    /// `theta` < 0 gives left skew and `nu` sets the excess kurtosis, without any jumps.
+    /// Assets are drawn independently. Build with `SamplerMode::variance_gamma`.
+    VarianceGamma {
+        sigma: f64,
+        nu: f64,
+        theta: f64,
+        dt: f64,
+        n_assets: usize,
+        periods_to_sample: usize,
+    },
+    /// α-stable log-returns `location + scale X`, X drawn by Chambers-Mallows-Stuck. `alpha` < 2
+    /// gives power-law tails (infinite variance), `alpha` = 2 is the Gaussian N(location, 2 scale^2).
+    /// With one asset `beta` sets the skew. With more, the vector is sub-Gaussian,
+    /// `sqrt(A) G` with `G` correlated normal and `A` a totally skewed α/2-stable draw shared by
+    /// all assets. That representation is symmetric, so `beta` must be 0.
+    /// Build with `SamplerMode::stable`.
+    Stable {
+        alpha: f64,
+        beta: f64,
+        scale: f64,
+        location: f64,
+        periods_to_sample: usize,
+        cholesky_factor: Vec<Vec<f64>>, // of the correlation matrix, 1x1 for a single asset
+    },
+    /// Gaussian kernel density estimate of each asset's historical log-returns: pick a past
+    /// return at random and add `bandwidth` times a normal shock, reflecting anything that lands
+    /// outside the historical range back inside it. Assets are drawn independently, so there is
+    /// no cross-asset correlation here, feed the KDE into a copula if that matters.
+    /// Build with `SamplerMode::kde`.
+    Kde {
+        historical_returns: Vec<Vec<f64>>, // per asset, NaNs (missing data) dropped
+        bandwidths: Vec<f64>,              // per asset, Silverman's rule unless given
+        periods_to_sample: usize,
+    },
+    /// Moving block bootstrap over historical scenarios: blocks of consecutive periods are drawn
+    /// with replacement and concatenated, which keeps short-range autocorrelation intact.
+    BlockBootstrap {
+        history: Arc<Vec<Scenario>>,
+        block_size: usize,
+        periods_to_sample: usize,
+    },
+    /// Each scenario comes entirely from `historical` with probability `historical_weight`,
+    /// from `parametric` otherwise. A compromise when the record is too short to bootstrap
+    /// alone but long enough not to throw away. Build with `SamplerMode::hybrid`.
+    Hybrid {
+        historical: Box<SamplerMode>,
+        parametric: Box<SamplerMode>,
+        historical_weight: f64,
+    },
+}
+
+// A KDE bandwidth beyond this many times an asset's historical range is a flat density, almost
+// certainly a unit mistake, and refused by the service
+pub const MAX_KDE_BANDWIDTH_RANGES: f64 = 10.0;
+
+impl SamplerMode {
+    pub fn gaussian_copula(
+        correlation_matrix: &[Vec<f64>],
+        marginals: Vec<MarginalSpec>,
+        periods_to_sample: usize,
+        quasi_random: bool,
+    ) -> Result<Self, LinalgError> {
+        check_marginals(&marginals, correlation_matrix.len())
+            .unwrap_or_else(|e| panic!("Configuration Error: {}.", e));
+        let cholesky_factor = cholesky(correlation_matrix)?;
+        let uniforms = if quasi_random {
+            UniformSource::halton(marginals.len())
+        } else {
+            UniformSource::Pseudorandom
+        };
+        Ok(SamplerMode::GaussianCopula {
+            marginals,
+            periods_to_sample,
+            cholesky_factor,
+            uniforms,
+        })
+    }
+
+    pub fn serially_correlated_copula(
+        correlation_matrix: &[Vec<f64>],
+        marginals: Vec<MarginalSpec>,
+        periods_to_sample: usize,
+        inter_iteration_correlation: f64,
+    ) -> Result<Self, LinalgError> {
+        // |rho| = 1 would freeze (or flip) the very first scenario forever
+        if inter_iteration_correlation.is_nan() || inter_iteration_correlation.abs() >= 1.0 {
+            panic!(
+                "Configuration Error: inter_iteration_correlation must be in (-1, 1) (found {}).",
+                inter_iteration_correlation
+            );
+        }
+        check_marginals(&marginals, correlation_matrix.len())
+            .unwrap_or_else(|e| panic!("Configuration Error: {}.", e));
+        let cholesky_factor = cholesky(correlation_matrix)?;
+        Ok(SamplerMode::SeriallyCorrelatedCopula {
+            marginals,
+            periods_to_sample,
+            cholesky_factor,
+            inter_iteration_correlation,
+            previous_shocks: Mutex::new(None),
+        })
+    }
+
+    pub fn c_vine(
+        pair_copulas: Vec<Vec<PairCopulaSpec>>,
+        marginals: Vec<MarginalSpec>,
+        periods_to_sample: usize,
+    ) -> Self {
+        let dimension = validate_c_vine(&pair_copulas)
+            .unwrap_or_else(|e| panic!("Configuration Error: invalid C-vine: {}", e));
+        check_marginals(&marginals, dimension).unwrap_or_else(|e| panic!("Configuration Error: {}.", e));
+        SamplerMode::CVine {
+            pair_copulas,
+            marginals,
+            periods_to_sample,
+        }
+    }
+
+    pub fn variance_gamma(
+        sigma: f64,
+        nu: f64,
+        theta: f64,
+        dt: f64,
+        n_assets: usize,
+        periods_to_sample: usize,
+    ) -> Self {
+        if sigma.is_nan() || sigma < 0.0 {
+            panic!("Configuration Error: sigma must be >= 0 (found {}).", sigma);
+        }
+        if nu.is_nan() || nu <= 0.0 || dt.is_nan() || dt <= 0.0 {
+            panic!("Configuration Error: nu and dt must be > 0 (found nu = {}, dt = {}).", nu, dt);
+        }
+        SamplerMode::VarianceGamma {
+            sigma,
+            nu,
+            theta,
+            dt,
+            n_assets,
+            periods_to_sample,
+        }
+    }
+
+    pub fn stable(
+        alpha: f64,
+        beta: f64,
+        scale: f64,
+        location: f64,
+        correlation_matrix: &[Vec<f64>],
+        periods_to_sample: usize,
+    ) -> Result<Self, LinalgError> {
+        if alpha.is_nan() || alpha <= 0.0 || alpha > 2.0 {
+            panic!("Configuration Error: alpha must be in (0, 2] (found {}).", alpha);
+        }
+        if !(-1.0..=1.0).contains(&beta) {
+            panic!("Configuration Error: beta must be in [-1, 1] (found {}).", beta);
+        }
+        if scale.is_nan() || scale <= 0.0 || !location.is_finite() {
+            panic!(
+                "Configuration Error: scale must be > 0 and location finite (found scale = {}, location = {}).",
+                scale, location
+            );
+        }
+        if correlation_matrix.len() > 1 && beta != 0.0 {
+            panic!(
+                "Configuration Error: a sub-Gaussian stable vector is symmetric, beta must be 0 for {} assets (found {}).",
+                correlation_matrix.len(),
+                beta
+            );
+        }
+        let cholesky_factor = cholesky(correlation_matrix)?;
+        Ok(SamplerMode::Stable {
+            alpha,
+            beta,
+            scale,
+            location,
+            periods_to_sample,
+            cholesky_factor,
+        })
+    }
+
+    /// `historical_returns` is `periods x assets`. Without a `bandwidth` each asset gets its own
+    /// from Silverman's rule of thumb.
+    pub fn kde(historical_returns: &[Vec<f64>], bandwidth: Option<f64>, periods_to_sample: usize) -> Self {
+        if let Some(h) = bandwidth {
+            if h.is_nan() || h < 0.0 {
+                panic!("Configuration Error: bandwidth must be >= 0 (found {}).", h);
+            }
+        }
+        let n_assets = historical_returns.first().map_or(0, Vec::len);
+        let columns: Vec<Vec<f64>> = (0..n_assets)
+            .map(|asset| {
+                historical_returns
+                    .iter()
+                    .map(|row| row[asset])
+                    .filter(|log_return| !log_return.is_nan())
+                    .collect()
+            })
+            .collect();
+        if let Some(asset) = columns.iter().position(Vec::is_empty) {
+            panic!("Configuration Error: asset {} has no historical returns to estimate a density from.", asset);
+        }
+        if n_assets > 1 {
+            warn!("KDE sampling draws the {} assets independently, cross-asset correlation is not modelled", n_assets);
+        }
+        let bandwidths = columns
+            .iter()
+            .map(|column| bandwidth.unwrap_or_else(|| silverman_bandwidth(column)))
+            .collect();
+        SamplerMode::Kde {
+            historical_returns: columns,
+            bandwidths,
+            periods_to_sample,
+        }
+    }
+
+    pub fn block_bootstrap(history: Arc<Vec<Scenario>>, block_size: usize, periods_to_sample: usize) -> Self {
+        if history.iter().all(Vec::is_empty) {
+            panic!("Configuration Error: cannot bootstrap from an empty history.");
+        }
+        SamplerMode::BlockBootstrap {
+            history,
+            block_size: block_size.max(1), // 0 is the proto default, i.e. a plain iid bootstrap
+            periods_to_sample,
+        }
+    }
+
+    pub fn copula_t(
+        degrees_of_freedom: f64,
+        correlation_matrix: Vec<Vec<f64>>,
+        marginals: Vec<MarginalSpec>,
+        periods_to_sample: usize,
+    ) -> Result<Self, LinalgError> {
+        if degrees_of_freedom <= 0.0 {
+            panic!("Configuration Error: degrees_of_freedom must be > 0 (found {}).", degrees_of_freedom);
+        }
+        check_marginals(&marginals, correlation_matrix.len())
+            .unwrap_or_else(|e| panic!("Configuration Error: {}.", e));
+        let cholesky_factor = cholesky(&correlation_matrix)?;
+        Ok(SamplerMode::CopulaT {
+            degrees_of_freedom,
+            correlation_matrix,
+            marginals,
+            periods_to_sample,
+            cholesky_factor,
+        })
+    }
+
+    pub fn hybrid(historical: SamplerMode, parametric: SamplerMode, historical_weight: f64) -> Self {
+        if !(0.0..=1.0).contains(&historical_weight) {
+            panic!(
+                "Configuration Error: historical_weight must be in [0, 1] (found {}).",
+                historical_weight
+            );
+        }
+        // a draw from either side must fit the same portfolios
+        if historical.dimension() != parametric.dimension() {
+            panic!(
+                "Configuration Error: {}-asset history mixed with a {}-asset parametric model.",
+                historical.dimension(),
+                parametric.dimension()
+            );
+        }
+        SamplerMode::Hybrid {
+            historical: Box::new(historical),
+            parametric: Box::new(parametric),
+            historical_weight,
+        }
+    }
+}
+
+/// Lower (= upper) tail dependence coefficient of a bivariate t-copula with correlation `rho`.
+/// Positive for any finite `degrees_of_freedom`, tending to 0 (the Gaussian case) as it grows.
+pub fn t_copula_tail_dependence(degrees_of_freedom: f64, rho: f64) -> f64 {
+    let nu = degrees_of_freedom;
+    2.0 * student_t_cdf(-((nu + 1.0) * (1.0 - rho) / (1.0 + rho)).sqrt(), nu + 1.0)
+}
+
+impl ScenarioSampler for SamplerMode {
+    fn sample_returns(&self) -> Scenario {
+        match self {
+            SamplerMode::ScenarioSet(set) => {
+                let index = match set.selection {
+                    ScenarioSelection::Cycle => {
+                        set.cursor.fetch_add(1, Ordering::Relaxed) % set.scenarios.len()
+                    }
+                    ScenarioSelection::Random => rand::rng().random_range(0..set.scenarios.len()),
+                };
+                set.scenarios[index].clone()
+            }
+            SamplerMode::CopulaT {
+                degrees_of_freedom,
+                marginals,
+                periods_to_sample,
+                cholesky_factor,
+                ..
+            } => {
+                let mut rng = rand::rng();
+                let chi_squared = ChiSquared::new(*degrees_of_freedom)
+                    .expect("degrees_of_freedom is validated in SamplerMode::copula_t");
+                let n_assets = marginals.len();
+
+                (0..*periods_to_sample)
+                    .map(|_| {
+                        // multivariate t = correlated normal / sqrt(chi2 / nu), with one shared chi2 draw
+                        let shocks: Vec<f64> = (0..n_assets).map(|_| StandardNormal.sample(&mut rng)).collect();
+                        let mixing = (chi_squared.sample(&mut rng) / degrees_of_freedom).sqrt();
+                        (0..n_assets)
+                            .map(|i| {
+                                let correlated = (0..=i).map(|k| cholesky_factor[i][k] * shocks[k]).sum::<f64>();
+                                let uniform = student_t_cdf(correlated / mixing, *degrees_of_freedom);
+                                marginals[i].inverse_cdf(uniform)
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+            SamplerMode::GaussianCopula {
+                marginals,
+                periods_to_sample,
+                cholesky_factor,
+                uniforms,
+            } => {
+                let mut rng = rand::rng();
+                let n_assets = marginals.len();
+
+                (0..*periods_to_sample)
+                    .map(|_| {
+                        let shocks: Vec<f64> = uniforms
+                            .next_point(n_assets, &mut rng)
+                            .into_iter()
+                            .map(normal_inv_cdf)
+                            .collect();
+                        (0..n_assets)
+                            .map(|i| {
+                                let correlated = (0..=i).map(|k| cholesky_factor[i][k] * shocks[k]).sum::<f64>();
+                                marginals[i].inverse_cdf(normal_cdf(correlated))
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+            SamplerMode::SeriallyCorrelatedCopula {
+                marginals,
+                periods_to_sample,
+                cholesky_factor,
+                inter_iteration_correlation: rho,
+                previous_shocks,
+            } => {
+                let mut rng = rand::rng();
+                let n_assets = marginals.len();
+                let innovation_scale = (1.0 - rho * rho).sqrt();
+
+                // held for the whole draw, concurrent callers would otherwise chain off the same scenario
+                let mut previous = previous_shocks.lock().expect("previous shocks poisoned");
+                let shocks: Vec<Vec<f64>> = (0..*periods_to_sample)
+                    .map(|t| {
+                        (0..n_assets)
+                            .map(|i| {
+                                let innovation: f64 = StandardNormal.sample(&mut rng);
+                                match previous.as_ref() {
+                                    Some(prev) => rho * prev[t][i] + innovation_scale * innovation,
+                                    None => innovation, // already stationary, N(0, 1)
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect();
+
+                let scenario = shocks
+                    .iter()
+                    .map(|period| {
+                        (0..n_assets)
+                            .map(|i| {
+                                let correlated = (0..=i).map(|k| cholesky_factor[i][k] * period[k]).sum::<f64>();
+                                marginals[i].inverse_cdf(normal_cdf(correlated))
+                            })
+                            .collect()
+                    })
+                    .collect();
+                *previous = Some(shocks);
+                scenario
+            }
+            SamplerMode::CVine {
+                pair_copulas,
+                marginals,
+                periods_to_sample,
+            } => {
+                let mut rng = rand::rng();
+                (0..*periods_to_sample)
+                    .map(|_| {
+                        let independent: Vec<f64> =
+                            (0..marginals.len()).map(|_| rng.random_range(f64::EPSILON..1.0)).collect();
+                        sample_c_vine(pair_copulas, &independent)
+                            .into_iter()
+                            .zip(marginals)
+                            .map(|(u, marginal)| marginal.inverse_cdf(u))
+                            .collect()
+                    })
+                    .collect()
+            }
+            SamplerMode::VarianceGamma {
+                sigma,
+                nu,
+                theta,
+                dt,
+                n_assets,
+                periods_to_sample,
+            } => {
+                let mut rng = rand::rng();
+                // mean dt, variance nu * dt
+                let clock = Gamma::new(dt / nu, *nu).expect("nu and dt are validated in SamplerMode::variance_gamma");
+                (0..*periods_to_sample)
+                    .map(|_| {
+                        (0..*n_assets)
+                            .map(|_| {
+                                let g = clock.sample(&mut rng);
+                                let z: f64 = StandardNormal.sample(&mut rng);
+                                theta * g + sigma * g.sqrt() * z
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+            SamplerMode::Stable {
+                alpha,
+                beta,
+                scale,
+                location,
+                periods_to_sample,
+                cholesky_factor,
+            } => {
+                let mut rng = rand::rng();
+                let mut uniform = || rng.random_range(f64::EPSILON..1.0);
+                let n_assets = cholesky_factor.len();
+
+                if n_assets == 1 {
+                    // scaling a skewed α = 1 variable also shifts it
+                    let drift = if (alpha - 1.0).abs() < f64::EPSILON {
+                        beta * scale * scale.ln() / FRAC_PI_2
+                    } else {
+                        0.0
+                    };
+                    return (0..*periods_to_sample)
+                        .map(|_| vec![location + drift + scale * stable_from_uniforms(*alpha, *beta, uniform(), uniform())])
+                        .collect();
+                }
+                // sub-Gaussian: the mixing scale is S(α/2, 1, cos(πα/4)^(2/α)), and sqrt(2) scale on
+                // the normals makes every marginal S(α, 0, scale)
+                let mixing_scale = (PI * alpha / 4.0).cos().powf(2.0 / alpha);
+                (0..*periods_to_sample)
+                    .map(|_| {
+                        let mixing = if *alpha == 2.0 {
+                            1.0 // the α/2 = 1 draw degenerates, this is the Gaussian limit
+                        } else {
+                            mixing_scale * stable_from_uniforms(alpha / 2.0, 1.0, uniform(), uniform())
+                        };
+                        let shocks: Vec<f64> = (0..n_assets).map(|_| normal_inv_cdf(uniform())).collect();
+                        (0..n_assets)
+                            .map(|i| {
+                                let correlated = (0..=i).map(|k| cholesky_factor[i][k] * shocks[k]).sum::<f64>();
+                                location + scale * SQRT_2 * mixing.sqrt() * correlated
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+            SamplerMode::Kde {
+                historical_returns,
+                bandwidths,
+                periods_to_sample,
+            } => {
+                let mut rng = rand::rng();
+                // ranges are fixed, work them out once per scenario rather than once per draw
+                let bounds: Vec<(f64, f64)> = historical_returns
+                    .iter()
+                    .map(|column| {
+                        column
+                            .iter()
+                            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lower, upper), r| (lower.min(*r), upper.max(*r)))
+                    })
+                    .collect();
+                (0..*periods_to_sample)
+                    .map(|_| {
+                        historical_returns
+                            .iter()
+                            .zip(bandwidths)
+                            .zip(&bounds)
+                            .map(|((column, bandwidth), (lower, upper))| {
+                                let shock: f64 = StandardNormal.sample(&mut rng);
+                                let draw = column[rng.random_range(0..column.len())] + bandwidth * shock;
+                                reflect_into(draw, *lower, *upper)
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+            SamplerMode::BlockBootstrap {
+                history,
+                block_size,
+                periods_to_sample,
+            } => {
+                let mut rng = rand::rng();
+                let mut periods = Vec::with_capacity(*periods_to_sample);
+                while periods.len() < *periods_to_sample {
+                    let source = &history[rng.random_range(0..history.len())];
+                    if source.is_empty() {
+                        continue;
+                    }
+                    let start = rng.random_range(0..=source.len().saturating_sub(*block_size));
+                    let remaining = *periods_to_sample - periods.len();
+                    periods.extend(source[start..].iter().take((*block_size).min(remaining)).cloned());
+                }
+                periods
+            }
+            SamplerMode::Hybrid {
+                historical,
+                parametric,
+                historical_weight,
+            } => {
+                if rand::rng().random_bool(*historical_weight) {
+                    historical.sample_returns()
+                } else {
+                    parametric.sample_returns()
+                }
+            }
+        }
+    }
+
+    fn dimension(&self) -> usize {
+        // width of the first non-empty period, client-supplied data is checked to be rectangular
+        fn history_width(scenarios: &[Scenario]) -> usize {
+            scenarios.iter().flatten().next().map_or(0, Vec::len)
+        }
+        match self {
+            SamplerMode::ScenarioSet(set) => history_width(&set.scenarios),
+            SamplerMode::CopulaT { marginals, .. }
+            | SamplerMode::GaussianCopula { marginals, .. }
+            | SamplerMode::SeriallyCorrelatedCopula { marginals, .. }
+            | SamplerMode::CVine { marginals, .. } => marginals.len(),
+            SamplerMode::VarianceGamma { n_assets, .. } => *n_assets,
+            SamplerMode::Stable { cholesky_factor, .. } => cholesky_factor.len(),
+            SamplerMode::Kde { historical_returns, .. } => historical_returns.len(),
+            SamplerMode::BlockBootstrap { history, .. } => history_width(history),
+            SamplerMode::Hybrid { parametric, .. } => parametric.dimension(),
+        }
+    }
+}
+
+/// Standard α-stable draw S(alpha, beta, 1, 0) from two independent uniforms in (0, 1), by
+/// Chambers-Mallows-Stuck: `u_angle` becomes V ~ U(-π/2, π/2) and `u_exp` W ~ Exp(1).
+pub fn stable_from_uniforms(alpha: f64, beta: f64, u_angle: f64, u_exp: f64) -> f64 {
+    let v = PI * (u_angle - 0.5);
+    let w = -u_exp.ln();
+    if (alpha - 1.0).abs() < f64::EPSILON {
+        let skewed = FRAC_PI_2 + beta * v;
+        return (skewed * v.tan() - beta * (FRAC_PI_2 * w * v.cos() / skewed).ln()) / FRAC_PI_2;
+    }
+    let zeta = beta * (PI * alpha / 2.0).tan();
+    let shift = zeta.atan() / alpha;
+    let factor = (1.0 + zeta * zeta).powf(1.0 / (2.0 * alpha));
+    factor * (alpha * (v + shift)).sin() / v.cos().powf(1.0 / alpha)
+        * ((v - alpha * (v + shift)).cos() / w).powf((1.0 - alpha) / alpha)
+}
+
+/// Silverman's rule of thumb, `0.9 min(std, IQR / 1.34) n^(-1/5)`. The IQR guards against heavy
+/// tails inflating the std, but is ignored when it is 0 (mostly tied data).
+pub fn silverman_bandwidth(values: &[f64]) -> f64 {
+    let (_, std_dev) = mean_and_std(values);
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+    let iqr = sorted_quantile(&sorted, 0.75) - sorted_quantile(&sorted, 0.25);
+    let spread = if iqr > 0.0 { std_dev.min(iqr / 1.34) } else { std_dev };
+    0.9 * spread * (values.len() as f64).powf(-0.2)
+}
+
+// Reflection method: mass the kernel pushes past a boundary is folded back inside, so the
+// estimate doesn't leak outside the support. A draw can be many ranges away (tiny ranges, wide
+// kernels): reflecting off both bounds is periodic with period 2 * range, so fold in closed form.
+fn reflect_into(value: f64, lower: f64, upper: f64) -> f64 {
+    let range = upper - lower;
+    if range <= 0.0 {
+        return lower;
+    }
+    let period = 2.0 * range;
+    let offset = (value - lower).rem_euclid(period);
+    lower + if offset > range { period - offset } else { offset }
+}
+
+/// Clamps every log-return to `[-limit, limit]` in place and returns the fraction that was clipped.
+pub fn winsorize_scenario(scenario: &mut Scenario, limit: f64) -> f64 {
+    let mut clipped = 0usize;
+    let mut total = 0usize;
+    for value in scenario.iter_mut().flatten() {
+        total += 1;
+        if value.abs() > limit {
+            *value = value.clamp(-limit, limit);
+            clipped += 1;
+        }
+    }
+    if total == 0 { 0.0 } else { clipped as f64 / total as f64 }
+}
+
+/// Checks a registered scenario set is usable: non-empty, at least 2 periods per scenario
+/// (needed for volatility) and the same number of assets everywhere.
+pub fn validate_scenarios(scenarios: &[Scenario]) -> Result<usize, String> {
+    let n_assets = scenarios
+        .first()
+        .and_then(|scenario| scenario.first())
+        .map(Vec::len)
+        .ok_or("Scenario set is empty")?;
+
+    for (index, scenario) in scenarios.iter().enumerate() {
+        if scenario.len() < 2 {
+            return Err(format!(
+                "Scenario {} has {} periods, at least 2 are required",
+                index,
+                scenario.len()
+            ));
+        }
+        if let Some(row) = scenario.iter().find(|row| row.len() != n_assets) {
+            return Err(format!(
+                "Scenario {} has a period with {} assets, expected {}",
+                index,
+                row.len(),
+                n_assets
+            ));
+        }
+    }
+
+    Ok(n_assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standard_normals(n_assets: usize) -> Vec<MarginalSpec> {
+        vec![MarginalSpec::Normal { mean: 0.0, std_dev: 1.0 }; n_assets]
+    }
+
+    fn identity(n: usize) -> Vec<Vec<f64>> {
+        (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect()
+    }
+
+    // P(U2 < q | U1 < q), the finite-sample counterpart of the lower tail dependence coefficient
+    fn lower_tail_frequency(scenario: &Scenario, q: f64) -> f64 {
+        let threshold = normal_inv_cdf(q);
+        let first_in_tail = scenario.iter().filter(|period| period[0] < threshold).count();
+        let both_in_tail = scenario.iter().filter(|period| period[0] < threshold && period[1] < threshold).count();
+        both_in_tail as f64 / first_in_tail as f64
+    }
+
+    #[test]
+    #[should_panic(expected = "3-asset history mixed with a 2-asset parametric model")]
+    fn hybrid_sides_must_have_the_same_assets() {
+        let history = Arc::new(vec![vec![vec![0.0; 3]; 5]]);
+        let historical = SamplerMode::block_bootstrap(history, 1, 5);
+        let parametric = SamplerMode::gaussian_copula(&identity(2), standard_normals(2), 5, false).unwrap();
+        let _ = SamplerMode::hybrid(historical, parametric, 0.5);
+    }
+
+    #[test]
+    fn marginals_must_match_the_copula() {
+        assert!(check_marginals(&standard_normals(2), 2).is_ok());
+        assert_eq!(
+            check_marginals(&standard_normals(3), 2),
+            Err("3 marginals for a 2-asset copula".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "3 marginals for a 2-asset copula")]
+    fn t_copula_rejects_extra_marginals() {
+        let _ = SamplerMode::copula_t(4.0, identity(2), standard_normals(3), 10);
+    }
+
+    #[test]
+    fn t_copula_has_lower_tail_dependence() {
+        assert!(t_copula_tail_dependence(2.0, 0.0) > 0.0);
+        assert!(t_copula_tail_dependence(2.0, 0.5) > t_copula_tail_dependence(30.0, 0.5));
+
+        // uncorrelated, so the Gaussian copula is plain independence and co-crashes at rate q
+        let q = 0.01;
+        let t = SamplerMode::copula_t(2.0, identity(2), standard_normals(2), 200_000).unwrap();
+        let gaussian = SamplerMode::gaussian_copula(&identity(2), standard_normals(2), 200_000, false).unwrap();
+        let t_frequency = lower_tail_frequency(&t.sample_returns(), q);
+        let gaussian_frequency = lower_tail_frequency(&gaussian.sample_returns(), q);
+        // λ = 2 t_3(-sqrt(3)) ≈ 0.18 for 2 degrees of freedom
+        assert!(t_frequency > 0.1, "t-copula co-crash frequency {}", t_frequency);
+        assert!(gaussian_frequency < 0.05, "Gaussian copula co-crash frequency {}", gaussian_frequency);
+    }
+
+    #[test]
+    fn variance_gamma_matches_its_moments() {
+        let (sigma, nu, theta, dt) = (0.2, 0.5, -0.1, 1.0);
+        let sampler = SamplerMode::variance_gamma(sigma, nu, theta, dt, 1, 200_000);
+        let draws: Vec<f64> = sampler.sample_returns().into_iter().map(|period| period[0]).collect();
+        let (mean, std_dev) = mean_and_std(&draws);
+        // E[X] = θ dt, Var[X] = (σ² + θ² ν) dt
+        assert!((mean - theta * dt).abs() < 3e-3, "mean {}", mean);
+        let variance = (sigma * sigma + theta * theta * nu) * dt;
+        assert!((std_dev * std_dev - variance).abs() < 2e-3, "variance {} vs {}", std_dev * std_dev, variance);
+    }
+
+    #[test]
+    fn stable_with_alpha_2_is_gaussian() {
+        // Chambers-Mallows-Stuck at α = 2 is Box-Muller with variance 2
+        for (u_angle, u_exp) in [(0.1, 0.3), (0.5, 0.5), (0.75, 0.9), (0.99, 0.01)] {
+            let expected = 2.0 * (PI * (u_angle - 0.5)).sin() * (-f64::ln(u_exp)).sqrt();
+            assert!((stable_from_uniforms(2.0, 0.0, u_angle, u_exp) - expected).abs() < 1e-12);
+        }
+
+        let (scale, location) = (0.1, 0.01);
+        let sampler = SamplerMode::stable(2.0, 0.0, scale, location, &identity(1), 200_000).unwrap();
+        let draws: Vec<f64> = sampler.sample_returns().into_iter().map(|period| period[0]).collect();
+        let (mean, std_dev) = mean_and_std(&draws);
+        assert!((mean - location).abs() < 2e-3, "mean {}", mean);
+        assert!((std_dev - SQRT_2 * scale).abs() < 2e-3, "std dev {}", std_dev);
+
+        // several assets go through the sub-Gaussian path, which must land on the same normal
+        let correlation = vec![vec![1.0, 0.5], vec![0.5, 1.0]];
+        let sampler = SamplerMode::stable(2.0, 0.0, scale, location, &correlation, 200_000).unwrap();
+        let scenario = sampler.sample_returns();
+        let first: Vec<f64> = scenario.iter().map(|period| period[0]).collect();
+        let second: Vec<f64> = scenario.iter().map(|period| period[1]).collect();
+        let (mean, std_dev) = mean_and_std(&second);
+        assert!((mean - location).abs() < 2e-3, "mean {}", mean);
+        assert!((std_dev - SQRT_2 * scale).abs() < 2e-3, "std dev {}", std_dev);
+        let covariance = first.iter().zip(&second).map(|(a, b)| (a - location) * (b - location)).sum::<f64>()
+            / first.len() as f64;
+        let correlation = covariance / (2.0 * scale * scale);
+        assert!((correlation - 0.5).abs() < 0.02, "correlation {}", correlation);
+    }
+
+    #[test]
+    #[should_panic(expected = "alpha must be in (0, 2]")]
+    fn stable_rejects_alpha_above_2() {
+        let _ = SamplerMode::stable(2.5, 0.0, 1.0, 0.0, &identity(1), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "alpha must be in (0, 2]")]
+    fn stable_rejects_non_positive_alpha() {
+        let _ = SamplerMode::stable(0.0, 0.0, 1.0, 0.0, &identity(1), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "beta must be in [-1, 1]")]
+    fn stable_rejects_beta_out_of_range() {
+        let _ = SamplerMode::stable(1.5, 1.5, 1.0, 0.0, &identity(1), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "beta must be 0")]
+    fn stable_rejects_skew_with_several_assets() {
+        let _ = SamplerMode::stable(1.5, 0.5, 1.0, 0.0, &identity(2), 10);
+    }
+
+    #[test]
+    fn silverman_bandwidth_takes_the_smaller_spread() {
+        let n_factor = 9f64.powf(-0.2);
+        // 1..=9: std sqrt(7.5) is below IQR / 1.34 = 4 / 1.34
+        let evenly_spread: Vec<f64> = (1..=9).map(f64::from).collect();
+        let expected = 0.9 * 7.5f64.sqrt() * n_factor;
+        assert!((silverman_bandwidth(&evenly_spread) - expected).abs() < 1e-12);
+
+        // an outlier inflates the std, the IQR (still 4) takes over
+        let heavy_tailed = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 100.0];
+        let expected = 0.9 * (4.0 / 1.34) * n_factor;
+        assert!((silverman_bandwidth(&heavy_tailed) - expected).abs() < 1e-12);
+
+        // mostly tied data has no IQR, the std is used instead
+        let tied = [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 9.0];
+        let (_, std_dev) = mean_and_std(&tied);
+        assert!((silverman_bandwidth(&tied) - 0.9 * std_dev * n_factor).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reflection_folds_back_into_bounds() {
+        assert_eq!(reflect_into(0.4, 0.0, 1.0), 0.4);
+        assert!((reflect_into(1.2, 0.0, 1.0) - 0.8).abs() < 1e-12);
+        assert!((reflect_into(-0.3, 0.0, 1.0) - 0.3).abs() < 1e-12);
+        // more than a whole range away: reflected off both bounds
+        assert!((reflect_into(2.5, 0.0, 1.0) - 0.5).abs() < 1e-12);
+        assert_eq!(reflect_into(3.0, 2.0, 2.0), 2.0);
+        // far away is as cheap as close by, and still lands inside
+        let folded = reflect_into(1e20, -0.05, 0.05);
+        assert!((-0.05..=0.05).contains(&folded));
+        assert!((reflect_into(-7.25, 0.0, 1.0) - 0.75).abs() < 1e-12);
+    }
+
+    #[test]
+    fn kde_draws_stay_within_the_historical_range() {
+        let history: Vec<Vec<f64>> = [-0.05, -0.01, 0.0, 0.02, 0.03].iter().map(|r| vec![*r]).collect();
+        // a bandwidth as wide as the range sends many draws past the bounds before reflection
+        let sampler = SamplerMode::kde(&history, Some(0.08), 10_000);
+        assert!(sampler.sample_returns().iter().flatten().all(|r| (-0.05..=0.03).contains(r)));
+    }
+}
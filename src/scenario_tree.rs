@@ -0,0 +1,123 @@
+// Scenario trees for multi-stage stochastic programming: instead of independent paths, every
+// node branches into `branching_factor` equally likely one-period outcomes, so decisions at
+// stage t can condition on everything observed up to t.
+use crate::error::SimulationError;
+use crate::sampling::ScenarioSampler;
+
+// A sampler producing periods never needs a second try, this only stops one that never does
+const MAX_EMPTY_SCENARIOS: u32 = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioNode {
+    pub period: usize,        // 0 for the root, which carries no returns
+    pub returns: Vec<f64>,    // one log-return per asset for this period
+    pub probability: f64,     // unconditional probability of reaching this node
+    pub children: Vec<usize>, // indices into ScenarioTree::nodes
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioTree {
+    pub nodes: Vec<ScenarioNode>,
+    pub root: usize,
+}
+
+impl ScenarioTree {
+    pub fn leaves(&self) -> impl Iterator<Item = &ScenarioNode> {
+        self.nodes.iter().filter(|node| node.children.is_empty())
+    }
+}
+
+/// Number of nodes `generate_scenario_tree` would create, or None on overflow.
+pub fn scenario_tree_size(n_stages: usize, branching_factor: usize) -> Option<usize> {
+    (0..=n_stages).try_fold(0usize, |total, stage| {
+        total.checked_add(branching_factor.checked_pow(stage as u32)?)
+    })
+}
+
+/// Fails if the sampler does, or if it keeps returning scenarios without any period.
+pub fn generate_scenario_tree<S: ScenarioSampler + ?Sized>(
+    n_stages: usize,
+    branching_factor: usize,
+    sampler: &S,
+) -> Result<ScenarioTree, SimulationError> {
+    if branching_factor == 0 {
+        panic!("Configuration Error: branching_factor must be at least 1.");
+    }
+
+    let mut nodes = vec![ScenarioNode {
+        period: 0,
+        returns: Vec::new(),
+        probability: 1.0,
+        children: Vec::new(),
+    }];
+    // Rows left over from the last sampled scenario, handed out one per child
+    let mut pending_rows: Vec<Vec<f64>> = Vec::new();
+    let mut frontier = vec![0];
+
+    for stage in 1..=n_stages {
+        let mut next_frontier = Vec::with_capacity(frontier.len() * branching_factor);
+        for parent in frontier {
+            let probability = nodes[parent].probability / branching_factor as f64;
+            for _ in 0..branching_factor {
+                let mut empty_scenarios = 0;
+                while pending_rows.is_empty() {
+                    if empty_scenarios == MAX_EMPTY_SCENARIOS {
+                        return Err(SimulationError::EmptyScenarios(empty_scenarios));
+                    }
+                    pending_rows = sampler.try_sample_returns()?;
+                    empty_scenarios += 1;
+                }
+                let returns = pending_rows.pop().expect("refilled above");
+                let child = nodes.len();
+                nodes.push(ScenarioNode {
+                    period: stage,
+                    returns,
+                    probability,
+                    children: Vec::new(),
+                });
+                nodes[parent].children.push(child);
+                next_frontier.push(child);
+            }
+        }
+        frontier = next_frontier;
+    }
+
+    Ok(ScenarioTree { nodes, root: 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::Scenario;
+
+    struct FixedSampler(Scenario);
+
+    impl ScenarioSampler for FixedSampler {
+        fn sample_returns(&self) -> Scenario {
+            self.0.clone()
+        }
+
+        fn dimension(&self) -> usize {
+            self.0.first().map_or(0, Vec::len)
+        }
+    }
+
+    #[test]
+    fn tree_has_one_node_per_branch() {
+        let sampler = FixedSampler(vec![vec![0.01, -0.02]; 3]);
+        let tree = generate_scenario_tree(3, 2, &sampler).unwrap();
+        assert_eq!(tree.nodes.len(), scenario_tree_size(3, 2).unwrap());
+        let leaves: Vec<&ScenarioNode> = tree.leaves().collect();
+        assert_eq!(leaves.len(), 8);
+        assert!((leaves.iter().map(|leaf| leaf.probability).sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn empty_scenarios_are_an_error_not_a_hang() {
+        let sampler = FixedSampler(Vec::new());
+        assert_eq!(
+            generate_scenario_tree(2, 2, &sampler),
+            Err(SimulationError::EmptyScenarios(MAX_EMPTY_SCENARIOS))
+        );
+    }
+}
@@ -0,0 +1,114 @@
+// Priority scheduling between concurrent batches.
+//
+// Every iteration of a batch already saturates the rayon pool, so running two batches
+// side by side only makes both slower. Instead, every active batch holds a ticket in a
+// priority queue and only the ticket at the top may run its next iteration. A high
+// priority request arriving mid-batch therefore preempts a low priority one at the next
+// iteration boundary, and equal priorities are served first come, first served.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Condvar, Mutex};
+
+use aegis_athena_contracts::simulation::Priority;
+
+fn rank(priority: Priority) -> u8 {
+    match priority {
+        Priority::Low => 0,
+        Priority::Normal => 1,
+        Priority::High => 2,
+        Priority::Critical => 3,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrioritizedRequest {
+    pub priority: Priority,
+    pub sequence: u64,
+}
+
+impl Ord for PrioritizedRequest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        rank(self.priority)
+            .cmp(&rank(other.priority))
+            // older requests (lower sequence) first among equals
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for PrioritizedRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    queue: BinaryHeap<PrioritizedRequest>,
+    next_sequence: u64,
+}
+
+#[derive(Default)]
+pub struct BatchScheduler {
+    state: Mutex<SchedulerState>,
+    turn_changed: Condvar,
+}
+
+impl BatchScheduler {
+    /// Registers a new batch. The ticket leaves the queue when it is dropped.
+    pub fn enqueue(self: &Arc<Self>, priority: Priority) -> SchedulerTicket {
+        let mut state = self.state.lock().expect("scheduler lock poisoned");
+        let request = PrioritizedRequest {
+            priority,
+            sequence: state.next_sequence,
+        };
+        state.next_sequence += 1;
+        state.queue.push(request);
+        drop(state);
+
+        // A new top of the queue means whoever was running has to yield
+        self.turn_changed.notify_all();
+        SchedulerTicket {
+            scheduler: Arc::clone(self),
+            request,
+        }
+    }
+
+    pub fn pending_requests(&self) -> usize {
+        self.state.lock().expect("scheduler lock poisoned").queue.len()
+    }
+
+    fn wait_for_turn(&self, request: &PrioritizedRequest) {
+        let state = self.state.lock().expect("scheduler lock poisoned");
+        let _state = self
+            .turn_changed
+            .wait_while(state, |state| state.queue.peek() != Some(request))
+            .expect("scheduler lock poisoned");
+    }
+
+    fn finish(&self, request: &PrioritizedRequest) {
+        self.state
+            .lock()
+            .expect("scheduler lock poisoned")
+            .queue
+            .retain(|queued| queued != request);
+        self.turn_changed.notify_all();
+    }
+}
+
+pub struct SchedulerTicket {
+    scheduler: Arc<BatchScheduler>,
+    request: PrioritizedRequest,
+}
+
+impl SchedulerTicket {
+    /// Blocks until this batch is the highest priority one. Call before every iteration.
+    pub fn wait_for_turn(&self) {
+        self.scheduler.wait_for_turn(&self.request);
+    }
+}
+
+impl Drop for SchedulerTicket {
+    fn drop(&mut self) {
+        self.scheduler.finish(&self.request);
+    }
+}
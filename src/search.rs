@@ -0,0 +1,117 @@
+// Random search over the weight simplex: draw candidate portfolios, evaluate them all on the
+// same Monte Carlo scenarios and keep the best mean Sharpe. Crude, but embarrassingly parallel
+// and a useful baseline for the smarter optimizers.
+use rand::Rng;
+use rand_distr::{Distribution, Exp1, StandardNormal};
+use rayon::prelude::*;
+
+use crate::performance::compute_portfolio_performance;
+use crate::sampling::ScenarioSampler;
+
+/// Uniform draw from the simplex, i.e. Dirichlet(1, ..., 1), as normalized Exp(1) draws.
+pub fn random_long_only_weights<R: Rng + ?Sized>(n_assets: usize, rng: &mut R) -> Vec<f64> {
+    let draws: Vec<f64> = (0..n_assets).map(|_| Exp1.sample(rng)).collect();
+    let total = draws.iter().sum::<f64>();
+    draws.into_iter().map(|d| d / total).collect()
+}
+
+/// Gaussian weights rescaled to sum to one, so shorts are allowed.
+pub fn random_long_short_weights<R: Rng + ?Sized>(n_assets: usize, rng: &mut R) -> Vec<f64> {
+    loop {
+        let draws: Vec<f64> = (0..n_assets).map(|_| StandardNormal.sample(rng)).collect();
+        let total = draws.iter().sum::<f64>();
+        // a near-zero sum would blow the weights up, just draw again
+        if total.abs() > 0.1 {
+            return draws.into_iter().map(|d| d / total).collect();
+        }
+    }
+}
+
+/// Mean Sharpe of each candidate over `iterations` fresh scenarios (common random numbers:
+/// every candidate sees the same scenarios).
+pub fn mean_sharpes<S: ScenarioSampler + ?Sized>(
+    candidates: &[Vec<f64>],
+    sampler: &S,
+    iterations: usize,
+    money_to_invest: f64,
+    risk_free_rate: f64,
+    time_horizon_in_days: f64,
+) -> Vec<f64> {
+    let mut sum_sharpes = vec![0.0; candidates.len()];
+    for _ in 0..iterations {
+        let scenario_returns = sampler.sample_returns();
+        let sharpes: Vec<f64> = candidates
+            .par_iter()
+            .map(|weights| {
+                compute_portfolio_performance(
+                    &scenario_returns,
+                    weights,
+                    money_to_invest,
+                    risk_free_rate,
+                    time_horizon_in_days,
+                )
+                .sharpe_ratio
+            })
+            .collect();
+        for (sum, sharpe) in sum_sharpes.iter_mut().zip(sharpes) {
+            *sum += sharpe;
+        }
+    }
+    sum_sharpes
+        .into_iter()
+        .map(|sum| sum / iterations.max(1) as f64)
+        .collect()
+}
+
+/// Best (weights, mean Sharpe) among the candidates. None if there are none.
+pub fn best_by_sharpe(candidates: Vec<Vec<f64>>, mean_sharpes: &[f64]) -> Option<(Vec<f64>, f64)> {
+    candidates
+        .into_iter()
+        .zip(mean_sharpes.iter().copied())
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::sampling::Scenario;
+
+    struct FixedSampler(Scenario);
+
+    impl ScenarioSampler for FixedSampler {
+        fn sample_returns(&self) -> Scenario {
+            self.0.clone()
+        }
+
+        fn dimension(&self) -> usize {
+            self.0.first().map_or(0, Vec::len)
+        }
+    }
+
+    #[test]
+    fn best_sharpe_never_drops_with_more_portfolios() {
+        let sampler = FixedSampler(vec![
+            vec![0.02, -0.01, 0.005],
+            vec![-0.03, 0.02, 0.01],
+            vec![0.04, 0.00, -0.02],
+            vec![0.01, -0.02, 0.015],
+            vec![-0.01, 0.03, 0.0],
+        ]);
+        // the same candidate stream each time, a larger search sees everything a smaller one did
+        let mut previous = f64::NEG_INFINITY;
+        for n_portfolios in [1, 10, 100, 1_000] {
+            let mut rng = StdRng::seed_from_u64(11);
+            let candidates: Vec<Vec<f64>> = (0..n_portfolios)
+                .map(|_| random_long_only_weights(3, &mut rng))
+                .collect();
+            let sharpes = mean_sharpes(&candidates, &sampler, 2, 1_000.0, 0.02, 365.0);
+            let (weights, best) = best_by_sharpe(candidates, &sharpes).unwrap();
+            assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            assert!(best >= previous, "{} portfolios: {} after {}", n_portfolios, best, previous);
+            previous = best;
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// Server-wide settings (as opposed to the per-request EvolutionConfig). Read from ATHENA_*
+// environment variables so deployments don't need a config file; anything unset falls back
+// to the defaults below.
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::audit::DEFAULT_AUDIT_QUEUE_CAPACITY;
+use crate::cache::DEFAULT_RESULT_CACHE_ENTRIES;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    pub listen_addr: SocketAddr,
+    pub max_request_bytes_mb: usize,
+    pub max_batch_memory_mb: usize, // per run_batch, checked before anything is allocated
+    pub audit_log_path: Option<PathBuf>, // directory for the scenario audit log, off when unset
+    pub audit_queue_capacity: usize,     // scenarios waiting to be written, more are dropped
+    pub sampler_pool_size: usize,        // 0 disables pooling, the sampler is then shared as-is
+    pub sampler_pool_timeout_ms: u64,
+    pub sampler_state_path: Option<PathBuf>, // sampler saved here on shutdown, restored on startup
+    pub prefetch_scenarios: usize,           // scenarios kept ready for plain Monte Carlo, 0 = off
+    pub checkpoint_path: Option<PathBuf>,    // directory for batch checkpoints, off when unset
+    pub result_cache_max_entries: usize,
+    pub result_cache_purge_secs: u64, // how often expired cached results are dropped
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen_addr: "0.0.0.0:50051".parse().expect("valid default address"),
+            max_request_bytes_mb: 256,
+            max_batch_memory_mb: 4096,
+            audit_log_path: None,
+            audit_queue_capacity: DEFAULT_AUDIT_QUEUE_CAPACITY,
+            sampler_pool_size: 0,
+            sampler_pool_timeout_ms: 5_000,
+            sampler_state_path: None,
+            prefetch_scenarios: 0,
+            checkpoint_path: None,
+            result_cache_max_entries: DEFAULT_RESULT_CACHE_ENTRIES,
+            result_cache_purge_secs: 60,
+        }
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(raw) => raw.parse().unwrap_or_else(|_| {
+            panic!("Configuration Error: could not parse {}={}.", name, raw)
+        }),
+        Err(_) => default,
+    }
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Self {
+        let defaults = ServerConfig::default();
+        ServerConfig {
+            listen_addr: env_or("ATHENA_LISTEN_ADDR", defaults.listen_addr),
+            max_request_bytes_mb: env_or("ATHENA_MAX_REQUEST_BYTES_MB", defaults.max_request_bytes_mb),
+            max_batch_memory_mb: env_or("ATHENA_MAX_BATCH_MEMORY_MB", defaults.max_batch_memory_mb),
+            audit_log_path: env::var_os("ATHENA_AUDIT_LOG_PATH").map(PathBuf::from),
+            audit_queue_capacity: env_or("ATHENA_AUDIT_QUEUE_CAPACITY", defaults.audit_queue_capacity),
+            sampler_pool_size: env_or("ATHENA_SAMPLER_POOL_SIZE", defaults.sampler_pool_size),
+            sampler_pool_timeout_ms: env_or("ATHENA_SAMPLER_POOL_TIMEOUT_MS", defaults.sampler_pool_timeout_ms),
+            sampler_state_path: env::var_os("ATHENA_SAMPLER_STATE_PATH").map(PathBuf::from),
+            prefetch_scenarios: env_or("ATHENA_PREFETCH_SCENARIOS", defaults.prefetch_scenarios),
+            checkpoint_path: env::var_os("ATHENA_CHECKPOINT_PATH").map(PathBuf::from),
+            result_cache_max_entries: env_or("ATHENA_RESULT_CACHE_MAX_ENTRIES", defaults.result_cache_max_entries),
+            result_cache_purge_secs: env_or("ATHENA_RESULT_CACHE_PURGE_SECS", defaults.result_cache_purge_secs),
+        }
+    }
+
+    pub fn max_request_bytes(&self) -> usize {
+        self.max_request_bytes_mb.saturating_mul(1024 * 1024)
+    }
+
+    pub fn max_batch_memory_bytes(&self) -> usize {
+        self.max_batch_memory_mb.saturating_mul(1024 * 1024)
+    }
+}
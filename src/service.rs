@@ -1,84 +1,2031 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use aegis_athena_contracts::sampling::Sampler;
+use aegis_athena_contracts::simulation::admin_service_server::AdminService;
+use aegis_athena_contracts::simulation::simulation_service_server::{SimulationService, SimulationServiceServer};
+use aegis_athena_contracts::simulation::{
+    BacktestVaRRequest, BacktestVaRResponse, CVineParams, CardinalityConstrainedRequest,
+    CardinalityConstrainedResponse, CorrelationValues, CreditSimulateRequest, CreditSimulateResponse,
+    CustomMetricValues, DistributionParams, DrainRequest, DrainResponse, EvolutionConfig, GenerateScenarioTreeRequest,
+    GenerateScenarioTreeResponse, GenericResponse, GetBatchProgressRequest, GetHealthRequest, GetHealthResponse,
+    MarketNeutralizeRequest, MarketNeutralizeResponse, MichaudResampleRequest, MichaudResampleResponse, MinCVaRRequest,
+    MinCVaRResponse, MinimizeTrackingErrorRequest, MinimizeTrackingErrorResponse, PairCopulaFamily, PerformanceDiff,
+    Portfolio, PortfolioMetrics, Priority, Progress, RegisterRequest, RegisterResponse, RegisterScenariosRequest,
+    RegisterScenariosResponse, ReturnSensitivityRequest, ReturnSensitivityResponse, RunBatchCompareRequest,
+    RunBatchCompareResponse, RunSearchRequest, RunSearchResponse, RuntimeEstimate, ScenarioTreeNode,
+    SimulateDrawdownRecoveryRequest, SimulateDrawdownRecoveryResponse, SimulationBatchRequest, SimulationBatchResult,
+    SimulationMode, SimulationScenario, StableParams, WhatIfRequest, WhatIfResponse,
+};
+use dashmap::DashMap;
+use rand::Rng;
 use rayon::prelude::*;
+use tonic::metadata::MetadataValue;
 use tonic::{Request, Response, Status};
-use aegis_athena_contracts::simulation::simulation_service_server::{SimulationService, SimulationServiceServer};
-use aegis_athena_contracts::simulation::{SimulationBatchRequest, SimulationBatchResult, SimulationScenario, EvolutionConfig, Portfolio};
-use aegis_athena_contracts::common_portfolio_evolution_ds::compute_portfolio_performance; 
-use aegis_athena_contracts::sampling::Sampler;                        
+use tracing::{debug, warn};
+
+use crate::analytics::{christoffersen_independence_test, kupiec_pof_test};
+use crate::audit::{AuditLog, current_hour};
+use crate::cache::{ResultCache, request_hash};
+use crate::checkpoint::{BatchCheckpoint, checkpoint_file, load_checkpoint, save_checkpoint};
+use crate::config::{ConfigError, ValidateConfig, describe_errors};
+use crate::credit::{CreditPortfolio, simulate_credit_loss};
+use crate::dca::apply_dca;
+use crate::drain::ConnectionDrain;
+use crate::drawdown::simulate_drawdown_recovery;
+use crate::encoding::{decode_portfolios, encode_portfolios_framed};
+use crate::error::SimulationError;
+use crate::expression::Expr;
+use crate::fixed_income::apply_rate_sensitivity;
+use crate::health::{LatencyTracker, ServiceHealth};
+use crate::hedging::apply_hedge;
+use crate::insurance::apply_protective_put;
+use crate::optimizer::{
+    cardinality_constrained_optimize, enforce_min_position_size, make_market_neutral, michaud_resample,
+    minimize_tracking_error, optimize_min_cvar, return_sensitivity_analysis, tracking_error_variance,
+};
+use crate::performance::{PortfolioPerformance, compute_portfolio_performance_with_rebalancing, valid_return_counts};
+use crate::precision::RoundMetrics;
+use crate::prefetch::{PrefetchedSampler, ScenarioPrefetch};
+use crate::progress::ProgressRegistry;
+use crate::rebalancing::RebalancingStrategy;
+use crate::response_format::{ResponseFormat, encode_batch_result};
+use crate::runtime_model::RuntimeModel;
+use crate::sampler_pool::SamplerPool;
+use crate::sampling::{
+    MAX_KDE_BANDWIDTH_RANGES, MarginalSpec, SamplerMode, Scenario, ScenarioSampler, ScenarioSelection, ScenarioSet,
+    check_marginals, validate_scenarios, winsorize_scenario,
+};
+use crate::scenario_tree::{generate_scenario_tree, scenario_tree_size};
+use crate::scheduler::BatchScheduler;
+use crate::search::{best_by_sharpe, mean_sharpes, random_long_only_weights, random_long_short_weights};
+use crate::stability::{DEFAULT_STABILITY_NOISE_SIGMA, DEFAULT_STABILITY_PERTURBATIONS, compute_stability_score};
+use crate::stats::{DEFAULT_COVARIANCE_DDOF, RunningCorrelation};
+use crate::stress::apply_correlation_stress;
+use crate::vine::{PairCopulaSpec, PairCopulaType, validate_c_vine};
+use crate::whatif::shifted_weights;
+
+// Trees grow as branching_factor^n_stages, refuse the ones that would eat the server
+const MAX_SCENARIO_TREE_NODES: usize = 1_000_000;
+
+const DEFAULT_MIN_VALID_PERIODS: u32 = 20;
+
+// Paths per SimulateDrawdownRecovery call, enough for a stable median
+const DRAWDOWN_RECOVERY_PATHS: usize = 10_000;
+
+const CACHE_TTL_HEADER: &str = "x-cache-ttl-seconds";
+const FROM_CACHE_HEADER: &str = "x-from-cache";
+const SCENARIO_LOG_FILE_HEADER: &str = "x-scenario-log-file";
+const RESPONSE_FORMAT_HEADER: &str = "x-response-format";
+
+// Rough measured throughput, in portfolio evaluations per second, by batch size.
+// Only used to warn clients whose timeout can't realistically be met.
+const THROUGHPUT_TABLE: &[(usize, f64)] = &[
+    (10, 50_000.0),
+    (100, 200_000.0),
+    (1_000, 600_000.0),
+    (10_000, 1_000_000.0),
+];
+
+// Peak memory of one batch: the accumulators, one PortfolioPerformance (with its per-period
+// returns) per portfolio for the iteration in flight, and the current + last scenario.
+fn estimated_batch_bytes(
+    n_portfolios: usize,
+    n_assets: usize,
+    periods: usize,
+    n_custom_metrics: usize,
+    portfolio_correlation: bool,
+) -> usize {
+    const F64: usize = std::mem::size_of::<f64>();
+    let accumulators = n_portfolios
+        * ((BatchAccumulators::PER_PORTFOLIO_SUMS + n_custom_metrics) * F64
+            + std::mem::size_of::<Option<String>>()
+            + std::mem::size_of::<Vec<f64>>());
+    let per_iteration = n_portfolios * (periods * F64 + std::mem::size_of::<Result<PortfolioPerformance, String>>());
+    let scenarios = 2 * periods * n_assets * F64;
+    // co-moments for the upper triangle, quadratic in the number of portfolios
+    let correlation = if portfolio_correlation {
+        n_portfolios.saturating_mul(n_portfolios + 1) / 2 * F64
+    } else {
+        0
+    };
+    accumulators
+        .saturating_add(per_iteration)
+        .saturating_add(scenarios)
+        .saturating_add(correlation)
+}
+
+// Transient sampler failures (a remote data source timing out) are retried with exponential
+// backoff, `backoff_ms * 2^attempt` before retry `attempt + 1`. Runs on the blocking pool, so
+// sleeping the thread is fine. Retries are counted in `retry_count` whether or not they succeed.
+fn sample_with_retries(
+    sampler: &dyn ScenarioSampler,
+    max_retries: u32,
+    backoff_ms: u64,
+    retry_count: &mut u32,
+) -> Result<Scenario, SimulationError> {
+    let mut attempt = 0;
+    loop {
+        match sampler.try_sample_returns() {
+            Ok(scenario) => return Ok(scenario),
+            Err(SimulationError::SamplerUnavailable(reason)) if attempt < max_retries => {
+                let delay_ms = backoff_ms.saturating_mul(1u64.checked_shl(attempt).unwrap_or(u64::MAX));
+                warn!(
+                    "sampling failed ({}), retry {} of {} in {}ms",
+                    reason,
+                    attempt + 1,
+                    max_retries,
+                    delay_ms
+                );
+                std::thread::sleep(Duration::from_millis(delay_ms));
+                attempt += 1;
+                *retry_count += 1;
+            }
+            Err(SimulationError::SamplerUnavailable(reason)) if max_retries > 0 => {
+                return Err(SimulationError::SamplerUnavailable(format!(
+                    "{} (after {} retries)",
+                    reason, max_retries
+                )));
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+fn estimated_batch_seconds(n_portfolios: usize, iterations: usize) -> f64 {
+    let throughput = THROUGHPUT_TABLE
+        .iter()
+        .find(|(size, _)| n_portfolios <= *size)
+        .or(THROUGHPUT_TABLE.last())
+        .map(|(_, per_second)| *per_second)
+        .unwrap_or(1.0);
+    (n_portfolios * iterations) as f64 / throughput
+}
+
+// Everything the blocking batch loop accumulates, handed back to the async side in one go.
+struct BatchAccumulators {
+    sum_returns: Vec<f64>,
+    sum_vols: Vec<f64>,
+    sum_sharpes: Vec<f64>,
+    last_scenario: Vec<Vec<f64>>,
+    crisis_iterations: u32,
+    portfolio_errors: Vec<Option<String>>, // first failure per portfolio, if any
+    sum_custom_metrics: Vec<Vec<f64>>,     // portfolio x expression
+    sum_hedged_sharpes: Vec<f64>,          // zeros unless a hedge is configured
+    sum_hedging_effectiveness: Vec<f64>,
+    sum_insured_sharpes: Vec<f64>, // zeros unless a protective put is configured
+    sum_insured_returns: Vec<f64>,
+    sum_insurance_costs: Vec<f64>,
+    sum_dca_terminal_wealth: Vec<f64>, // zeros unless DCA is configured
+    sum_lump_sum_terminal_wealth: Vec<f64>,
+    sum_stability_scores: Vec<f64>, // zeros unless compute_stability is set
+    sum_portfolio_dv01s: Vec<f64>,  // zeros for portfolios without fixed_income_assets
+    sum_portfolio_convexities: Vec<f64>,
+    sampling_retry_count: u32,
+    portfolio_correlation: Option<RunningCorrelation>, // only with compute_portfolio_correlation
+}
+
+impl BatchAccumulators {
+    // Number of f64 sum vectors above, for estimated_batch_bytes
+    const PER_PORTFOLIO_SUMS: usize = 13;
+
+    fn new(n_portfolios: usize, n_custom_metrics: usize, portfolio_correlation: bool) -> Self {
+        BatchAccumulators {
+            sum_returns: vec![0.0; n_portfolios],
+            sum_vols: vec![0.0; n_portfolios],
+            sum_sharpes: vec![0.0; n_portfolios],
+            last_scenario: Vec::new(),
+            crisis_iterations: 0,
+            portfolio_errors: vec![None; n_portfolios],
+            sum_custom_metrics: vec![vec![0.0; n_custom_metrics]; n_portfolios],
+            sum_hedged_sharpes: vec![0.0; n_portfolios],
+            sum_hedging_effectiveness: vec![0.0; n_portfolios],
+            sum_insured_sharpes: vec![0.0; n_portfolios],
+            sum_insured_returns: vec![0.0; n_portfolios],
+            sum_insurance_costs: vec![0.0; n_portfolios],
+            sum_dca_terminal_wealth: vec![0.0; n_portfolios],
+            sum_lump_sum_terminal_wealth: vec![0.0; n_portfolios],
+            sum_stability_scores: vec![0.0; n_portfolios],
+            sum_portfolio_dv01s: vec![0.0; n_portfolios],
+            sum_portfolio_convexities: vec![0.0; n_portfolios],
+            sampling_retry_count: 0,
+            portfolio_correlation: portfolio_correlation.then(|| RunningCorrelation::new(n_portfolios)),
+        }
+    }
+}
+
+// Plain Monte Carlo on the server's own sampler, see resolve_sampler
+fn uses_default_sampler(req: &SimulationBatchRequest) -> bool {
+    req.config.simulation_mode == SimulationMode::MonteCarlo as i32
+        && req.scenario_set_id.is_empty()
+        && req.config.distribution_params.is_none()
+        && req.config.variance_gamma.is_none()
+        && req.config.stable.is_none()
+        && req.config.kde.is_none()
+}
+
+// Fields that decide how scenarios are drawn. A compare draws them once from config_a's sampler,
+// a config_b that differs here would be silently ignored.
+fn sampling_fields_differing(a: &EvolutionConfig, b: &EvolutionConfig) -> Vec<&'static str> {
+    [
+        ("simulation_mode", a.simulation_mode == b.simulation_mode),
+        ("distribution_params", a.distribution_params == b.distribution_params),
+        ("periods_to_sample", a.periods_to_sample == b.periods_to_sample),
+        (
+            "inter_iteration_correlation",
+            a.inter_iteration_correlation == b.inter_iteration_correlation,
+        ),
+        ("bootstrap_block_size", a.bootstrap_block_size == b.bootstrap_block_size),
+        ("historical_weight", a.historical_weight == b.historical_weight),
+        ("variance_gamma", a.variance_gamma == b.variance_gamma),
+        ("stable", a.stable == b.stable),
+        ("kde", a.kde == b.kde),
+    ]
+    .into_iter()
+    .filter(|(_, same)| !same)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+// Crisis regimes for a compare, decided up front: iteration i is a crisis when crisis_draws[i] falls
+// below crisis_probability (what random_bool does), so both configs share the same draws.
+fn stress_scenarios(
+    scenarios: &[Scenario],
+    crisis_draws: &[f64],
+    crisis_probability: f64,
+    crisis_correlation: f64,
+) -> (Vec<Scenario>, u32) {
+    let mut crisis_iterations = 0;
+    let stressed = scenarios
+        .iter()
+        .zip(crisis_draws)
+        .map(|(scenario, &draw)| {
+            if draw < crisis_probability.clamp(0.0, 1.0) {
+                crisis_iterations += 1;
+                apply_correlation_stress(scenario, crisis_correlation)
+            } else {
+                scenario.clone()
+            }
+        })
+        .collect();
+    (stressed, crisis_iterations)
+}
+
+// Matrices travel as flattened row-major repeated doubles
+fn check_square(name: &str, values: &[f64], n: usize) -> Result<(), Status> {
+    if values.len() != n * n {
+        return Err(Status::invalid_argument(format!(
+            "{} must be a flattened {}x{} matrix (found {} values)",
+            name,
+            n,
+            n,
+            values.len()
+        )));
+    }
+    Ok(())
+}
+
+fn unflatten_square(values: &[f64], n: usize) -> Vec<Vec<f64>> {
+    values.chunks(n.max(1)).map(<[f64]>::to_vec).collect()
+}
+
+fn pair_copula_type(family: i32) -> Result<PairCopulaType, Status> {
+    match PairCopulaFamily::try_from(family) {
+        Ok(PairCopulaFamily::Gaussian) => Ok(PairCopulaType::Gaussian),
+        Ok(PairCopulaFamily::Clayton) => Ok(PairCopulaType::Clayton),
+        Ok(PairCopulaFamily::Gumbel) => Ok(PairCopulaType::Gumbel),
+        Ok(PairCopulaFamily::Frank) => Ok(PairCopulaType::Frank),
+        Err(_) => Err(Status::invalid_argument(format!(
+            "Unknown pair copula family {}",
+            family
+        ))),
+    }
+}
+
+// Trees as sent, validated against the marginals (one per asset)
+fn c_vine_pair_copulas(c_vine: &CVineParams, n_assets: usize) -> Result<Vec<Vec<PairCopulaSpec>>, Status> {
+    let pair_copulas = c_vine
+        .trees
+        .iter()
+        .map(|tree| {
+            tree.pair_copulas
+                .iter()
+                .map(|pair| {
+                    Ok(PairCopulaSpec {
+                        copula_type: pair_copula_type(pair.family)?,
+                        parameter: pair.parameter,
+                    })
+                })
+                .collect::<Result<Vec<_>, Status>>()
+        })
+        .collect::<Result<Vec<_>, Status>>()?;
+    let dimension = validate_c_vine(&pair_copulas)
+        .map_err(|e| Status::invalid_argument(format!("Invalid distribution_params.c_vine: {}", e)))?;
+    if dimension != n_assets {
+        return Err(Status::invalid_argument(format!(
+            "distribution_params.c_vine has {} trees, a {}-asset vine needs {}",
+            c_vine.trees.len(),
+            n_assets,
+            n_assets.saturating_sub(1)
+        )));
+    }
+    Ok(pair_copulas)
+}
+
+// Per-asset means / std devs and a flattened correlation matrix. With degrees_of_freedom set the
+// marginals are Student-t (std_devs are then used as the scale), normal otherwise. With an
+// inter_iteration_correlation successive scenarios are chained (pseudorandom uniforms only).
+// A c_vine replaces the correlation matrix as the dependence structure.
+fn copula_sampler(
+    params: &DistributionParams,
+    periods_to_sample: usize,
+    quasi_random: bool,
+    inter_iteration_correlation: Option<f64>,
+) -> Result<SamplerMode, Status> {
+    let n_assets = params.means.len();
+    if params.std_devs.len() != n_assets {
+        return Err(Status::invalid_argument(format!(
+            "distribution_params has {} means but {} std_devs",
+            n_assets,
+            params.std_devs.len()
+        )));
+    }
+    if params.c_vine.is_none() {
+        check_square(
+            "distribution_params.correlation_matrix",
+            &params.correlation_matrix,
+            n_assets,
+        )?;
+    }
+    if let Some(nu) = params.degrees_of_freedom {
+        if nu.is_nan() || nu <= 0.0 {
+            return Err(Status::invalid_argument(format!(
+                "distribution_params.degrees_of_freedom must be > 0 (found {})",
+                nu
+            )));
+        }
+    }
+    if let Some(nu) = params.copula_degrees_of_freedom {
+        if nu.is_nan() || nu <= 0.0 {
+            return Err(Status::invalid_argument(format!(
+                "distribution_params.copula_degrees_of_freedom must be > 0 (found {})",
+                nu
+            )));
+        }
+        // the t-copula draws its own pseudorandom shocks, one chi-squared per period
+        if quasi_random || inter_iteration_correlation.is_some() {
+            return Err(Status::invalid_argument(
+                "distribution_params.copula_degrees_of_freedom requires simulation_mode MONTE_CARLO without inter_iteration_correlation",
+            ));
+        }
+    }
+
+    let marginals = params
+        .means
+        .iter()
+        .zip(&params.std_devs)
+        .map(|(mean, std_dev)| match params.degrees_of_freedom {
+            Some(degrees_of_freedom) => MarginalSpec::StudentT {
+                location: *mean,
+                scale: *std_dev,
+                degrees_of_freedom,
+            },
+            None => MarginalSpec::Normal {
+                mean: *mean,
+                std_dev: *std_dev,
+            },
+        })
+        .collect::<Vec<_>>();
+    check_marginals(&marginals, n_assets)
+        .map_err(|e| Status::invalid_argument(format!("Invalid distribution_params: {}", e)))?;
+    if let Some(c_vine) = &params.c_vine {
+        // the vine draws its own pseudorandom uniforms and has no correlation matrix to t-mix
+        if quasi_random || inter_iteration_correlation.is_some() || params.copula_degrees_of_freedom.is_some() {
+            return Err(Status::invalid_argument(
+                "distribution_params.c_vine requires simulation_mode MONTE_CARLO, without inter_iteration_correlation or copula_degrees_of_freedom",
+            ));
+        }
+        let pair_copulas = c_vine_pair_copulas(c_vine, n_assets)?;
+        return Ok(SamplerMode::c_vine(pair_copulas, marginals, periods_to_sample));
+    }
+    let correlation = unflatten_square(&params.correlation_matrix, n_assets);
+    match (params.copula_degrees_of_freedom, inter_iteration_correlation) {
+        (Some(nu), _) => SamplerMode::copula_t(nu, correlation, marginals, periods_to_sample),
+        (None, Some(rho)) => SamplerMode::serially_correlated_copula(&correlation, marginals, periods_to_sample, rho),
+        (None, None) => SamplerMode::gaussian_copula(&correlation, marginals, periods_to_sample, quasi_random),
+    }
+    .map_err(|e| Status::invalid_argument(format!("Invalid distribution_params.correlation_matrix: {}", e)))
+}
+
+// One asset per row of the (flattened) correlation matrix, a single asset sends [1.0]
+fn stable_sampler(params: &StableParams, periods_to_sample: usize) -> Result<SamplerMode, Status> {
+    let n_assets = (params.correlation_matrix.len() as f64).sqrt().round() as usize;
+    if n_assets == 0 {
+        return Err(Status::invalid_argument("stable.correlation_matrix must not be empty"));
+    }
+    check_square("stable.correlation_matrix", &params.correlation_matrix, n_assets)?;
+    if n_assets > 1 && params.beta != 0.0 {
+        return Err(Status::invalid_argument(format!(
+            "stable.beta must be 0 for more than one asset, the sub-Gaussian vector is symmetric (found {})",
+            params.beta
+        )));
+    }
+    SamplerMode::stable(
+        params.alpha,
+        params.beta,
+        params.scale,
+        params.location,
+        &unflatten_square(&params.correlation_matrix, n_assets),
+        periods_to_sample,
+    )
+    .map_err(|e| Status::invalid_argument(format!("Invalid stable.correlation_matrix: {}", e)))
+}
+
+// Every period of every registered scenario is one observation
+fn kde_sampler(history: &[Scenario], bandwidth: Option<f64>, periods_to_sample: usize) -> Result<SamplerMode, Status> {
+    let observations: Vec<Vec<f64>> = history.iter().flatten().cloned().collect();
+    let n_assets = observations.first().map_or(0, Vec::len);
+    if let Some(asset) = (0..n_assets).find(|&asset| observations.iter().all(|row| row[asset].is_nan())) {
+        return Err(Status::invalid_argument(format!(
+            "Asset {} has no historical returns in the scenario set to estimate a density from",
+            asset
+        )));
+    }
+    if let Some(bandwidth) = bandwidth {
+        for asset in 0..n_assets {
+            let valid = observations
+                .iter()
+                .map(|row| row[asset])
+                .filter(|log_return| !log_return.is_nan());
+            let (lowest, highest) = valid.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), value| {
+                (lo.min(value), hi.max(value))
+            });
+            let range = highest - lowest;
+            if range > 0.0 && bandwidth > MAX_KDE_BANDWIDTH_RANGES * range {
+                return Err(Status::invalid_argument(format!(
+                    "kde.bandwidth {} is more than {} times the historical range of asset {} ({})",
+                    bandwidth, MAX_KDE_BANDWIDTH_RANGES, asset, range
+                )));
+            }
+        }
+    }
+    // SamplerMode::kde warns about the lost cross-asset correlation
+    Ok(SamplerMode::kde(&observations, bandwidth, periods_to_sample))
+}
+
+// EvolutionConfig.min_position_size for the optimizer RPCs, which take it on the request
+fn check_min_position_size(min_position_size: Option<f64>) -> Result<(), Status> {
+    match min_position_size {
+        Some(size) if size.is_nan() || size <= 0.0 || size >= 1.0 => Err(Status::invalid_argument(
+            ConfigError::InvalidMinPositionSize(size).to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn apply_min_position_size(weights: &mut [f64], min_position_size: Option<f64>) -> u32 {
+    min_position_size.map_or(0, |min_position_size| {
+        enforce_min_position_size(weights, min_position_size)
+    })
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|msg| msg.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "portfolio evaluation panicked".to_string())
+}
 
 #[derive(Clone)]
 pub struct SimulationServiceImpl {
-    pub sampler: Sampler,
+    pub sampler: Arc<Sampler>, // shared, so the state persisted on shutdown is the live one
+    // Portfolios registered once via register_portfolios and referenced by id afterwards
+    pub portfolio_sets: Arc<DashMap<String, Arc<Vec<Portfolio>>>>,
+    // Client-supplied scenario matrices, registered via register_scenarios
+    pub scenario_sets: Arc<DashMap<String, Arc<Vec<Scenario>>>>,
+    pub scheduler: Arc<BatchScheduler>,
+    pub result_cache: Arc<ResultCache<SimulationBatchResult>>,
+    pub latencies: Arc<LatencyTracker>,
+    pub runtime_model: Arc<RuntimeModel>,
+    pub max_batch_memory_bytes: usize,
+    pub audit_log: Option<Arc<AuditLog>>,
+    pub progress: Arc<ProgressRegistry>,
+    pub sampler_pool: Option<Arc<SamplerPool>>, // used instead of `sampler` for plain Monte Carlo when set
+    pub prefetch: Option<Arc<ScenarioPrefetch>>, // pre-generated scenarios from `sampler`
+    pub drain: Arc<ConnectionDrain>,
+    pub checkpoint_dir: Option<PathBuf>, // batches with checkpoint_every_n write here
 }
 
-#[tonic::async_trait]
-impl simulation::simulation_service_server::SimulationService for SimulationServiceImpl {
-    async fn run_batch(
-        &self,
-        request: Request<SimulationBatchRequest>,
-    ) -> Result<Response<SimulationBatchResult>, Status> {
-        // Extract the batch request
-        let req = request.into_inner();
-        
-        // Deserialize the portfolios blob using bincode.
-        // The blob is assumed to be a bincode serialized Vec<Portfolio>.
-        let portfolios: Vec<Portfolio> = bincode::deserialize(&req.portfolios_blob)
-            .map_err(|e| Status::invalid_argument(format!("Failed to deserialize portfolios: {}", e)))?;
+impl SimulationServiceImpl {
+    pub fn new(sampler: Sampler) -> Self {
+        SimulationServiceImpl {
+            sampler: Arc::new(sampler),
+            portfolio_sets: Arc::new(DashMap::new()),
+            scenario_sets: Arc::new(DashMap::new()),
+            scheduler: Arc::new(BatchScheduler::default()),
+            result_cache: Arc::new(ResultCache::default()),
+            latencies: Arc::new(LatencyTracker::default()),
+            runtime_model: Arc::new(RuntimeModel::default()),
+            max_batch_memory_bytes: usize::MAX,
+            audit_log: None,
+            progress: Arc::new(ProgressRegistry::default()),
+            sampler_pool: None,
+            prefetch: None,
+            drain: Arc::new(ConnectionDrain::default()),
+            checkpoint_dir: None,
+        }
+    }
+
+    /// Keeps up to `capacity` scenarios from the default sampler ready. Nothing is generated
+    /// until `prefetch_scenarios` is called.
+    pub fn with_scenario_prefetch(mut self, capacity: usize) -> Self {
+        self.prefetch = Some(Arc::new(ScenarioPrefetch::new(Arc::clone(&self.sampler), capacity)));
+        self
+    }
+
+    /// Tops the prefetch buffer up by (at most) `n` scenarios in the background.
+    pub fn prefetch_scenarios(&self, n: usize) {
+        if let Some(prefetch) = &self.prefetch {
+            prefetch.refill(n);
+        }
+    }
+
+    pub fn with_sampler_pool(mut self, max_size: usize, acquire_timeout: Duration) -> Self {
+        self.sampler_pool = Some(Arc::new(SamplerPool::new(
+            (*self.sampler).clone(),
+            max_size,
+            acquire_timeout,
+        )));
+        self
+    }
+
+    /// Caps the results kept for the x-cache-ttl-seconds header, the oldest is evicted first.
+    pub fn with_result_cache_capacity(mut self, max_entries: usize) -> Self {
+        self.result_cache = Arc::new(ResultCache::new(max_entries));
+        self
+    }
+
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(Arc::new(audit_log));
+        self
+    }
+
+    pub fn with_checkpoint_dir(mut self, checkpoint_dir: PathBuf) -> Self {
+        self.checkpoint_dir = Some(checkpoint_dir);
+        self
+    }
+
+    pub fn with_max_batch_memory_bytes(mut self, max_batch_memory_bytes: usize) -> Self {
+        self.max_batch_memory_bytes = max_batch_memory_bytes;
+        self
+    }
+
+    /// Replaces the default runtime model with one measured on this machine. Takes a moment,
+    /// call it once at startup before serving.
+    pub fn with_calibrated_runtime_model(mut self) -> Self {
+        self.runtime_model = Arc::new(RuntimeModel::calibrate(self.sampler.as_ref()));
+        self
+    }
+
+    pub fn health_status(&self) -> ServiceHealth {
+        ServiceHealth {
+            // rayon doesn't say how many workers are busy, only how many the global pool has
+            rayon_pool_threads: rayon::current_num_threads(),
+            pending_requests: self.scheduler.pending_requests() as u32,
+            cache_hit_rate: self.result_cache.hit_rate(),
+            p99_latency_ms: self.latencies.quantile_ms(0.99),
+        }
+    }
+
+    fn checkpoint_directory(&self) -> Result<&Path, Status> {
+        self.checkpoint_dir
+            .as_deref()
+            .ok_or_else(|| Status::failed_precondition("Checkpointing is not enabled on this server"))
+    }
+
+    async fn simulate_batch(&self, req: SimulationBatchRequest) -> Result<SimulationBatchResult, Status> {
+        // Every batch RPC comes through here, a draining server turns them all away
+        let _in_flight = self.drain.enter()?;
+
+        // Reject bad configurations before any deserialization or sampling work
+        req.config
+            .validate()
+            .map_err(|errors| Status::invalid_argument(describe_errors(&errors)))?;
+
+        let portfolios: Vec<Portfolio> = self.resolve_portfolios(&req)?;
+        // Resolve the sampler to use within the blocking task (dispatches on simulation_mode).
+        let mut sampler = self.resolve_sampler(&req).await?;
+        // Prefetched scenarios come from the default sampler, only batches that would use it get them
+        let mut prefetched = 0;
+        if let Some(prefetch) = self.prefetch.as_ref().filter(|_| uses_default_sampler(&req)) {
+            let scenarios = prefetch.take(req.iterations as usize).await;
+            prefetched = scenarios.len();
+            sampler = Arc::new(PrefetchedSampler::new(scenarios, sampler));
+        }
+        // A length mismatch would otherwise be silently truncated by the zip in the evaluation
+        let dimension = sampler.dimension();
+        if let Some((idx, p)) = portfolios
+            .iter()
+            .enumerate()
+            .find(|(_, p)| p.weights.len() != dimension)
+        {
+            return Err(Status::invalid_argument(format!(
+                "Portfolio {} has {} weights but the sampler generates returns for {} assets",
+                idx,
+                p.weights.len(),
+                dimension
+            )));
+        }
+        // e.g. hedge legs indexing assets, a bad index would panic mid-batch
+        req.config
+            .validate_for_dimension(dimension)
+            .map_err(|errors| Status::invalid_argument(describe_errors(&errors)))?;
+        for (idx, p) in portfolios.iter().enumerate() {
+            p.validate()
+                .and_then(|()| p.validate_for_dimension(dimension))
+                .map_err(|errors| {
+                    Status::invalid_argument(format!("Portfolio {}: {}", idx, describe_errors(&errors)))
+                })?;
+        }
         let config = req.config; // Using config if needed; otherwise, you can ignore it.
         let iterations = req.iterations as usize;
+        let output_precision = config.output_precision;
+        let rebalancing = match config.rebalancing_band {
+            Some(threshold) => RebalancingStrategy::BandRebalancing { threshold },
+            None => RebalancingStrategy::EveryPeriod,
+        };
+        // 0 is the proto default; complete scenarios shorter than this still pass, see the loop
+        let min_valid_periods = if config.min_valid_periods == 0 {
+            DEFAULT_MIN_VALID_PERIODS
+        } else {
+            config.min_valid_periods
+        };
 
-        // Prepare accumulators
+        // Parse client-defined metrics up front, a typo shouldn't cost a whole batch
+        let custom_metrics = config
+            .custom_metric_expressions
+            .iter()
+            .map(|source| {
+                Expr::parse(source)
+                    .map_err(|e| Status::invalid_argument(format!("Invalid custom metric '{}': {}", source, e)))
+            })
+            .collect::<Result<Vec<Expr>, Status>>()?;
+
+        // Prepare accumulators, unless they (and the per-iteration working set) won't fit
         let n = portfolios.len();
-        let mut sum_returns = vec![0.0; n];
-        let mut sum_vols = vec![0.0; n];
-        let mut sum_sharpes = vec![0.0; n];
-        let mut last_scenario = Vec::new();
+        let n_assets = portfolios.first().map_or(0, |p| p.weights.len());
+        let estimated_bytes = estimated_batch_bytes(
+            n,
+            n_assets,
+            config.periods_to_sample as usize,
+            custom_metrics.len(),
+            config.compute_portfolio_correlation,
+        );
+        if estimated_bytes > self.max_batch_memory_bytes {
+            return Err(Status::resource_exhausted(format!(
+                "Batch would need an estimated {} bytes, the server allows {}",
+                estimated_bytes, self.max_batch_memory_bytes
+            )));
+        }
+        let mut acc = BatchAccumulators::new(n, custom_metrics.len(), config.compute_portfolio_correlation);
+
+        // Parsed before the batch is queued or started: an error past that point would detach it
+        let deadline = req
+            .timeout_seconds
+            .map(|timeout_seconds| {
+                Duration::try_from_secs_f32(timeout_seconds).map_err(|e| {
+                    Status::invalid_argument(format!("Invalid timeout_seconds {}: {}", timeout_seconds, e))
+                })
+            })
+            .transpose()?;
+
+        // Unset or unknown priorities are treated as Normal
+        let priority = Priority::try_from(req.priority).unwrap_or(Priority::Normal);
+        let ticket = self.scheduler.enqueue(priority);
+
+        // Blocking tasks can't be aborted, so on timeout we ask the loop to stop instead.
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancelled_in_batch = Arc::clone(&cancelled);
+        let audit_log = self.audit_log.clone();
+
+        if req.report_progress && req.job_id.is_empty() {
+            return Err(Status::invalid_argument("report_progress requires a job_id"));
+        }
+
+        // Opt-in checkpoints, named after the job_id so the client knows what to resume from
+        let checkpoint = match config.checkpoint_every_n {
+            Some(every_n) => {
+                if req.job_id.is_empty() {
+                    return Err(Status::invalid_argument("checkpoint_every_n requires a job_id"));
+                }
+                Some((
+                    every_n as usize,
+                    checkpoint_file(self.checkpoint_directory()?, &req.job_id),
+                ))
+            }
+            None => None,
+        };
+        let start_iteration = match &req.resume_from_checkpoint {
+            Some(path) => {
+                // only checkpoints this server wrote, a client path can't point anywhere else
+                let file_name = Path::new(path)
+                    .file_name()
+                    .ok_or_else(|| Status::invalid_argument(format!("Invalid checkpoint path '{}'", path)))?;
+                let saved = load_checkpoint(&self.checkpoint_directory()?.join(file_name))
+                    .await
+                    .map_err(|e| Status::not_found(format!("Could not load checkpoint '{}': {}", path, e)))?;
+                if [&saved.sum_returns, &saved.sum_vols, &saved.sum_sharpes]
+                    .iter()
+                    .any(|sums| sums.len() != n)
+                {
+                    return Err(Status::invalid_argument(format!(
+                        "Checkpoint '{}' is for {} portfolios, this batch has {}",
+                        path,
+                        saved.sum_returns.len(),
+                        n
+                    )));
+                }
+                if saved.completed_iterations > req.iterations {
+                    return Err(Status::invalid_argument(format!(
+                        "Checkpoint '{}' has {} completed iterations, more than the {} requested",
+                        path, saved.completed_iterations, req.iterations
+                    )));
+                }
+                acc.sum_returns = saved.sum_returns;
+                acc.sum_vols = saved.sum_vols;
+                acc.sum_sharpes = saved.sum_sharpes;
+                saved.completed_iterations as usize
+            }
+            None => 0,
+        };
 
-        // Clone the sampler to use within the blocking task.
-        let sampler = self.sampler.clone();
+        // Opt-in progress, pollable by job_id while the batch runs. Registered last, the guard
+        // moves into the batch and marks the job finished whenever that ends.
+        let progress = req.report_progress.then(|| {
+            self.progress
+                .register(req.job_id.clone(), req.iterations, start_iteration as u32)
+        });
 
         // Run the batch *synchronously*, but wrap in spawn_blocking to avoid blocking the async executor.
-        let (sr, sv, ss, ls) = tokio::task::spawn_blocking(move || {
-            for i in 0..iterations {
+        let batch = tokio::task::spawn_blocking(move || {
+            let mut rng = rand::rng();
+            for i in start_iteration..iterations {
+                // yield to higher priority batches at every iteration boundary
+                ticket.wait_for_turn();
+                if cancelled_in_batch.load(Ordering::Relaxed) {
+                    break;
+                }
+
                 // sample scenario
-                let scenario_returns = sampler.sample_returns();
+                let mut scenario_returns = sample_with_retries(
+                    sampler.as_ref(),
+                    config.max_sampler_retries,
+                    config.sampler_retry_backoff_ms,
+                    &mut acc.sampling_retry_count,
+                )?;
+
+                // crisis regime: correlations jump towards crisis_correlation for this scenario
+                if let Some(stress) = &config.correlation_stress {
+                    if rng.random_bool(stress.crisis_probability.clamp(0.0, 1.0)) {
+                        scenario_returns = apply_correlation_stress(&scenario_returns, stress.crisis_correlation);
+                        acc.crisis_iterations += 1;
+                    }
+                }
+
+                // cap outliers so a single +1000% draw can't dominate the statistics
+                if let Some(limit) = config.winsorize {
+                    let clipped = winsorize_scenario(&mut scenario_returns, limit);
+                    if clipped > 0.0 {
+                        debug!(
+                            "iteration {}: winsorized {:.4}% of returns at ±{}",
+                            i,
+                            clipped * 100.0,
+                            limit
+                        );
+                    }
+                }
+
+                // assets with gaps (NaN) need enough real data for their statistics to mean anything
+                let required = min_valid_periods.min(scenario_returns.len() as u32);
+                let valid_counts = valid_return_counts(&scenario_returns);
+                if let Some((asset, &count)) = valid_counts.iter().enumerate().find(|(_, count)| **count < required) {
+                    return Err(SimulationError::InsufficientAssetData(asset, count, required));
+                }
+
+                if let Some(audit_log) = &audit_log {
+                    audit_log.record(SimulationScenario {
+                        returns: scenario_returns.clone(),
+                    });
+                }
+
                 if i == iterations - 1 {
-                    last_scenario = scenario_returns.clone();
+                    acc.last_scenario = scenario_returns.clone();
                 }
 
                 // parallel evaluation of all portfolios
-                let metrics: Vec<(f64, f64, f64)> = portfolios
+                let metrics: Vec<Result<PortfolioPerformance, String>> = portfolios
                     .par_iter()
                     .map(|p| {
-                        let perf = compute_portfolio_performance(
-                            &scenario_returns,
-                            &p.weights,
-                            config.money_to_invest,
-                            config.risk_free_rate,
-                            config.time_horizon_in_days,
-                        );
-                        (perf.annualized_return, perf.percent_annualized_volatility, perf.sharpe_ratio)
+                        let evaluate = || {
+                            let mut perf = compute_portfolio_performance_with_rebalancing(
+                                &scenario_returns,
+                                &p.weights,
+                                config.money_to_invest,
+                                config.risk_free_rate,
+                                config.time_horizon_in_days,
+                                rebalancing,
+                            );
+                            apply_rate_sensitivity(
+                                &mut perf,
+                                &p.fixed_income_assets,
+                                &p.weights,
+                                config.money_to_invest,
+                            );
+                            if let Some(hedge) = &config.hedge {
+                                apply_hedge(
+                                    &mut perf,
+                                    &scenario_returns,
+                                    hedge,
+                                    config.money_to_invest,
+                                    config.risk_free_rate,
+                                );
+                            }
+                            if let Some(put) = &config.protective_put {
+                                apply_protective_put(&mut perf, put, config.money_to_invest, config.risk_free_rate);
+                            }
+                            if let Some(dca) = &config.dca {
+                                apply_dca(&mut perf, dca, config.money_to_invest);
+                            }
+                            if config.compute_stability {
+                                perf.stability_score = Some(compute_stability_score(
+                                    &p.weights,
+                                    &scenario_returns,
+                                    DEFAULT_STABILITY_NOISE_SIGMA,
+                                    DEFAULT_STABILITY_PERTURBATIONS,
+                                    config.risk_free_rate,
+                                    config.time_horizon_in_days,
+                                ));
+                            }
+                            perf
+                        };
+                        if config.error_recovery_mode {
+                            // a malformed portfolio only takes itself down, not the whole batch
+                            panic::catch_unwind(AssertUnwindSafe(evaluate)).map_err(panic_message)
+                        } else {
+                            Ok(evaluate())
+                        }
                     })
                     .collect();
 
+                // iterations where a portfolio failed are left out, the pairs have to line up
+                if let Some(correlation) = &mut acc.portfolio_correlation {
+                    let annualized_returns: Option<Vec<f64>> = metrics
+                        .iter()
+                        .map(|metric| metric.as_ref().ok().map(|perf| perf.annualized_return))
+                        .collect();
+                    if let Some(annualized_returns) = annualized_returns {
+                        correlation.push(&annualized_returns);
+                    }
+                }
+
                 // accumulate
-                for (idx, (r, v, s)) in metrics.into_iter().enumerate() {
-                    sum_returns[idx] += r;
-                    sum_vols[idx]    += v;
-                    sum_sharpes[idx] += s;
+                for (idx, metric) in metrics.into_iter().enumerate() {
+                    match metric {
+                        Ok(perf) => {
+                            // still accumulated, but a NaN here will show up in the sums
+                            if let Err(issues) = perf.validate() {
+                                warn!(
+                                    "iteration {}: portfolio {} has degenerate metrics: {}",
+                                    i,
+                                    idx,
+                                    issues.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+                                );
+                            }
+                            acc.sum_returns[idx] += perf.annualized_return;
+                            acc.sum_vols[idx] += perf.percent_annualized_volatility;
+                            acc.sum_sharpes[idx] += perf.sharpe_ratio;
+                            acc.sum_hedged_sharpes[idx] += perf.hedged_sharpe.unwrap_or_default();
+                            acc.sum_hedging_effectiveness[idx] += perf.hedging_effectiveness.unwrap_or_default();
+                            acc.sum_insured_sharpes[idx] += perf.insured_sharpe.unwrap_or_default();
+                            acc.sum_insured_returns[idx] += perf.insured_return.unwrap_or_default();
+                            acc.sum_insurance_costs[idx] += perf.insurance_cost_total.unwrap_or_default();
+                            acc.sum_dca_terminal_wealth[idx] += perf.dca_terminal_wealth.unwrap_or_default();
+                            acc.sum_lump_sum_terminal_wealth[idx] += perf.lump_sum_terminal_wealth.unwrap_or_default();
+                            acc.sum_stability_scores[idx] += perf.stability_score.unwrap_or_default();
+                            acc.sum_portfolio_dv01s[idx] += perf.portfolio_dv01.unwrap_or_default();
+                            acc.sum_portfolio_convexities[idx] += perf.portfolio_convexity.unwrap_or_default();
+                            for (sum, expr) in acc.sum_custom_metrics[idx].iter_mut().zip(&custom_metrics) {
+                                *sum += expr.evaluate(&perf);
+                            }
+                        }
+                        Err(description) => {
+                            acc.portfolio_errors[idx].get_or_insert(description);
+                        }
+                    }
+                }
+                if let Some(progress) = &progress {
+                    progress.iteration_done();
+                }
+                if let Some((every_n, path)) = &checkpoint {
+                    if (i + 1) % every_n == 0 {
+                        let saved = BatchCheckpoint {
+                            sum_returns: acc.sum_returns.clone(),
+                            sum_vols: acc.sum_vols.clone(),
+                            sum_sharpes: acc.sum_sharpes.clone(),
+                            completed_iterations: (i + 1) as u32,
+                        };
+                        // losing a checkpoint is no reason to lose the batch
+                        if let Err(e) = save_checkpoint(&saved, path) {
+                            warn!("iteration {}: could not write checkpoint {}: {}", i, path.display(), e);
+                        }
+                    }
+                }
+            }
+            Ok(acc)
+        });
+
+        let joined = match deadline {
+            Some(deadline) => {
+                let timeout_seconds = deadline.as_secs_f64();
+                let estimated = estimated_batch_seconds(n, iterations);
+                if timeout_seconds < estimated {
+                    warn!(
+                        "timeout_seconds={} is below the estimated {:.2}s for {} portfolios x {} iterations",
+                        timeout_seconds, estimated, n, iterations
+                    );
+                }
+                match tokio::time::timeout(deadline, batch).await {
+                    Ok(joined) => joined,
+                    Err(_) => {
+                        cancelled.store(true, Ordering::Relaxed);
+                        self.prefetch_scenarios(prefetched);
+                        return Err(Status::deadline_exceeded(format!(
+                            "Batch did not complete within {}s",
+                            timeout_seconds
+                        )));
+                    }
+                }
+            }
+            None => batch.await,
+        };
+        // replace what this batch used, whether or not it succeeded
+        self.prefetch_scenarios(prefetched);
+        let acc = joined
+            .map_err(|e| Status::internal(format!("batch panicked: {}", e)))?
+            .map_err(Status::from)?;
+
+        // Build the gRPC response
+        let mut reply = SimulationBatchResult {
+            sum_returns: acc.sum_returns,
+            sum_volatilities: acc.sum_vols,
+            sum_sharpes: acc.sum_sharpes,
+            last_scenario: SimulationScenario {
+                returns: acc.last_scenario,
+            },
+            crisis_iterations: acc.crisis_iterations,
+            // proto has no repeated optional, an empty string means the portfolio was fine
+            portfolio_errors: acc
+                .portfolio_errors
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect(),
+            custom_metrics: acc
+                .sum_custom_metrics
+                .into_iter()
+                .map(|values| CustomMetricValues { values })
+                .collect(),
+            sum_hedged_sharpes: acc.sum_hedged_sharpes,
+            sum_hedging_effectiveness: acc.sum_hedging_effectiveness,
+            sum_insured_sharpes: acc.sum_insured_sharpes,
+            sum_insured_returns: acc.sum_insured_returns,
+            sum_insurance_costs: acc.sum_insurance_costs,
+            sum_dca_terminal_wealth: acc.sum_dca_terminal_wealth,
+            sum_lump_sum_terminal_wealth: acc.sum_lump_sum_terminal_wealth,
+            sum_stability_scores: acc.sum_stability_scores,
+            sum_portfolio_dv01s: acc.sum_portfolio_dv01s,
+            sum_portfolio_convexities: acc.sum_portfolio_convexities,
+            sampling_retry_count: acc.sampling_retry_count,
+            portfolio_return_correlation: acc.portfolio_correlation.map(|correlation| CorrelationValues {
+                values: correlation.upper_triangle(),
+            }),
+        };
+        if let Some(precision) = output_precision {
+            reply.round_metrics(precision);
+        }
+        Ok(reply)
+    }
+
+    // Top-level dispatch on simulation_mode: how scenarios are generated. distribution_params,
+    // when present, only decides what the returns look like.
+    // async only to check a sampler out of the pool, which must happen outside blocking code
+    async fn resolve_sampler(&self, req: &SimulationBatchRequest) -> Result<Arc<dyn ScenarioSampler>, Status> {
+        let config = &req.config;
+        let mode = SimulationMode::try_from(config.simulation_mode)
+            .map_err(|_| Status::invalid_argument(format!("Unknown simulation_mode {}", config.simulation_mode)))?;
+        let periods_to_sample = config.periods_to_sample as usize;
+        // only the parametric Monte Carlo copula can chain its draws
+        let is_parametric_monte_carlo = mode == SimulationMode::MonteCarlo
+            && req.scenario_set_id.is_empty()
+            && config.distribution_params.is_some();
+        if config.inter_iteration_correlation.is_some() && !is_parametric_monte_carlo {
+            return Err(Status::invalid_argument(
+                "inter_iteration_correlation requires simulation_mode MONTE_CARLO with distribution_params",
+            ));
+        }
+
+        if let Some(sampler) = self.return_model_sampler(req, mode)? {
+            return Ok(Arc::new(sampler));
+        }
+
+        match mode {
+            // Older clients reference a scenario set without setting a mode (MonteCarlo is the proto default)
+            SimulationMode::MonteCarlo if !req.scenario_set_id.is_empty() => self.replay_sampler(req),
+            SimulationMode::MonteCarlo => match &config.distribution_params {
+                Some(params) => Ok(Arc::new(copula_sampler(
+                    params,
+                    periods_to_sample,
+                    false,
+                    config.inter_iteration_correlation,
+                )?)),
+                None => match &self.sampler_pool {
+                    Some(pool) => Ok(Arc::new(pool.acquire().await?)),
+                    None => Ok(Arc::clone(&self.sampler) as Arc<dyn ScenarioSampler>),
+                },
+            },
+            SimulationMode::QuasiMonteCarlo => {
+                let params = config.distribution_params.as_ref().ok_or_else(|| {
+                    Status::invalid_argument("simulation_mode QUASI_MONTE_CARLO requires distribution_params")
+                })?;
+                Ok(Arc::new(copula_sampler(params, periods_to_sample, true, None)?))
+            }
+            SimulationMode::Bootstrap => Ok(Arc::new(SamplerMode::block_bootstrap(
+                self.registered_scenarios(req)?,
+                config.bootstrap_block_size as usize,
+                periods_to_sample,
+            ))),
+            SimulationMode::HistoricalReplay => self.replay_sampler(req),
+            SimulationMode::Hybrid => {
+                let params = config
+                    .distribution_params
+                    .as_ref()
+                    .ok_or_else(|| Status::invalid_argument("simulation_mode HYBRID requires distribution_params"))?;
+                if !(0.0..=1.0).contains(&config.historical_weight) {
+                    return Err(Status::invalid_argument(format!(
+                        "historical_weight must be in [0, 1] (found {})",
+                        config.historical_weight
+                    )));
+                }
+                let historical = SamplerMode::block_bootstrap(
+                    self.registered_scenarios(req)?,
+                    config.bootstrap_block_size as usize,
+                    periods_to_sample,
+                );
+                let parametric = copula_sampler(params, periods_to_sample, false, None)?;
+                // dimension() only reports the parametric side, portfolios are checked against that
+                if historical.dimension() != parametric.dimension() {
+                    return Err(Status::invalid_argument(format!(
+                        "Scenario set '{}' has {} assets but distribution_params has {}",
+                        req.scenario_set_id,
+                        historical.dimension(),
+                        parametric.dimension()
+                    )));
+                }
+                Ok(Arc::new(SamplerMode::hybrid(
+                    historical,
+                    parametric,
+                    config.historical_weight,
+                )))
+            }
+        }
+    }
+
+    // Return models that replace the copula of distribution_params outright. Parameters are
+    // range-checked by ValidateConfig, this only sorts out which model (if any) was asked for.
+    fn return_model_sampler(
+        &self,
+        req: &SimulationBatchRequest,
+        mode: SimulationMode,
+    ) -> Result<Option<SamplerMode>, Status> {
+        let config = &req.config;
+        if config.variance_gamma.is_none() && config.stable.is_none() && config.kde.is_none() {
+            return Ok(None);
+        }
+        if mode != SimulationMode::MonteCarlo || config.distribution_params.is_some() {
+            return Err(Status::invalid_argument(
+                "variance_gamma, stable and kde require simulation_mode MONTE_CARLO and no distribution_params",
+            ));
+        }
+        let periods_to_sample = config.periods_to_sample as usize;
+        let sampler = match (&config.variance_gamma, &config.stable, &config.kde) {
+            (Some(vg), None, None) => SamplerMode::variance_gamma(
+                vg.sigma,
+                vg.nu,
+                vg.theta,
+                vg.dt,
+                vg.n_assets as usize,
+                periods_to_sample,
+            ),
+            (None, Some(stable), None) => stable_sampler(stable, periods_to_sample)?,
+            (None, None, Some(kde)) => {
+                if req.scenario_set_id.is_empty() {
+                    return Err(Status::invalid_argument(
+                        "kde requires a scenario_set_id to estimate densities from",
+                    ));
                 }
+                let history = self.registered_scenarios(req)?;
+                kde_sampler(&history, kde.bandwidth, periods_to_sample)?
+            }
+            _ => {
+                return Err(Status::invalid_argument(
+                    "Only one of variance_gamma, stable and kde can be set",
+                ));
             }
-            (sum_returns, sum_vols, sum_sharpes, last_scenario)
+        };
+        Ok(Some(sampler))
+    }
+
+    fn registered_scenarios(&self, req: &SimulationBatchRequest) -> Result<Arc<Vec<Scenario>>, Status> {
+        if req.scenario_set_id.is_empty() {
+            return Err(Status::invalid_argument(
+                "simulation_mode BOOTSTRAP, HISTORICAL_REPLAY and HYBRID require a scenario_set_id",
+            ));
+        }
+        let scenarios = self
+            .scenario_sets
+            .get(&req.scenario_set_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown scenario_set_id '{}'", req.scenario_set_id)))?;
+        Ok(Arc::clone(&scenarios))
+    }
+
+    fn replay_sampler(&self, req: &SimulationBatchRequest) -> Result<Arc<dyn ScenarioSampler>, Status> {
+        let selection = if req.randomize_scenario_order {
+            ScenarioSelection::Random
+        } else {
+            ScenarioSelection::Cycle
+        };
+        Ok(Arc::new(SamplerMode::ScenarioSet(ScenarioSet::new(
+            self.registered_scenarios(req)?,
+            selection,
+        ))))
+    }
+
+    // Registered set (if referenced) followed by whatever came inline in the blob.
+    fn resolve_portfolios(&self, req: &SimulationBatchRequest) -> Result<Vec<Portfolio>, Status> {
+        let mut portfolios = Vec::new();
+        if !req.portfolio_set_id.is_empty() {
+            let registered = self
+                .portfolio_sets
+                .get(&req.portfolio_set_id)
+                .ok_or_else(|| Status::not_found(format!("Unknown portfolio_set_id '{}'", req.portfolio_set_id)))?;
+            portfolios.extend(registered.iter().cloned());
+        }
+        if !req.portfolios_blob.is_empty() {
+            // Deserialize the portfolios blob using bincode.
+            // Either a plain bincode Vec<Portfolio> or the framed layout, see `encoding`.
+            let inline =
+                decode_portfolios(&req.portfolios_blob).map_err(|e| Status::invalid_argument(e.to_string()))?;
+            portfolios.extend(inline);
+        }
+        Ok(portfolios)
+    }
+}
+
+#[tonic::async_trait]
+impl SimulationService for SimulationServiceImpl {
+    async fn run_batch(
+        &self,
+        request: Request<SimulationBatchRequest>,
+    ) -> Result<Response<SimulationBatchResult>, Status> {
+        // Opt-in caching: clients send the TTL they're happy with as metadata
+        let cache_ttl = request
+            .metadata()
+            .get(CACHE_TTL_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|ttl| *ttl > 0)
+            .map(Duration::from_secs);
+
+        // Extract the batch request
+        let req = request.into_inner();
+        let started = Instant::now();
+        let first_hour = current_hour();
+
+        let (reply, from_cache) = match cache_ttl {
+            Some(ttl) => {
+                let key = request_hash(&req);
+                match self.result_cache.get_fresh(key, ttl) {
+                    Some(cached) => (cached, true),
+                    None => {
+                        let reply = self.simulate_batch(req).await?;
+                        self.result_cache.insert(key, reply.clone(), ttl);
+                        (reply, false)
+                    }
+                }
+            }
+            None => (self.simulate_batch(req).await?, false),
+        };
+        self.latencies.record(started.elapsed());
+
+        let mut response = Response::new(reply);
+        response.metadata_mut().insert(
+            FROM_CACHE_HEADER,
+            MetadataValue::from_static(if from_cache { "true" } else { "false" }),
+        );
+        // Tell auditors where this batch's scenarios went (a cached reply sampled nothing new)
+        if let (Some(audit_log), false) = (&self.audit_log, from_cache) {
+            match audit_log.files_between(first_hour, current_hour()).parse() {
+                Ok(files) => {
+                    response.metadata_mut().insert(SCENARIO_LOG_FILE_HEADER, files);
+                }
+                Err(_) => warn!(
+                    "audit log path is not valid metadata, {} not set",
+                    SCENARIO_LOG_FILE_HEADER
+                ),
+            }
+        }
+        Ok(response)
+    }
+
+    async fn register_portfolios(
+        &self,
+        request: Request<RegisterRequest>,
+    ) -> Result<Response<RegisterResponse>, Status> {
+        let req = request.into_inner();
+        if req.portfolio_set_id.is_empty() {
+            return Err(Status::invalid_argument("portfolio_set_id cannot be empty"));
+        }
+
+        let portfolios =
+            decode_portfolios(&req.portfolios_blob).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let portfolio_count = portfolios.len() as u32;
+
+        // Re-registering an id simply replaces the previous set
+        self.portfolio_sets
+            .insert(req.portfolio_set_id.clone(), Arc::new(portfolios));
+
+        Ok(Response::new(RegisterResponse {
+            portfolio_set_id: req.portfolio_set_id,
+            portfolio_count,
+        }))
+    }
+
+    async fn register_scenarios(
+        &self,
+        request: Request<RegisterScenariosRequest>,
+    ) -> Result<Response<RegisterScenariosResponse>, Status> {
+        let req = request.into_inner();
+        if req.scenario_set_id.is_empty() {
+            return Err(Status::invalid_argument("scenario_set_id cannot be empty"));
+        }
+
+        // iterations x periods x assets, bincode encoded
+        let scenarios: Vec<Scenario> = bincode::deserialize(&req.scenarios_blob)
+            .map_err(|e| Status::invalid_argument(format!("Failed to deserialize scenarios: {}", e)))?;
+        let n_assets = validate_scenarios(&scenarios).map_err(Status::invalid_argument)?;
+        let scenario_count = scenarios.len() as u32;
+
+        self.scenario_sets
+            .insert(req.scenario_set_id.clone(), Arc::new(scenarios));
+
+        Ok(Response::new(RegisterScenariosResponse {
+            scenario_set_id: req.scenario_set_id,
+            scenario_count,
+            n_assets: n_assets as u32,
+        }))
+    }
+
+    async fn credit_simulate(
+        &self,
+        request: Request<CreditSimulateRequest>,
+    ) -> Result<Response<CreditSimulateResponse>, Status> {
+        let req = request.into_inner();
+        let n_issuers = req.exposures.len();
+        if req.default_probs.len() != n_issuers || req.recovery_rates.len() != n_issuers {
+            return Err(Status::invalid_argument(
+                "exposures, default_probs and recovery_rates must have the same length",
+            ));
+        }
+        check_square("correlation", &req.correlation, n_issuers)?;
+        // both are probabilities, a pd outside [0, 1] has no default threshold
+        for (name, values) in [
+            ("default_probs", &req.default_probs),
+            ("recovery_rates", &req.recovery_rates),
+        ] {
+            if let Some((idx, value)) = values.iter().enumerate().find(|(_, v)| !(0.0..=1.0).contains(*v)) {
+                return Err(Status::invalid_argument(format!(
+                    "{} must be in [0, 1] (found {} for issuer {})",
+                    name, value, idx
+                )));
+            }
+        }
+        if req.n_simulations == 0 {
+            return Err(Status::invalid_argument("n_simulations must be positive"));
+        }
+        // the loss quantile at 0 or 1 is the best / worst simulated loss, not a risk measure
+        if req.confidence_level.is_nan() || req.confidence_level <= 0.0 || req.confidence_level >= 1.0 {
+            return Err(Status::invalid_argument(format!(
+                "confidence_level must be in (0, 1) (found {})",
+                req.confidence_level
+            )));
+        }
+
+        let portfolio = CreditPortfolio {
+            exposures: req.exposures,
+            default_probs: req.default_probs,
+            recovery_rates: req.recovery_rates,
+        };
+        let correlation = unflatten_square(&req.correlation, n_issuers);
+        let n_simulations = req.n_simulations as usize;
+        let confidence = req.confidence_level;
+
+        let distribution =
+            tokio::task::spawn_blocking(move || simulate_credit_loss(&portfolio, &correlation, n_simulations))
+                .await
+                .map_err(|e| Status::internal(format!("credit simulation panicked: {}", e)))?
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        Ok(Response::new(CreditSimulateResponse {
+            expected_loss: distribution.expected_loss,
+            loss_std_dev: distribution.loss_std_dev,
+            value_at_risk: distribution.value_at_risk(confidence),
+            expected_shortfall: distribution.expected_shortfall(confidence),
+        }))
+    }
+
+    async fn minimize_tracking_error(
+        &self,
+        request: Request<MinimizeTrackingErrorRequest>,
+    ) -> Result<Response<MinimizeTrackingErrorResponse>, Status> {
+        let req = request.into_inner();
+        let n_assets = req.target_weights.len();
+        check_square("covariance", &req.covariance, n_assets)?;
+        let cov = unflatten_square(&req.covariance, n_assets);
+        check_min_position_size(req.min_position_size)?;
+        let max_positions = req.max_positions as usize;
+        let target_weights = req.target_weights;
+        let min_position_size = req.min_position_size;
+
+        let (weights, tracking_error, constraint_violations_resolved) = tokio::task::spawn_blocking(move || {
+            let mut weights = minimize_tracking_error(&target_weights, &cov, n_assets, max_positions);
+            let zeroed = apply_min_position_size(&mut weights, min_position_size);
+            // of the weights actually returned, after any small position was dropped
+            let tracking_error = tracking_error_variance(&weights, &target_weights, &cov).sqrt();
+            (weights, tracking_error, zeroed)
         })
         .await
-        .map_err(|e| Status::internal(format!("batch panicked: {}", e)))?;
+        .map_err(|e| Status::internal(format!("tracking error minimization panicked: {}", e)))?;
 
-        // Build the gRPC response
-        let reply = PopulationPartialResult {
-            sum_returns: sr,
-            sum_volatilities: sv,
-            sum_sharpes: ss,
-            last_scenario: SimulationScenario { returns: ls },
+        Ok(Response::new(MinimizeTrackingErrorResponse {
+            weights,
+            tracking_error,
+            constraint_violations_resolved,
+        }))
+    }
+
+    async fn generate_scenario_tree(
+        &self,
+        request: Request<GenerateScenarioTreeRequest>,
+    ) -> Result<Response<GenerateScenarioTreeResponse>, Status> {
+        let req = request.into_inner();
+        let n_stages = req.n_stages as usize;
+        let branching_factor = req.branching_factor as usize;
+        if branching_factor == 0 {
+            return Err(Status::invalid_argument("branching_factor must be at least 1"));
+        }
+        match scenario_tree_size(n_stages, branching_factor) {
+            Some(size) if size <= MAX_SCENARIO_TREE_NODES => {}
+            _ => {
+                return Err(Status::resource_exhausted(format!(
+                    "A tree with {} stages and branching factor {} exceeds {} nodes",
+                    n_stages, branching_factor, MAX_SCENARIO_TREE_NODES
+                )));
+            }
+        }
+
+        let sampler = Arc::clone(&self.sampler);
+        let tree =
+            tokio::task::spawn_blocking(move || generate_scenario_tree(n_stages, branching_factor, sampler.as_ref()))
+                .await
+                .map_err(|e| Status::internal(format!("scenario tree generation panicked: {}", e)))?
+                .map_err(Status::from)?;
+
+        Ok(Response::new(GenerateScenarioTreeResponse {
+            root: tree.root as u32,
+            nodes: tree
+                .nodes
+                .into_iter()
+                .map(|node| ScenarioTreeNode {
+                    period: node.period as u32,
+                    returns: node.returns,
+                    probability: node.probability,
+                    children: node.children.into_iter().map(|child| child as u32).collect(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn run_search(&self, request: Request<RunSearchRequest>) -> Result<Response<RunSearchResponse>, Status> {
+        let req = request.into_inner();
+        req.config
+            .validate()
+            .map_err(|errors| Status::invalid_argument(describe_errors(&errors)))?;
+        if req.n_portfolios == 0 || req.n_assets == 0 {
+            return Err(Status::invalid_argument("n_portfolios and n_assets must be positive"));
+        }
+        // candidates are evaluated on the server's sampler, a mismatch would be silently truncated
+        let dimension = self.sampler.dimension();
+        if req.n_assets as usize != dimension {
+            return Err(Status::invalid_argument(format!(
+                "n_assets is {} but the sampler generates returns for {} assets",
+                req.n_assets, dimension
+            )));
+        }
+
+        let config = req.config;
+        let long_only = req.constraints.long_only;
+        let n_assets = req.n_assets as usize;
+        let n_portfolios = req.n_portfolios as usize;
+        let iterations = req.iterations as usize;
+        let sampler = Arc::clone(&self.sampler);
+
+        let best = tokio::task::spawn_blocking(move || {
+            let mut rng = rand::rng();
+            let candidates: Vec<Vec<f64>> = (0..n_portfolios)
+                .map(|_| {
+                    if long_only {
+                        random_long_only_weights(n_assets, &mut rng)
+                    } else {
+                        random_long_short_weights(n_assets, &mut rng)
+                    }
+                })
+                .collect();
+            let sharpes = mean_sharpes(
+                &candidates,
+                sampler.as_ref(),
+                iterations,
+                config.money_to_invest,
+                config.risk_free_rate,
+                config.time_horizon_in_days,
+            );
+            best_by_sharpe(candidates, &sharpes)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("search panicked: {}", e)))?;
+
+        let (mut weights, best_sharpe) = best.ok_or_else(|| Status::internal("search produced no candidates"))?;
+        // best_sharpe is that of the candidate as found, before any position was dropped
+        let constraint_violations_resolved = apply_min_position_size(&mut weights, config.min_position_size);
+        Ok(Response::new(RunSearchResponse {
+            best_portfolio: Portfolio {
+                weights,
+                ..Default::default()
+            },
+            best_sharpe,
+            constraint_violations_resolved,
+        }))
+    }
+
+    async fn run_what_if(&self, request: Request<WhatIfRequest>) -> Result<Response<WhatIfResponse>, Status> {
+        let req = request.into_inner();
+        if req.iterations == 0 {
+            return Err(Status::invalid_argument("iterations must be positive"));
+        }
+
+        // Base portfolio first, then one portfolio per shift, all evaluated on the same scenarios
+        // so the differences are down to the shifts and not to sampling noise
+        let base = req.base_portfolio;
+        let mut portfolios = vec![base.clone()];
+        for shift in &req.shifts {
+            let weights = shifted_weights(
+                &base.weights,
+                shift.from_asset as usize,
+                shift.to_asset as usize,
+                shift.shift_fraction,
+            )
+            .map_err(Status::invalid_argument)?;
+            portfolios.push(Portfolio {
+                weights,
+                ..base.clone()
+            });
+        }
+
+        let portfolios_blob = encode_portfolios_framed(&portfolios)
+            .map_err(|e| Status::internal(format!("Failed to encode shifted portfolios: {}", e)))?;
+        let output_precision = req.config.output_precision;
+        let batch = self
+            .simulate_batch(SimulationBatchRequest {
+                config: req.config,
+                iterations: req.iterations,
+                portfolios_blob,
+                ..Default::default()
+            })
+            .await?;
+
+        // Batch sums -> per-iteration means
+        let iterations = f64::from(req.iterations);
+        let mut results: Vec<PortfolioMetrics> = (0..portfolios.len())
+            .map(|idx| {
+                let has_bonds = !portfolios[idx].fixed_income_assets.is_empty();
+                PortfolioMetrics {
+                    annualized_return: batch.sum_returns[idx] / iterations,
+                    percent_annualized_volatility: batch.sum_volatilities[idx] / iterations,
+                    sharpe_ratio: batch.sum_sharpes[idx] / iterations,
+                    portfolio_dv01: has_bonds.then(|| batch.sum_portfolio_dv01s[idx] / iterations),
+                    portfolio_convexity: has_bonds.then(|| batch.sum_portfolio_convexities[idx] / iterations),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        if let Some(precision) = output_precision {
+            results.iter_mut().for_each(|result| result.round_metrics(precision));
+        }
+        let base_result = results.remove(0);
+
+        Ok(Response::new(WhatIfResponse { base_result, results }))
+    }
+
+    async fn estimate_runtime(
+        &self,
+        request: Request<SimulationBatchRequest>,
+    ) -> Result<Response<RuntimeEstimate>, Status> {
+        let req = request.into_inner();
+        let n_portfolios = self.resolve_portfolios(&req)?.len();
+        let (estimated_ms, lower_ms, upper_ms) = self.runtime_model.estimate_ms(n_portfolios, req.iterations as usize);
+        Ok(Response::new(RuntimeEstimate {
+            estimated_ms,
+            lower_ms,
+            upper_ms,
+        }))
+    }
+
+    async fn backtest_va_r(
+        &self,
+        request: Request<BacktestVaRRequest>,
+    ) -> Result<Response<BacktestVaRResponse>, Status> {
+        let req = request.into_inner();
+        if req.realized_returns.is_empty() {
+            return Err(Status::invalid_argument("realized_returns cannot be empty"));
+        }
+        // at 0 every observation is expected to be a violation, the null likelihood is degenerate
+        if req.confidence_level.is_nan() || req.confidence_level <= 0.0 || req.confidence_level >= 1.0 {
+            return Err(Status::invalid_argument(format!(
+                "confidence_level must be in (0, 1) (found {})",
+                req.confidence_level
+            )));
+        }
+
+        // var_historical is a positive dollar loss, a violation is a realized loss beyond it
+        let violation_sequence: Vec<bool> = req
+            .realized_returns
+            .iter()
+            .map(|ret| *ret < -req.var_historical)
+            .collect();
+        let violations = violation_sequence.iter().filter(|v| **v).count() as u32;
+        let n_observations = violation_sequence.len() as u32;
+        let kupiec = kupiec_pof_test(violations, n_observations, req.confidence_level);
+        // frequency (Kupiec) and clustering (Christoffersen) are separate failure modes, report both
+        let christoffersen =
+            (violation_sequence.len() >= 2).then(|| christoffersen_independence_test(&violation_sequence).into());
+
+        Ok(Response::new(BacktestVaRResponse {
+            violations,
+            n_observations,
+            kupiec: Some(kupiec.into()),
+            christoffersen,
+        }))
+    }
+
+    async fn get_batch_progress(
+        &self,
+        request: Request<GetBatchProgressRequest>,
+    ) -> Result<Response<Progress>, Status> {
+        let job_id = request.into_inner().job_id;
+        self.progress
+            .get(&job_id)
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("No batch reporting progress as '{}'", job_id)))
+    }
+
+    async fn min_c_va_r(&self, request: Request<MinCVaRRequest>) -> Result<Response<MinCVaRResponse>, Status> {
+        let req = request.into_inner();
+        let n_assets = req.n_assets as usize;
+        // scenarios x assets, flattened row-major like the matrices elsewhere
+        if n_assets == 0 || req.scenario_returns.is_empty() || req.scenario_returns.len() % n_assets != 0 {
+            return Err(Status::invalid_argument(format!(
+                "scenario_returns must be a flattened n_scenarios x {} matrix (found {} values)",
+                n_assets,
+                req.scenario_returns.len()
+            )));
+        }
+        if !(0.0..1.0).contains(&req.confidence) {
+            return Err(Status::invalid_argument(format!(
+                "confidence must be in [0, 1) (found {})",
+                req.confidence
+            )));
+        }
+
+        check_min_position_size(req.min_position_size)?;
+
+        let scenarios: Vec<Vec<f64>> = req.scenario_returns.chunks(n_assets).map(<[f64]>::to_vec).collect();
+        let target_return = req.target_return;
+        let confidence = req.confidence;
+        let mut solution =
+            tokio::task::spawn_blocking(move || optimize_min_cvar(&scenarios, n_assets, target_return, confidence))
+                .await
+                .map_err(|e| Status::internal(format!("min-CVaR optimization panicked: {}", e)))?
+                .map_err(|e| Status::failed_precondition(format!("No min-CVaR portfolio: {}", e)))?;
+
+        // VaR and CVaR are those of the optimum, before any position was dropped
+        let constraint_violations_resolved = apply_min_position_size(&mut solution.weights, req.min_position_size);
+        Ok(Response::new(MinCVaRResponse {
+            weights: solution.weights,
+            value_at_risk: solution.value_at_risk,
+            conditional_value_at_risk: solution.conditional_value_at_risk,
+            constraint_violations_resolved,
+        }))
+    }
+
+    async fn simulate_drawdown_recovery(
+        &self,
+        request: Request<SimulateDrawdownRecoveryRequest>,
+    ) -> Result<Response<SimulateDrawdownRecoveryResponse>, Status> {
+        let req = request.into_inner();
+        req.config
+            .validate()
+            .map_err(|errors| Status::invalid_argument(describe_errors(&errors)))?;
+        if !(0.0..1.0).contains(&req.current_drawdown) {
+            return Err(Status::invalid_argument(format!(
+                "current_drawdown must be in [0, 1) (found {})",
+                req.current_drawdown
+            )));
+        }
+        if req.max_recovery_periods == 0 {
+            return Err(Status::invalid_argument("max_recovery_periods must be positive"));
+        }
+
+        // Same sampler a batch with this config would get
+        let sampler = self
+            .resolve_sampler(&SimulationBatchRequest {
+                config: req.config,
+                ..Default::default()
+            })
+            .await?;
+        let weights = req.portfolio.weights;
+        if weights.len() != sampler.dimension() {
+            return Err(Status::invalid_argument(format!(
+                "portfolio has {} weights but the sampler generates returns for {} assets",
+                weights.len(),
+                sampler.dimension()
+            )));
+        }
+        let current_drawdown = req.current_drawdown;
+        let max_recovery_periods = req.max_recovery_periods as usize;
+        let distribution = tokio::task::spawn_blocking(move || {
+            simulate_drawdown_recovery(
+                sampler.as_ref(),
+                &weights,
+                current_drawdown,
+                max_recovery_periods,
+                DRAWDOWN_RECOVERY_PATHS,
+            )
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Drawdown recovery simulation panicked: {}", e)))?
+        .map_err(Status::from)?;
+
+        Ok(Response::new(SimulateDrawdownRecoveryResponse {
+            mean_recovery_periods: distribution.mean_recovery_periods,
+            median_recovery_periods: distribution.median_recovery_periods,
+            probability_of_no_recovery: distribution.probability_of_no_recovery,
+        }))
+    }
+
+    async fn run_batch_compare(
+        &self,
+        request: Request<RunBatchCompareRequest>,
+    ) -> Result<Response<RunBatchCompareResponse>, Status> {
+        let req = request.into_inner();
+        for config in [&req.config_a, &req.config_b] {
+            config
+                .validate()
+                .map_err(|errors| Status::invalid_argument(describe_errors(&errors)))?;
+        }
+        if req.iterations == 0 {
+            return Err(Status::invalid_argument("iterations must be positive"));
+        }
+
+        // Common random numbers: the scenarios are drawn once, from config_a's sampler, and replayed
+        // under both configs. Our samplers can't be seeded, so a config_b that samples differently
+        // can't share them and is turned away instead of ignored.
+        let differing = sampling_fields_differing(&req.config_a, &req.config_b);
+        if !differing.is_empty() {
+            return Err(Status::invalid_argument(format!(
+                "config_a and config_b share their scenarios, so they must sample the same way (differing: {})",
+                differing.join(", ")
+            )));
+        }
+        let sampling_request = SimulationBatchRequest {
+            config: req.config_a.clone(),
+            iterations: req.iterations,
+            portfolios_blob: req.portfolios_blob.clone(),
+            ..Default::default()
+        };
+        let n_assets = self
+            .resolve_portfolios(&sampling_request)?
+            .first()
+            .map_or(0, |p| p.weights.len());
+        // one shared copy, plus a stressed copy for each config with correlation_stress
+        let copies = 1 + [&req.config_a, &req.config_b]
+            .iter()
+            .filter(|config| config.correlation_stress.is_some())
+            .count();
+        let scenario_bytes = (req.iterations as usize)
+            .saturating_mul(req.config_a.periods_to_sample as usize)
+            .saturating_mul(n_assets)
+            .saturating_mul(std::mem::size_of::<f64>())
+            .saturating_mul(copies);
+        if scenario_bytes > self.max_batch_memory_bytes {
+            return Err(Status::resource_exhausted(format!(
+                "Shared scenarios would need an estimated {} bytes, the server allows {}",
+                scenario_bytes, self.max_batch_memory_bytes
+            )));
+        }
+        let sampler = self.resolve_sampler(&sampling_request).await?;
+        let iterations = req.iterations as usize;
+        let stresses = [&req.config_a, &req.config_b].map(|config| {
+            config
+                .correlation_stress
+                .as_ref()
+                .map(|stress| (stress.crisis_probability, stress.crisis_correlation))
+        });
+        let (scenario_sets, crisis_iterations) = tokio::task::spawn_blocking(move || {
+            let scenarios = Arc::new(
+                (0..iterations)
+                    .map(|_| sampler.try_sample_returns())
+                    .collect::<Result<Vec<Scenario>, SimulationError>>()?,
+            );
+            // Crises are applied here rather than in the replays, which would each draw their own
+            let mut rng = rand::rng();
+            let crisis_draws: Vec<f64> = (0..iterations).map(|_| rng.random::<f64>()).collect();
+            let mut scenario_sets = Vec::with_capacity(2);
+            let mut crisis_iterations = Vec::with_capacity(2);
+            for stress in stresses {
+                match stress {
+                    Some((crisis_probability, crisis_correlation)) => {
+                        let (stressed, crises) =
+                            stress_scenarios(&scenarios, &crisis_draws, crisis_probability, crisis_correlation);
+                        scenario_sets.push(Arc::new(stressed));
+                        crisis_iterations.push(crises);
+                    }
+                    None => {
+                        scenario_sets.push(Arc::clone(&scenarios));
+                        crisis_iterations.push(0);
+                    }
+                }
+            }
+            Ok::<_, SimulationError>((scenario_sets, crisis_iterations))
+        })
+        .await
+        .map_err(|e| Status::internal(format!("scenario sampling panicked: {}", e)))?
+        .map_err(Status::from)?;
+
+        // Registered under throwaway ids for the two replays, removed whatever the outcome
+        let scenario_set_ids: Vec<String> = scenario_sets
+            .into_iter()
+            .map(|scenarios| {
+                let scenario_set_id = format!("compare-{:016x}", rand::rng().random::<u64>());
+                self.scenario_sets.insert(scenario_set_id.clone(), scenarios);
+                scenario_set_id
+            })
+            .collect();
+        let replay = |config: EvolutionConfig, scenario_set_id: &str| SimulationBatchRequest {
+            config: EvolutionConfig {
+                simulation_mode: SimulationMode::HistoricalReplay as i32,
+                correlation_stress: None,
+                ..config
+            },
+            iterations: req.iterations,
+            portfolios_blob: req.portfolios_blob.clone(),
+            scenario_set_id: scenario_set_id.to_string(),
+            ..Default::default()
+        };
+        let result_a = self.simulate_batch(replay(req.config_a, &scenario_set_ids[0])).await;
+        let result_b = self.simulate_batch(replay(req.config_b, &scenario_set_ids[1])).await;
+        for scenario_set_id in &scenario_set_ids {
+            self.scenario_sets.remove(scenario_set_id);
+        }
+        let (mut result_a, mut result_b) = (result_a?, result_b?);
+        result_a.crisis_iterations = crisis_iterations[0];
+        result_b.crisis_iterations = crisis_iterations[1];
+
+        // b relative to a, per-iteration means
+        let iterations = f64::from(req.iterations);
+        let mean_diff = |a: &[f64], b: &[f64], idx: usize| (b[idx] - a[idx]) / iterations;
+        let comparison = (0..result_a.sum_returns.len())
+            .map(|idx| PerformanceDiff {
+                annualized_return_diff: mean_diff(&result_a.sum_returns, &result_b.sum_returns, idx),
+                percent_annualized_volatility_diff: mean_diff(
+                    &result_a.sum_volatilities,
+                    &result_b.sum_volatilities,
+                    idx,
+                ),
+                sharpe_ratio_diff: mean_diff(&result_a.sum_sharpes, &result_b.sum_sharpes, idx),
+            })
+            .collect();
+
+        Ok(Response::new(RunBatchCompareResponse {
+            result_a,
+            result_b,
+            comparison,
+        }))
+    }
+
+    async fn michaud_resample(
+        &self,
+        request: Request<MichaudResampleRequest>,
+    ) -> Result<Response<MichaudResampleResponse>, Status> {
+        let req = request.into_inner();
+        let n_assets = req.n_assets as usize;
+        // observations x assets, flattened row-major like the matrices elsewhere
+        if n_assets == 0 || req.returns.len() % n_assets != 0 || req.returns.len() / n_assets < 2 {
+            return Err(Status::invalid_argument(format!(
+                "returns must be a flattened n_observations x {} matrix with at least 2 observations (found {} values)",
+                n_assets,
+                req.returns.len()
+            )));
+        }
+        if req.n_resamples == 0 {
+            return Err(Status::invalid_argument("n_resamples must be positive"));
+        }
+        check_min_position_size(req.min_position_size)?;
+        let covariance_ddof = req.covariance_ddof.unwrap_or(DEFAULT_COVARIANCE_DDOF);
+        if covariance_ddof as usize >= req.returns.len() / n_assets {
+            return Err(Status::invalid_argument(format!(
+                "covariance_ddof must be below the number of observations (found {})",
+                covariance_ddof
+            )));
+        }
+
+        let returns: Vec<Vec<f64>> = req.returns.chunks(n_assets).map(<[f64]>::to_vec).collect();
+        let n_resamples = req.n_resamples as usize;
+        let target_return = req.target_return;
+        let mut weights = tokio::task::spawn_blocking(move || {
+            michaud_resample(&returns, n_resamples, target_return, covariance_ddof)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Michaud resampling panicked: {}", e)))?
+        .map_err(|e| Status::failed_precondition(format!("No resampled portfolio: {}", e)))?;
+
+        let constraint_violations_resolved = apply_min_position_size(&mut weights, req.min_position_size);
+        Ok(Response::new(MichaudResampleResponse {
+            weights,
+            constraint_violations_resolved,
+        }))
+    }
+
+    async fn market_neutralize(
+        &self,
+        request: Request<MarketNeutralizeRequest>,
+    ) -> Result<Response<MarketNeutralizeResponse>, Status> {
+        let req = request.into_inner();
+        if req.weights.len() != req.betas.len() {
+            return Err(Status::invalid_argument(format!(
+                "{} weights for {} betas",
+                req.weights.len(),
+                req.betas.len()
+            )));
+        }
+        if req.betas.iter().any(|beta| !beta.is_finite()) {
+            return Err(Status::invalid_argument("betas must be finite"));
+        }
+
+        let weights = make_market_neutral(&req.weights, &req.betas);
+        // ~0 up to round-off, reported so clients can check
+        let portfolio_beta = weights.iter().zip(&req.betas).map(|(w, b)| w * b).sum::<f64>();
+        Ok(Response::new(MarketNeutralizeResponse {
+            weights,
+            portfolio_beta,
+        }))
+    }
+
+    async fn cardinality_constrained(
+        &self,
+        request: Request<CardinalityConstrainedRequest>,
+    ) -> Result<Response<CardinalityConstrainedResponse>, Status> {
+        let req = request.into_inner();
+        let n_assets = req.expected_returns.len();
+        check_square("covariance", &req.covariance, n_assets)?;
+        if n_assets == 0 || req.max_positions == 0 {
+            return Err(Status::invalid_argument(
+                "expected_returns and max_positions must be non-empty / positive",
+            ));
+        }
+        check_min_position_size(req.min_position_size)?;
+        let cov = unflatten_square(&req.covariance, n_assets);
+        let max_positions = req.max_positions as usize;
+        let expected_returns = req.expected_returns;
+
+        let mut weights = tokio::task::spawn_blocking(move || {
+            cardinality_constrained_optimize(&expected_returns, &cov, max_positions)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("cardinality constrained optimization panicked: {}", e)))?
+        .map_err(|e| Status::failed_precondition(format!("No cardinality constrained portfolio: {}", e)))?;
+
+        let constraint_violations_resolved = apply_min_position_size(&mut weights, req.min_position_size);
+        Ok(Response::new(CardinalityConstrainedResponse {
+            weights,
+            constraint_violations_resolved,
+        }))
+    }
+
+    async fn run_batch_encoded(
+        &self,
+        request: Request<SimulationBatchRequest>,
+    ) -> Result<Response<GenericResponse>, Status> {
+        let format = match request.metadata().get(RESPONSE_FORMAT_HEADER) {
+            Some(value) => value
+                .to_str()
+                .map_err(|_| Status::invalid_argument(format!("{} is not valid ASCII", RESPONSE_FORMAT_HEADER)))?
+                .parse::<ResponseFormat>()
+                .map_err(Status::invalid_argument)?,
+            None => ResponseFormat::Proto,
         };
-        Ok(Response::new(reply))
+
+        let req = request.into_inner();
+        let started = Instant::now();
+        let reply = self.simulate_batch(req).await?;
+        self.latencies.record(started.elapsed());
+
+        let data = tokio::task::spawn_blocking(move || encode_batch_result(&reply, format))
+            .await
+            .map_err(|e| Status::internal(format!("response encoding panicked: {}", e)))?
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(GenericResponse {
+            format: format.as_str().to_string(),
+            data,
+        }))
+    }
+
+    async fn return_sensitivity(
+        &self,
+        request: Request<ReturnSensitivityRequest>,
+    ) -> Result<Response<ReturnSensitivityResponse>, Status> {
+        let req = request.into_inner();
+        let n_assets = req.base_weights.len();
+        if n_assets == 0 {
+            return Err(Status::invalid_argument("base_weights cannot be empty"));
+        }
+        check_square("covariance", &req.covariance, n_assets)?;
+        // optional, without it only the Jacobian is returned
+        if !req.delta_returns.is_empty() && req.delta_returns.len() != n_assets {
+            return Err(Status::invalid_argument(format!(
+                "delta_returns has {} values for {} assets",
+                req.delta_returns.len(),
+                n_assets
+            )));
+        }
+        let cov = unflatten_square(&req.covariance, n_assets);
+
+        let sensitivity = tokio::task::spawn_blocking(move || {
+            return_sensitivity_analysis(&req.base_weights, &cov, &req.delta_returns)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("return sensitivity panicked: {}", e)))?
+        .map_err(|e| Status::failed_precondition(format!("Covariance of the held assets: {}", e)))?;
+
+        Ok(Response::new(ReturnSensitivityResponse {
+            jacobian: sensitivity.jacobian.into_iter().flatten().collect(),
+            weight_changes: sensitivity.weight_changes,
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl AdminService for SimulationServiceImpl {
+    async fn get_health(&self, _request: Request<GetHealthRequest>) -> Result<Response<GetHealthResponse>, Status> {
+        Ok(Response::new(self.health_status().into()))
+    }
+
+    async fn drain_connections(&self, request: Request<DrainRequest>) -> Result<Response<DrainResponse>, Status> {
+        let req = request.into_inner();
+        let running = self.drain.in_flight();
+        let drained_connections = self
+            .drain
+            .drain(Duration::from_secs(u64::from(req.timeout_seconds)))
+            .await;
+        if drained_connections < running {
+            warn!(
+                "drain timed out after {}s with {} batches still running",
+                req.timeout_seconds,
+                running - drained_connections
+            );
+        }
+        Ok(Response::new(DrainResponse { drained_connections }))
     }
 }
@@ -0,0 +1,51 @@
+// Stability of a portfolio's Sharpe to small weight errors (execution slippage, rounding to lots,
+// microstructure noise): a robust allocation shouldn't fall apart when its weights move a little.
+use rand_distr::{Distribution, Normal};
+
+use crate::performance::{FLOAT_COMPARISON_EPSILON, compute_portfolio_performance};
+use crate::stats::mean_and_std;
+
+// Used when EvolutionConfig.compute_stability is set, roughly a 1% weight error on each asset
+pub const DEFAULT_STABILITY_NOISE_SIGMA: f64 = 0.01;
+pub const DEFAULT_STABILITY_PERTURBATIONS: u32 = 32;
+
+/// Minus the standard deviation of the Sharpe ratio over `n_perturbations` copies of `weights`,
+/// each asset's weight shifted by N(0, `noise_sigma`) and the total rescaled back to the
+/// original one. Higher (closer to 0) is more stable. The Sharpe doesn't depend on the amount
+/// invested, so it is computed per dollar; `risk_free_rate` and `time_horizon_in_days` are the
+/// same as for `compute_portfolio_performance`.
+pub fn compute_stability_score(
+    weights: &[f64],
+    returns: &[Vec<f64>],
+    noise_sigma: f64,
+    n_perturbations: u32,
+    risk_free_rate: f64,
+    time_horizon_in_days: f64,
+) -> f64 {
+    if n_perturbations < 2 {
+        panic!(
+            "Configuration Error: need at least 2 perturbations to measure stability (found {}).",
+            n_perturbations
+        );
+    }
+    let noise = Normal::new(0.0, noise_sigma).unwrap_or_else(|_| {
+        panic!("Configuration Error: noise_sigma must be finite and >= 0 (found {}).", noise_sigma)
+    });
+
+    let mut rng = rand::rng();
+    let total_weight = weights.iter().sum::<f64>();
+    let sharpes: Vec<f64> = (0..n_perturbations)
+        .map(|_| {
+            let mut perturbed: Vec<f64> = weights.iter().map(|w| w + noise.sample(&mut rng)).collect();
+            // a market-neutral book has nothing to rescale to
+            let perturbed_total = perturbed.iter().sum::<f64>();
+            if total_weight.abs() >= FLOAT_COMPARISON_EPSILON && perturbed_total.abs() >= FLOAT_COMPARISON_EPSILON {
+                let rescale = total_weight / perturbed_total;
+                perturbed.iter_mut().for_each(|w| *w *= rescale);
+            }
+            compute_portfolio_performance(returns, &perturbed, 1.0, risk_free_rate, time_horizon_in_days).sharpe_ratio
+        })
+        .collect();
+    let (_, sharpe_std) = mean_and_std(&sharpes);
+    -sharpe_std
+}
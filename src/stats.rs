@@ -0,0 +1,388 @@
+// Distribution helpers that `rand_distr` doesn't give us (CDFs and quantiles).
+
+/// Standard normal CDF, via the Abramowitz & Stegun 7.1.26 erf approximation (|error| < 1.5e-7).
+pub fn normal_cdf(x: f64) -> f64 {
+    0.5 * (1.0 + erf(x / std::f64::consts::SQRT_2))
+}
+
+pub fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    sign * (1.0 - poly * (-x * x).exp())
+}
+
+/// Standard normal quantile (Acklam's rational approximation, relative error ~1e-9).
+/// Returns -inf/+inf at 0 and 1.
+pub fn normal_inv_cdf(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+
+    if p < P_LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -normal_inv_cdf(1.0 - p)
+    }
+}
+
+/// Linear-interpolated quantile of an already sorted slice (numpy's default "linear" method).
+pub fn sorted_quantile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let position = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = position - lower as f64;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * fraction
+}
+
+/// Mean and sample (n-1, numpy's ddof=1) standard deviation.
+pub fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    if values.is_empty() {
+        return (f64::NAN, f64::NAN);
+    }
+    let mean = values.iter().sum::<f64>() / n;
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, variance.sqrt())
+}
+
+/// Delta degrees of freedom of `sample_covariance` unless told otherwise, the unbiased estimator
+/// (and numpy.cov's default).
+pub const DEFAULT_COVARIANCE_DDOF: u32 = 1;
+
+/// Covariance matrix of `observations` (one row per observation, one column per variable),
+/// normalized by `n - ddof`. Same result as `numpy.cov(observations, rowvar=False, ddof=ddof)`,
+/// so calibrations done in a notebook carry over unchanged. NaN where `n <= ddof`.
+pub fn sample_covariance(observations: &[Vec<f64>], ddof: u32) -> Vec<Vec<f64>> {
+    let n_variables = observations.first().map_or(0, Vec::len);
+    let n = observations.len() as f64;
+    let means: Vec<f64> = (0..n_variables)
+        .map(|j| observations.iter().map(|row| row[j]).sum::<f64>() / n)
+        .collect();
+    let denominator = n - f64::from(ddof);
+
+    (0..n_variables)
+        .map(|i| {
+            (0..n_variables)
+                .map(|j| {
+                    if denominator <= 0.0 {
+                        return f64::NAN; // numpy warns and returns inf/nan here too
+                    }
+                    observations
+                        .iter()
+                        .map(|row| (row[i] - means[i]) * (row[j] - means[j]))
+                        .sum::<f64>()
+                        / denominator
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// ln Γ(x) for x > 0 (Lanczos approximation, g = 7, n = 9).
+pub fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Regularized incomplete beta I_x(a, b), continued fraction (Numerical Recipes `betacf`).
+pub fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let ln_front = ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln();
+    // The continued fraction converges quickly only below the mean, use symmetry otherwise
+    if x < (a + 1.0) / (a + b + 2.0) {
+        ln_front.exp() * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - ln_front.exp() * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 300;
+    const TINY: f64 = 1e-300;
+    let (qab, qap, qam) = (a + b, a + 1.0, a - 1.0);
+    let mut c = 1.0;
+    let mut d = 1.0 - qab * x / qap;
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        // even step
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        h *= d * c;
+        // odd step
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1.0 + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1.0 + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < 1e-14 {
+            break;
+        }
+    }
+    h
+}
+
+/// CDF of a standard Student-t with `degrees_of_freedom` (> 0).
+pub fn student_t_cdf(x: f64, degrees_of_freedom: f64) -> f64 {
+    let nu = degrees_of_freedom;
+    let tail = 0.5 * regularized_incomplete_beta(nu / (nu + x * x), 0.5 * nu, 0.5);
+    if x >= 0.0 { 1.0 - tail } else { tail }
+}
+
+/// Quantile of a standard Student-t, by bisection on `student_t_cdf` (there is no closed form).
+pub fn student_t_inv_cdf(p: f64, degrees_of_freedom: f64) -> f64 {
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+    // Widen the bracket until it contains the quantile, fat tails can put it far out
+    let mut bound = 10.0;
+    while student_t_cdf(-bound, degrees_of_freedom) > p || student_t_cdf(bound, degrees_of_freedom) < p {
+        bound *= 2.0;
+        if bound > 1e12 {
+            break;
+        }
+    }
+    let (mut low, mut high) = (-bound, bound);
+    for _ in 0..200 {
+        let mid = 0.5 * (low + high);
+        if student_t_cdf(mid, degrees_of_freedom) < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+        if high - low < 1e-12 * mid.abs().max(1.0) {
+            break;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// Welford's online mean / variance, plus the running min and max.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunningStats {
+    pub count: u64,
+    pub mean: f64,
+    pub m2: f64, // sum of squared deviations from the running mean
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for RunningStats {
+    fn default() -> Self {
+        RunningStats {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl RunningStats {
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Sample (n-1) variance, 0 below two values.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 { 0.0 } else { self.m2 / (self.count - 1) as f64 }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+}
+
+/// Online pairwise correlations of a vector-valued series (Welford-style co-moments).
+/// Co-moments are stored for the upper triangle only, diagonal included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunningCorrelation {
+    pub count: u64,
+    pub means: Vec<f64>,
+    pub co_moments: Vec<Vec<f64>>, // row i holds sum (x_i - mean_i)(x_j - mean_j) for j >= i
+}
+
+impl RunningCorrelation {
+    pub fn new(dimension: usize) -> Self {
+        RunningCorrelation {
+            count: 0,
+            means: vec![0.0; dimension],
+            co_moments: (0..dimension).map(|i| vec![0.0; dimension - i]).collect(),
+        }
+    }
+
+    pub fn push(&mut self, values: &[f64]) {
+        self.count += 1;
+        let n = self.count as f64;
+        let deltas: Vec<f64> = values.iter().zip(&self.means).map(|(x, mean)| x - mean).collect();
+        for (mean, delta) in self.means.iter_mut().zip(&deltas) {
+            *mean += delta / n;
+        }
+        // (x_i - old mean_i) * (x_j - new mean_j) keeps the update exact
+        for (i, row) in self.co_moments.iter_mut().enumerate() {
+            for (offset, co_moment) in row.iter_mut().enumerate() {
+                let j = i + offset;
+                *co_moment += deltas[i] * (values[j] - self.means[j]);
+            }
+        }
+    }
+
+    /// Correlations for i < j, flattened row by row. NaN where a series has no variance.
+    pub fn upper_triangle(&self) -> Vec<f64> {
+        let variance = |i: usize| self.co_moments[i][0];
+        self.co_moments
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| {
+                row.iter().enumerate().skip(1).map(move |(offset, co_moment)| {
+                    let denominator = (variance(i) * variance(i + offset)).sqrt();
+                    if denominator > 0.0 { co_moment / denominator } else { f64::NAN }
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // numpy.cov(np.array([[1, 2], [2, 1], [3, 5], [4, 3]]), rowvar=False, ddof=...)
+    fn observations() -> Vec<Vec<f64>> {
+        vec![vec![1.0, 2.0], vec![2.0, 1.0], vec![3.0, 5.0], vec![4.0, 3.0]]
+    }
+
+    fn assert_matrix_close(actual: &[Vec<f64>], expected: &[[f64; 2]; 2]) {
+        for (row, expected_row) in actual.iter().zip(expected) {
+            for (value, expected_value) in row.iter().zip(expected_row) {
+                assert!((value - expected_value).abs() < 1e-12, "{:?} != {:?}", actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn covariance_matches_numpy_cov() {
+        let cov = sample_covariance(&observations(), DEFAULT_COVARIANCE_DDOF);
+        let expected = [[5.0 / 3.0, 3.5 / 3.0], [3.5 / 3.0, 8.75 / 3.0]];
+        assert_matrix_close(&cov, &expected);
+    }
+
+    #[test]
+    fn covariance_honours_ddof() {
+        let cov = sample_covariance(&observations(), 0);
+        assert_matrix_close(&cov, &[[1.25, 0.875], [0.875, 2.1875]]);
+    }
+
+    #[test]
+    fn covariance_is_nan_without_enough_observations() {
+        let cov = sample_covariance(&observations(), 4);
+        assert!(cov.iter().flatten().all(|v| v.is_nan()));
+    }
+}
@@ -0,0 +1,116 @@
+// Correlation stress: during crises every asset tends to move together, which is
+// exactly when diversification is needed the most. We take a sampled scenario,
+// strip out its own correlation structure and impose an equicorrelated one instead,
+// keeping each asset's mean and volatility for the scenario untouched.
+use crate::linalg::{cholesky, matrix_multiply, solve_triangular, transpose};
+use crate::performance::{FLOAT_COMPARISON_EPSILON, PortfolioPerformance, compute_portfolio_performance};
+
+// An all-ones correlation matrix is only semi-definite, so we stop just short of it.
+const MAX_CRISIS_CORRELATION: f64 = 0.999_999;
+
+/// Correlation matrix with ones on the diagonal and `rho` everywhere else.
+pub fn crisis_correlation_matrix(n_assets: usize, rho: f64) -> Vec<Vec<f64>> {
+    (0..n_assets)
+        .map(|i| {
+            (0..n_assets)
+                .map(|j| if i == j { 1.0 } else { rho })
+                .collect()
+        })
+        .collect()
+}
+
+/// Re-correlates a `periods x assets` scenario so all off-diagonal correlations equal
+/// `crisis_correlation`. Per-asset mean and standard deviation are preserved.
+pub fn apply_correlation_stress(returns: &[Vec<f64>], crisis_correlation: f64) -> Vec<Vec<f64>> {
+    let n_periods = returns.len();
+    let n_assets = returns.first().map_or(0, Vec::len);
+    if n_periods < 2 || n_assets < 2 {
+        return returns.to_vec(); // nothing to correlate
+    }
+
+    // Equicorrelation is only PD for rho in (-1/(n-1), 1)
+    let min_correlation = -1.0 / (n_assets as f64 - 1.0) + 1e-6;
+    let rho = crisis_correlation.clamp(min_correlation, MAX_CRISIS_CORRELATION);
+
+    // --- Standardize each asset column ---
+    let means: Vec<f64> = (0..n_assets)
+        .map(|j| returns.iter().map(|row| row[j]).sum::<f64>() / n_periods as f64)
+        .collect();
+    let std_devs: Vec<f64> = (0..n_assets)
+        .map(|j| {
+            (returns.iter().map(|row| (row[j] - means[j]).powi(2)).sum::<f64>()
+                / (n_periods as f64 - 1.0))
+                .sqrt()
+        })
+        .collect();
+    let standardized: Vec<Vec<f64>> = returns
+        .iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(j, r)| {
+                    if std_devs[j] < FLOAT_COMPARISON_EPSILON {
+                        0.0
+                    } else {
+                        (r - means[j]) / std_devs[j]
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // --- Whiten with the scenario's own correlation (if it's usable) ---
+    let sample_correlation: Vec<Vec<f64>> = matrix_multiply(&transpose(&standardized), &standardized)
+        .into_iter()
+        .map(|row| row.into_iter().map(|c| c / (n_periods as f64 - 1.0)).collect())
+        .collect();
+    let whitened = match cholesky(&sample_correlation) {
+        // Z = W L^T  =>  each row w solves L w = z
+        Ok(l) => standardized
+            .iter()
+            .map(|z| solve_triangular(&l, z))
+            .collect(),
+        // Fewer periods than assets (or a constant asset): treat the columns as independent
+        Err(_) => standardized,
+    };
+
+    // --- Re-correlate with the crisis structure and undo the standardization ---
+    let crisis_l = cholesky(&crisis_correlation_matrix(n_assets, rho))
+        .expect("clamped equicorrelation matrix is positive definite");
+    matrix_multiply(&whitened, &transpose(&crisis_l))
+        .into_iter()
+        .map(|z| {
+            z.into_iter()
+                .enumerate()
+                .map(|(j, z_j)| means[j] + std_devs[j] * z_j)
+                .collect()
+        })
+        .collect()
+}
+
+/// Evaluates a portfolio on the scenario as sampled and on its crisis-correlated twin,
+/// so the diversification collapse can be read off the difference.
+pub fn compare_under_stress(
+    returns: &[Vec<f64>],
+    weights: &[f64],
+    crisis_correlation: f64,
+    money_to_invest: f64,
+    risk_free_rate: f64,
+    time_horizon_in_days: f64,
+) -> (PortfolioPerformance, PortfolioPerformance) {
+    let unstressed = compute_portfolio_performance(
+        returns,
+        weights,
+        money_to_invest,
+        risk_free_rate,
+        time_horizon_in_days,
+    );
+    let stressed = compute_portfolio_performance(
+        &apply_correlation_stress(returns, crisis_correlation),
+        weights,
+        money_to_invest,
+        risk_free_rate,
+        time_horizon_in_days,
+    );
+    (unstressed, stressed)
+}
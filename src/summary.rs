@@ -0,0 +1,70 @@
+// One-call overview of a collection of results (e.g. every iteration of one portfolio).
+use crate::performance::PortfolioPerformance;
+use crate::stats::RunningStats;
+
+/// Mean, sample std, min and max of the headline metrics. All NaN for an empty collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryStatistics {
+    pub sharpe_mean: f64,
+    pub sharpe_std: f64,
+    pub sharpe_min: f64,
+    pub sharpe_max: f64,
+    pub return_mean: f64,
+    pub return_std: f64,
+    pub return_min: f64,
+    pub return_max: f64,
+    pub volatility_mean: f64,
+    pub volatility_std: f64,
+    pub volatility_min: f64,
+    pub volatility_max: f64,
+}
+
+/// Single pass, Welford's algorithm for all three metrics at once.
+pub fn summary_statistics(performances: &[PortfolioPerformance]) -> SummaryStatistics {
+    let mut sharpe = RunningStats::default();
+    let mut annualized_return = RunningStats::default();
+    let mut volatility = RunningStats::default();
+    for perf in performances {
+        sharpe.push(perf.sharpe_ratio);
+        annualized_return.push(perf.annualized_return);
+        volatility.push(perf.percent_annualized_volatility);
+    }
+
+    // (mean, std, min, max)
+    let describe = |stats: RunningStats| {
+        if stats.count == 0 {
+            (f64::NAN, f64::NAN, f64::NAN, f64::NAN)
+        } else {
+            (stats.mean, stats.std_dev(), stats.min, stats.max)
+        }
+    };
+    let (sharpe_mean, sharpe_std, sharpe_min, sharpe_max) = describe(sharpe);
+    let (return_mean, return_std, return_min, return_max) = describe(annualized_return);
+    let (volatility_mean, volatility_std, volatility_min, volatility_max) = describe(volatility);
+
+    SummaryStatistics {
+        sharpe_mean,
+        sharpe_std,
+        sharpe_min,
+        sharpe_max,
+        return_mean,
+        return_std,
+        return_min,
+        return_max,
+        volatility_mean,
+        volatility_std,
+        volatility_min,
+        volatility_max,
+    }
+}
+
+/// `performances.summary_statistics()` on a `Vec` (or slice) of results.
+pub trait SummarizePerformance {
+    fn summary_statistics(&self) -> SummaryStatistics;
+}
+
+impl SummarizePerformance for [PortfolioPerformance] {
+    fn summary_statistics(&self) -> SummaryStatistics {
+        summary_statistics(self)
+    }
+}
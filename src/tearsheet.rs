@@ -0,0 +1,207 @@
+// pyfolio-style tear sheet: the metrics of one PortfolioPerformance grouped into sections,
+// with the ones we don't store (drawdown, Sortino, Omega, ...) derived from portfolio_returns.
+// Dollar amounts throughout, like PortfolioPerformance itself.
+use std::fmt;
+
+use crate::performance::{FLOAT_COMPARISON_EPSILON, PortfolioPerformance, VAR_CONFIDENCE};
+use crate::stats::sorted_quantile;
+
+const MONTHS_PER_YEAR: f64 = 12.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnsSection {
+    pub annualized_return: f64,
+    pub monthly_return: f64, // annualized_return / 12, same arithmetic annualization
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskSection {
+    pub annualized_volatility: f64,
+    pub value_at_risk: f64,             // one period, at VAR_CONFIDENCE, as a positive loss
+    pub conditional_value_at_risk: f64, // mean loss beyond value_at_risk
+    pub max_drawdown: f64,              // largest peak-to-trough fall of the cumulative P&L
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskAdjustedSection {
+    pub sharpe_ratio: f64,
+    pub sortino_ratio: f64, // excess return over the annualized downside deviation
+    pub calmar_ratio: f64,  // annualized return over max drawdown
+    pub omega_ratio: f64,   // gains over losses, threshold 0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistributionSection {
+    pub skewness: f64,
+    pub excess_kurtosis: f64,
+    pub tail_ratio: f64, // |95th percentile| / |5th percentile|
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TearSheet {
+    pub returns: ReturnsSection,
+    pub risk: RiskSection,
+    pub risk_adjusted: RiskAdjustedSection,
+    pub distribution: DistributionSection,
+}
+
+// NaN rather than inf when the denominator is (effectively) zero
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator.abs() < FLOAT_COMPARISON_EPSILON { f64::NAN } else { numerator / denominator }
+}
+
+impl TearSheet {
+    pub fn from_performance(perf: &PortfolioPerformance) -> TearSheet {
+        let returns = &perf.portfolio_returns;
+        let n = returns.len() as f64;
+        let mut sorted = returns.clone();
+        sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        let value_at_risk = -sorted_quantile(&sorted, 1.0 - VAR_CONFIDENCE);
+        let tail: Vec<f64> = sorted.iter().copied().filter(|ret| -ret >= value_at_risk).collect();
+        let conditional_value_at_risk = if tail.is_empty() {
+            value_at_risk
+        } else {
+            -tail.iter().sum::<f64>() / tail.len() as f64
+        };
+
+        // Returns are on constant capital, so the P&L path is their running sum
+        let (max_drawdown, _, _) = returns.iter().fold((0.0_f64, 0.0_f64, 0.0_f64), |(max_dd, peak, pnl), ret| {
+            let pnl = pnl + ret;
+            let peak = peak.max(pnl);
+            (max_dd.max(peak - pnl), peak, pnl)
+        });
+
+        let excess_return = perf.annualized_return - perf.implied_risk_free_return();
+        let downside_deviation = (returns.iter().map(|ret| ret.min(0.0).powi(2)).sum::<f64>() / n).sqrt()
+            * perf.periods_per_year.sqrt();
+        let gains = returns.iter().filter(|ret| **ret > 0.0).sum::<f64>();
+        let losses = -returns.iter().filter(|ret| **ret < 0.0).sum::<f64>();
+
+        let mean = returns.iter().sum::<f64>() / n;
+        let central_moment = |k: i32| returns.iter().map(|ret| (ret - mean).powi(k)).sum::<f64>() / n;
+        let m2 = central_moment(2);
+
+        TearSheet {
+            returns: ReturnsSection {
+                annualized_return: perf.annualized_return,
+                monthly_return: perf.annualized_return / MONTHS_PER_YEAR,
+            },
+            risk: RiskSection {
+                annualized_volatility: perf.annualized_dollar_volatility(),
+                value_at_risk,
+                conditional_value_at_risk,
+                max_drawdown,
+            },
+            risk_adjusted: RiskAdjustedSection {
+                sharpe_ratio: perf.sharpe_ratio,
+                sortino_ratio: ratio(excess_return, downside_deviation),
+                calmar_ratio: ratio(perf.annualized_return, max_drawdown),
+                omega_ratio: ratio(gains, losses),
+            },
+            distribution: DistributionSection {
+                skewness: ratio(central_moment(3), m2.powf(1.5)),
+                excess_kurtosis: ratio(central_moment(4), m2 * m2) - 3.0,
+                tail_ratio: ratio(
+                    sorted_quantile(&sorted, 0.95).abs(),
+                    sorted_quantile(&sorted, 0.05).abs(),
+                ),
+            },
+        }
+    }
+
+    fn sections(&self) -> [(&'static str, Vec<(&'static str, f64)>); 4] {
+        [
+            (
+                "returns",
+                vec![
+                    ("annualized_return", self.returns.annualized_return),
+                    ("monthly_return", self.returns.monthly_return),
+                ],
+            ),
+            (
+                "risk",
+                vec![
+                    ("annualized_volatility", self.risk.annualized_volatility),
+                    ("value_at_risk", self.risk.value_at_risk),
+                    ("conditional_value_at_risk", self.risk.conditional_value_at_risk),
+                    ("max_drawdown", self.risk.max_drawdown),
+                ],
+            ),
+            (
+                "risk_adjusted",
+                vec![
+                    ("sharpe_ratio", self.risk_adjusted.sharpe_ratio),
+                    ("sortino_ratio", self.risk_adjusted.sortino_ratio),
+                    ("calmar_ratio", self.risk_adjusted.calmar_ratio),
+                    ("omega_ratio", self.risk_adjusted.omega_ratio),
+                ],
+            ),
+            (
+                "distribution",
+                vec![
+                    ("skewness", self.distribution.skewness),
+                    ("excess_kurtosis", self.distribution.excess_kurtosis),
+                    ("tail_ratio", self.distribution.tail_ratio),
+                ],
+            ),
+        ]
+    }
+
+    /// `{"returns": {"annualized_return": ..., ...}, "risk": {...}, ...}`. NaN and infinities,
+    /// which JSON can't represent, become null.
+    pub fn to_json(&self) -> String {
+        let sections: Vec<String> = self
+            .sections()
+            .iter()
+            .map(|(section, metrics)| {
+                let fields: Vec<String> = metrics
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = if value.is_finite() { value.to_string() } else { "null".to_string() };
+                        format!("\"{}\":{}", name, value)
+                    })
+                    .collect();
+                format!("\"{}\":{{{}}}", section, fields.join(","))
+            })
+            .collect();
+        format!("{{{}}}", sections.join(","))
+    }
+}
+
+impl fmt::Display for TearSheet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (section, metrics)) in self.sections().iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "{}", section.replace('_', "-").to_uppercase())?;
+            for (name, value) in metrics {
+                writeln!(f, "  {:<28}{:>16.4}", name.replace('_', " "), value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::compute_portfolio_performance;
+
+    #[test]
+    fn sortino_with_negative_risk_free_rate() {
+        let returns: Vec<Vec<f64>> = [0.01, -0.02, 0.03, 0.0].iter().map(|simple: &f64| vec![simple.ln_1p()]).collect();
+        let money = 1_000.0;
+        let perf = compute_portfolio_performance(&returns, &[1.0], money, -0.005, 365.0);
+        let sheet = TearSheet::from_performance(&perf);
+
+        // only the -20 dollar period is downside, over 4 periods a year
+        let downside_deviation = (400.0_f64 / 4.0).sqrt() * 2.0;
+        let expected = (perf.annualized_return + 0.005 * money) / downside_deviation;
+        assert!((sheet.risk_adjusted.sortino_ratio - expected).abs() < 1e-9);
+
+        let zero_rate = TearSheet::from_performance(&compute_portfolio_performance(&returns, &[1.0], money, 0.0, 365.0));
+        assert!(sheet.risk_adjusted.sortino_ratio > zero_rate.risk_adjusted.sortino_ratio);
+    }
+}
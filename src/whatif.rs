@@ -0,0 +1,31 @@
+// "What if I move 1% from asset A to asset B?" — weight manipulation for the RunWhatIf RPC.
+
+/// `weights` with `shift_fraction` of the portfolio moved from `from_asset` to `to_asset`.
+/// The fraction is in weight units (0.01 = one percentage point) and may leave `from_asset`
+/// short, the total is unchanged either way.
+pub fn shifted_weights(
+    weights: &[f64],
+    from_asset: usize,
+    to_asset: usize,
+    shift_fraction: f64,
+) -> Result<Vec<f64>, String> {
+    if from_asset >= weights.len() || to_asset >= weights.len() {
+        return Err(format!(
+            "Shift from asset {} to asset {} is out of range for a portfolio of {} assets",
+            from_asset,
+            to_asset,
+            weights.len()
+        ));
+    }
+    if from_asset == to_asset {
+        return Err(format!("Shift from asset {} to itself does nothing", from_asset));
+    }
+    if !shift_fraction.is_finite() {
+        return Err(format!("shift_fraction must be finite (found {})", shift_fraction));
+    }
+
+    let mut shifted = weights.to_vec();
+    shifted[from_asset] -= shift_fraction;
+    shifted[to_asset] += shift_fraction;
+    Ok(shifted)
+}